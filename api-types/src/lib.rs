@@ -0,0 +1,8 @@
+//! Request and response types of node-api, shared with centric-api (and any other client) so
+//! both sides of a request are checked against the same schema at compile time.
+//!
+//! Types are grouped by API version, a breaking change to the wire format goes into a new
+//! version module while the previous one is kept around for older nodes.
+
+pub mod v1;
+pub mod v2;
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Struct representing the cumulative traffic of a single user in bytes.
+#[derive(Deserialize, Serialize, Clone, Copy, Default, Debug)]
+pub struct UserTraffic {
+    pub rx: u64,
+    pub tx: u64,
+}
+
+/// Struct representing cumulative per-user traffic and when it was last updated.
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct TrafficTotals {
+    /// Unix timestamp (seconds) of the last counter poll.
+    pub timestamp: u64,
+    pub users: HashMap<String, UserTraffic>,
+}
+
+/// Struct representing a user's traffic quota and how much of it is left, in bytes.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UserQuota {
+    pub username: String,
+    pub quota: Option<u64>,
+    pub used: u64,
+    pub remaining: Option<u64>,
+    pub exhausted: bool,
+}
@@ -0,0 +1,29 @@
+use super::UserResult;
+use serde::{Deserialize, Serialize};
+
+/// Struct representing a command recorded in the audit log of a node.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AuditEntry {
+    /// Unix timestamp (seconds) the command was handled at.
+    pub timestamp: u64,
+    /// Subject of the token the command was sent with, if it had one.
+    pub subject: Option<String>,
+    /// Route of the command, e.g. `/api/cmd/userlock`.
+    pub route: String,
+    /// Account the command applied to, missing for commands not targeting a single account
+    /// and bulk commands that selected none.
+    pub username: Option<String>,
+    /// Body of the command, with passwords redacted.
+    pub args: serde_json::Value,
+    /// Whether the command was only validated.
+    pub dry_run: bool,
+    /// Outcome of the command, missing if it was refused.
+    #[serde(default)]
+    pub result: Option<UserResult<()>>,
+    /// Why the node refused the command without running it (invalid token, bad parameters).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refused: Option<String>,
+    /// Whether the change was reverted after another account of the same bulk command failed.
+    #[serde(default)]
+    pub rolled_back: bool,
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+/// Struct representing the claims of the API tokens centric-api mints for every node request.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Claims {
+    /// ID the node is registered with in centric-api.
+    pub aud: String,
+    pub exp: u64,
+    /// Space separated scopes granted by the token.
+    pub scope: String,
+    /// Who the token was minted for (e.g. `centric-api`), recorded in the audit log.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+}
+
+impl Claims {
+    /// Creates new claims for the given node, granting the given scopes until `exp`.
+    pub fn new(node_id: impl ToString, exp: u64, scopes: &[Scope]) -> Claims {
+        Claims {
+            aud: node_id.to_string(),
+            exp,
+            scope: scopes
+                .iter()
+                .map(Scope::as_str)
+                .collect::<Vec<_>>()
+                .join(" "),
+            sub: None,
+        }
+    }
+
+    /// Sets the subject of the claims.
+    pub fn with_subject(mut self, sub: impl Into<String>) -> Claims {
+        self.sub = Some(sub.into());
+        self
+    }
+
+    /// Checks whether the claims grant the given scope.
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scope.split_whitespace().any(|s| s == scope.as_str())
+    }
+}
+
+/// Enum representing the scopes routes require.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// Hardware and network statistics.
+    StatsRead,
+    /// Accounts, their usage and sessions.
+    UsersRead,
+    /// Every command changing accounts or sessions.
+    UsersWrite,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::StatsRead => "stats:read",
+            Scope::UsersRead => "users:read",
+            Scope::UsersWrite => "users:write",
+        }
+    }
+}
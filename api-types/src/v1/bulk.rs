@@ -0,0 +1,87 @@
+use super::requests::{InputSSHUser, UserLookupParams};
+use super::UserResult;
+use serde::{Deserialize, Serialize};
+
+/// Enum representing the accounts a bulk command applies to, either listed by username or
+/// selected by prefix or group.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum UserSelection {
+    Usernames(Vec<String>),
+    Lookup(UserLookupParams),
+}
+
+/// Struct representing a bulk command only taking the accounts, e.g. `userlock`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BulkUsers {
+    pub users: UserSelection,
+    /// Reverts the accounts already changed as soon as one fails.
+    #[serde(default)]
+    pub rollback: bool,
+}
+
+/// Struct representing a bulk expiry date change.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BulkUserExpDate {
+    pub users: UserSelection,
+    pub exp_date: String,
+    #[serde(default)]
+    pub rollback: bool,
+}
+
+/// Struct representing a bulk group change.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BulkUserGrp {
+    pub users: UserSelection,
+    pub group: String,
+    #[serde(default)]
+    pub rollback: bool,
+}
+
+/// Struct representing the creation of several accounts.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BulkUseradd {
+    pub users: Vec<InputSSHUser>,
+    #[serde(default)]
+    pub rollback: bool,
+}
+
+/// Struct representing the creation of `count` accounts named after the prefix, numbered
+/// from `users_count + 1`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BulkAutoUseradd {
+    pub prefix: String,
+    pub users_count: u64,
+    pub count: u64,
+    pub exp_date: String,
+    pub group: String,
+    /// Traffic quota in bytes of every account.
+    pub traffic_quota: Option<u64>,
+    /// Maximum concurrent SSH sessions of every account.
+    pub max_logins: Option<u32>,
+    #[serde(default)]
+    pub rollback: bool,
+}
+
+/// Struct representing the outcome of a bulk command for a single account.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BulkItem<T> {
+    pub username: String,
+    pub result: UserResult<T>,
+}
+
+/// Struct representing the outcome of a bulk command, in the order the accounts were
+/// processed.
+///
+/// With `rollback` set, processing stops at the first failure and the accounts changed before
+/// it are reverted, `results` then ends with the failure.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct BulkResult<T> {
+    pub results: Vec<BulkItem<T>>,
+    pub rolled_back: bool,
+    /// Accounts whose change couldn't be reverted.
+    pub rollback_failed: Vec<String>,
+    /// Accounts only partially reverted, e.g. deleted accounts recreated under a new uid.
+    #[serde(default)]
+    pub partially_restored: Vec<String>,
+}
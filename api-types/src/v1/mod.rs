@@ -0,0 +1,64 @@
+//! First version of the node-api wire format.
+
+use serde::{Deserialize, Serialize};
+
+pub mod accounting;
+pub mod audit;
+pub mod auth;
+pub mod bulk;
+pub mod node;
+pub mod requests;
+pub mod sessions;
+pub mod stats;
+pub mod users;
+
+use self::users::UserErrors;
+
+/// Version of the wire format described by this module.
+pub const VERSION: &str = "v1";
+
+/// Result of a command applied to an account, serialized as `{"Ok": ...}` or
+/// `{"Err": "<UserErrors variant>"}`.
+pub type UserResult<T> = Result<T, UserErrors>;
+
+/// Struct representing the body node-api answers with when it refuses a request, e.g. for an
+/// invalid token or missing parameters: `{"Err": "Missing scope stats:read"}`.
+///
+/// The message is required and no other field is allowed, so a response that doesn't match
+/// the route's type isn't mistaken for a refusal.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Refusal {
+    #[serde(rename = "Err")]
+    pub err: String,
+}
+
+impl Refusal {
+    /// Creates a new `Refusal` with the given message.
+    pub fn new(msg: impl Into<String>) -> Refusal {
+        Refusal { err: msg.into() }
+    }
+
+    /// Returns the message of the refusal.
+    pub fn msg(&self) -> &str {
+        &self.err
+    }
+}
+
+/// Enum representing any reply of node-api: the response of the route, or a refusal.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Reply<T> {
+    Ok(T),
+    Refused(Refusal),
+}
+
+impl<T> Reply<T> {
+    /// Converts the reply to a `Result`.
+    pub fn into_result(self) -> Result<T, Refusal> {
+        match self {
+            Reply::Ok(value) => Ok(value),
+            Reply::Refused(refusal) => Err(refusal),
+        }
+    }
+}
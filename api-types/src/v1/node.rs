@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// Struct representing information about an SSH management node.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct NodeInfo {
+    pub name: String,
+    pub location: String,
+    /// Number of accounts the node is meant to hold.
+    pub capacity: Option<u64>,
+}
+
+/// Struct representing the request a node enrolls itself in centric-api with.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct EnrollRequest {
+    /// One-time code created by an admin in centric-api.
+    pub code: String,
+    /// Address centric-api reaches the node at, e.g. `https://node01.example.com:8010`.
+    pub address: String,
+    /// SHA-256 fingerprint of the node's TLS certificate, pinned for the node.
+    pub tls_fingerprint: Option<String>,
+    pub node_info: NodeInfo,
+}
+
+/// Struct representing the credentials an enrolled node verifies API tokens with.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct EnrolledNode {
+    /// ID of the node, the audience of its API tokens.
+    pub node_id: i32,
+    pub jwt_secret: String,
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+
+/// Response header node-api echoes the `dry_run` flag of a command in, `true` or `false`.
+pub const DRY_RUN_HEADER: &str = "Sshmgmt-Dry-Run";
+
+/// Struct representing the body of a command: its parameters, along with whether it should
+/// only be validated and reported on without changing anything.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Command<T> {
+    #[serde(flatten)]
+    pub params: T,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Struct representing parameters for user lookup.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct UserLookupParams {
+    pub username: Option<String>,
+    pub prefix: Option<String>,
+    pub group: Option<String>,
+}
+
+/// Struct representing user expiration date.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UserExpDate {
+    pub username: String,
+    pub exp_date: String,
+}
+
+/// Struct representing user password.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UserPasswd {
+    pub username: String,
+    pub password: String,
+}
+
+/// Struct representing user group.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UserGrp {
+    pub username: String,
+    pub group: String,
+}
+
+/// Struct representing the traffic quota (bytes) and concurrent login limit of a user.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct UserLimits {
+    pub username: String,
+    pub traffic_quota: Option<u64>,
+    pub max_logins: Option<u32>,
+}
+
+/// Struct representing only username.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct OnlyUser {
+    pub username: String,
+}
+
+/// Struct representing the sessions to kick, either all of a user's or a single one by PID.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct KickParams {
+    pub username: Option<String>,
+    pub pid: Option<u32>,
+}
+
+/// Struct representing auto-generated SSH user.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AutoSSHUser {
+    pub prefix: String,
+    pub users_count: u64,
+    pub exp_date: String,
+    pub group: String,
+    /// Traffic quota in bytes, the account gets locked once it's used up.
+    pub traffic_quota: Option<u64>,
+    /// Maximum concurrent SSH sessions, newer sessions over it get killed.
+    pub max_logins: Option<u32>,
+}
+
+/// Struct representing input for SSH user.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct InputSSHUser {
+    pub username: String,
+    pub password: String,
+    pub exp_date: String,
+    pub group: String,
+    pub shell: Option<String>,
+    /// Traffic quota in bytes, the account gets locked once it's used up.
+    pub traffic_quota: Option<u64>,
+    /// Maximum concurrent SSH sessions, newer sessions over it get killed.
+    pub max_logins: Option<u32>,
+}
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+/// Struct representing a live SSH session, backed by the per-connection `sshd` process of a user.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Session {
+    pub username: String,
+    pub pid: u32,
+    pub source_ip: Option<String>,
+    /// Unix timestamp (seconds) the session process started at.
+    pub started_at: Option<u64>,
+    /// Bytes read by the session process (client and channel traffic), from `/proc/<pid>/io`.
+    pub io_read: Option<u64>,
+    /// Bytes written by the session process (client and channel traffic), from `/proc/<pid>/io`.
+    pub io_written: Option<u64>,
+}
+
+/// Struct representing the sessions terminated by a kick.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct KickedSessions {
+    pub killed_pids: Vec<u32>,
+    pub failed_pids: Vec<u32>,
+}
+
+/// Struct representing a user found over their concurrent login limit.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct LoginViolation {
+    pub username: String,
+    pub max_logins: u32,
+    pub sessions: usize,
+    pub killed_pids: Vec<u32>,
+    pub timestamp: u64,
+}
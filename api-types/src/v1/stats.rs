@@ -0,0 +1,158 @@
+use bytesize::ByteSize;
+use serde::{Deserialize, Serialize};
+
+/// Struct representing network usage information for a specific interface.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct NetworkUsage {
+    pub interface: String,
+    pub tx: String,
+    pub rx: String,
+    pub total: String,
+    /// Raw counters behind `tx` and `rx`, missing (0) from older nodes.
+    #[serde(default)]
+    pub tx_bytes: u64,
+    #[serde(default)]
+    pub rx_bytes: u64,
+}
+
+impl NetworkUsage {
+    /// Creates a new NetworkUsage instance with calculated total data.
+    pub fn new(interface: String, tx: u64, rx: u64) -> NetworkUsage {
+        let total: u64 = tx + rx;
+
+        NetworkUsage {
+            interface,
+            tx: ByteSize(tx).to_string(),
+            rx: ByteSize(rx).to_string(),
+            total: ByteSize(total).to_string(),
+            tx_bytes: tx,
+            rx_bytes: rx,
+        }
+    }
+}
+
+/// Represents information about system uptime.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UptimeInfo {
+    pub seconds: u64,
+    pub pretty: String,
+}
+
+impl UptimeInfo {
+    /// Creates a new `UptimeInfo` instance based on the given number of seconds.
+    pub fn new(seconds: u64) -> UptimeInfo {
+        UptimeInfo {
+            seconds,
+            pretty: UptimeInfo::seconds_to_pretty(seconds),
+        }
+    }
+
+    /// Converts a duration in seconds to a human-readable string format.
+    fn seconds_to_pretty(seconds: u64) -> String {
+        let mut time_string = String::new();
+        let mut _remaining = seconds;
+
+        //up 4 days, 15 hours, 22 minutes
+        let months: u64 = _remaining / 2592000;
+        _remaining -= months * 2592000;
+
+        let weeks: u64 = _remaining / 604800;
+        _remaining -= weeks / 604800;
+
+        let days: u64 = _remaining / 86400;
+        _remaining -= days * 86400;
+
+        let hours: u64 = _remaining / 3600;
+        _remaining -= hours * 3600;
+
+        let minutes: u64 = _remaining / 60;
+        _remaining -= minutes * 60;
+
+        if months > 0 {
+            time_string.push_str(&format!("{months} months "));
+        }
+        if weeks > 0 {
+            time_string.push_str(&format!("{weeks} weeks "));
+        }
+        if days > 0 {
+            time_string.push_str(&format!("{days} days "));
+        }
+        if hours > 0 {
+            time_string.push_str(&format!("{hours} hours "));
+        }
+        if minutes > 0 {
+            time_string.push_str(&format!("{minutes} minutes"));
+        }
+
+        time_string
+    }
+}
+
+/// Represents information about memory usage.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MemInfo {
+    pub used: u64,
+    pub free: u64,
+    pub total: u64,
+    pub pretty: String,
+}
+
+impl MemInfo {
+    /// Creates a new `MemInfo` instance based on total and free memory values.
+    pub fn new(total: u64, free: u64) -> MemInfo {
+        let used = total - free;
+        let pretty = format!(
+            "{}/{} ({})",
+            ByteSize(total - free),
+            ByteSize(total),
+            ByteSize(free)
+        );
+        MemInfo {
+            used,
+            free,
+            total,
+            pretty,
+        }
+    }
+}
+
+/// Represents information about disk usage.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct DiskInfo {
+    pub mount_point: String,
+    pub used: u64,
+    pub free: u64,
+    pub total: u64,
+    pub pretty: String,
+}
+
+impl DiskInfo {
+    /// Creates a new `DiskInfo` instance based on mount point, total, and free disk space values.
+    pub fn new(mount_point: String, total: u64, free: u64) -> DiskInfo {
+        let used = total - free;
+        let pretty = format!(
+            "{}: {}/{} ({})",
+            mount_point,
+            ByteSize(total - free),
+            ByteSize(total),
+            ByteSize(free)
+        );
+        DiskInfo {
+            mount_point,
+            used,
+            free,
+            total,
+            pretty,
+        }
+    }
+}
+
+/// Represents hardware usage information including CPU load, memory usage, swap usage, disk info, and uptime.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct HwUsage {
+    pub cpu_load: (f32, f32, f32),
+    pub memory_usage: MemInfo,
+    pub swap_usage: MemInfo,
+    pub disk_info: DiskInfo,
+    pub uptime: UptimeInfo,
+}
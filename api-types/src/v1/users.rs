@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+
+/// Struct representing an SSH user as created by `useradd` and `auto_useradd`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SSHUser {
+    pub username: String,
+    pub password_hash: String,
+    pub shell: String,
+    pub usergroup: String,
+    pub exp_date: String,
+}
+
+/// Struct representing the account details of an SSH user.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SSHUserInfo {
+    pub username: String,
+    pub userid: u32,
+    pub usergroup: Option<(u32, String)>,
+    pub exp_date: String,
+    pub locked: bool,
+    pub last_change: Option<String>,
+    pub min_age: Option<i64>,
+    pub max_age: Option<i64>,
+    pub inactivity: Option<i64>,
+    /// Algorithm of the password hash (e.g. `sha512-crypt`, `yescrypt`).
+    pub hash_method: Option<String>,
+}
+
+/// Struct representing the new password hash of a user.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UserPasswordHash {
+    pub username: String,
+    pub password_hash: String,
+}
+
+/// Struct representing the credentials of a user right after a password reset.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UserRawCreds {
+    pub username: String,
+    pub password: String,
+    pub password_hash: String,
+}
+
+/// Struct representing the expiry date of a user.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UserExp {
+    pub username: String,
+    pub exp_date: String,
+}
+
+/// Struct representing the outcome of a command applied to a user, e.g. `locked`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct UserStatus {
+    pub username: String,
+    pub status: String,
+}
+
+/// Struct representing the outcome of an expiry date change.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ChExpMsg {
+    pub username: String,
+    pub exp_date: String,
+    pub message: String,
+}
+
+/// Struct representing the outcome of a group change.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ChGrpMsg {
+    pub username: String,
+    pub group: String,
+    pub message: String,
+}
+
+/// Enum representing the errors of commands applied to accounts.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UserErrors {
+    UserAlreadyExists,
+    InvalidUserOrGroup,
+    InvalidShell,
+    InvalidExpDate,
+    InvalidPasswordHash,
+    PermissionDenied,
+    UnexpectedError,
+    ProcessTerminated,
+    CannotDeleteYourSelf,
+    InvalidTraceFile,
+    CommandNotFound,
+    SessionNotFound,
+    /// The shadow file doesn't exist.
+    ShadowNotFound,
+    /// A login limit of zero, which would kill every session.
+    InvalidLoginLimit,
+}
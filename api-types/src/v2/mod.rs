@@ -0,0 +1,8 @@
+//! Second version of the node-api wire format, served under `/api/v2`.
+//!
+//! Only the routes that changed are redefined here, the others keep their `v1` types.
+
+pub mod stats;
+
+/// Version of the wire format described by this module.
+pub const VERSION: &str = "v2";
@@ -0,0 +1,196 @@
+use crate::v1::stats::UptimeInfo;
+use bytesize::ByteSize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Struct representing the counters of a network interface, read from
+/// `/sys/class/net/<interface>/statistics`.
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct InterfaceStats {
+    pub interface: String,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+    /// Bytes per second over the node's last sampling window, missing until two samples were
+    /// taken or when the counters were reset in between.
+    pub rx_rate: Option<f64>,
+    pub tx_rate: Option<f64>,
+    /// Human-readable summary, only sent when asked for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pretty: Option<String>,
+}
+
+impl InterfaceStats {
+    /// Adds the human-readable summary, e.g. `rx 1.2 GB (3.4 KB/s), tx 56.7 MB (890 B/s)`.
+    pub fn with_pretty(mut self) -> InterfaceStats {
+        let rate = |rate: Option<f64>| match rate {
+            Some(rate) => format!(" ({}/s)", ByteSize(rate as u64)),
+            None => String::new(),
+        };
+
+        self.pretty = Some(format!(
+            "rx {}{}, tx {}{}",
+            ByteSize(self.rx_bytes),
+            rate(self.rx_rate),
+            ByteSize(self.tx_bytes),
+            rate(self.tx_rate)
+        ));
+        self
+    }
+}
+
+/// Struct representing the network statistics of a node.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct NetStats {
+    /// Seconds between the two samples rates were computed from, missing until two samples
+    /// were taken.
+    pub rate_window: Option<f64>,
+    pub interfaces: Vec<InterfaceStats>,
+}
+
+/// Struct representing the 1, 5 and 15 minutes load averages.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct LoadAverage {
+    pub one: f32,
+    pub five: f32,
+    pub fifteen: f32,
+}
+
+/// Struct representing memory or swap usage in bytes.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MemoryStats {
+    pub total: u64,
+    pub free: u64,
+    pub used: u64,
+    /// Human-readable summary, only sent when asked for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pretty: Option<String>,
+}
+
+impl MemoryStats {
+    /// Creates a new `MemoryStats` from total and free bytes.
+    pub fn new(total: u64, free: u64) -> MemoryStats {
+        MemoryStats {
+            total,
+            free,
+            used: total.saturating_sub(free),
+            pretty: None,
+        }
+    }
+
+    /// Adds the human-readable summary, e.g. `1.2 GB/4.0 GB (2.8 GB free)`.
+    pub fn with_pretty(mut self) -> MemoryStats {
+        self.pretty = Some(format!(
+            "{}/{} ({} free)",
+            ByteSize(self.used),
+            ByteSize(self.total),
+            ByteSize(self.free)
+        ));
+        self
+    }
+}
+
+/// Struct representing the usage of a mounted filesystem in bytes.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct FilesystemStats {
+    pub mount_point: String,
+    /// Device or source the filesystem is mounted from.
+    pub device: String,
+    pub fs_type: String,
+    pub total: u64,
+    pub free: u64,
+    /// Free bytes available to unprivileged users.
+    pub avail: u64,
+    pub used: u64,
+    pub files_total: u64,
+    pub files_free: u64,
+    /// Human-readable summary, only sent when asked for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pretty: Option<String>,
+}
+
+impl FilesystemStats {
+    /// Adds the human-readable summary, e.g. `/: 12.3 GB/40.0 GB (27.7 GB free)`.
+    pub fn with_pretty(mut self) -> FilesystemStats {
+        self.pretty = Some(format!(
+            "{}: {}/{} ({} free)",
+            self.mount_point,
+            ByteSize(self.used),
+            ByteSize(self.total),
+            ByteSize(self.avail)
+        ));
+        self
+    }
+}
+
+/// Struct representing the hardware statistics of a node.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct HwStats {
+    pub load: LoadAverage,
+    pub memory: MemoryStats,
+    pub swap: MemoryStats,
+    /// Every mounted filesystem with a size, pseudo filesystems like `proc` are left out.
+    pub filesystems: Vec<FilesystemStats>,
+    /// Uptime in seconds.
+    pub uptime: u64,
+    /// Human-readable uptime, only sent when asked for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uptime_pretty: Option<String>,
+}
+
+impl HwStats {
+    /// Adds the human-readable summaries of every field.
+    pub fn with_pretty(self) -> HwStats {
+        HwStats {
+            memory: self.memory.with_pretty(),
+            swap: self.swap.with_pretty(),
+            filesystems: self
+                .filesystems
+                .into_iter()
+                .map(FilesystemStats::with_pretty)
+                .collect(),
+            uptime_pretty: Some(UptimeInfo::new(self.uptime).pretty),
+            ..self
+        }
+    }
+}
+
+/// Struct representing the utilisation of a CPU core.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct CpuStats {
+    /// Index of the core, as numbered in `/proc/stat`.
+    pub core: usize,
+    /// Percentage of time the core was busy over the node's last sampling window, missing
+    /// until two samples were taken.
+    pub usage: Option<f32>,
+}
+
+/// Struct representing the CPU, process, connection and account load of a node, to be
+/// weighed against its capacity.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SystemStats {
+    /// Percentage of time all cores together were busy over the node's last sampling window,
+    /// missing until two samples were taken.
+    pub cpu_usage: Option<f32>,
+    pub cpus: Vec<CpuStats>,
+    /// CPU temperature in degrees Celsius, missing when the node doesn't expose one.
+    pub cpu_temperature: Option<f32>,
+    pub processes: u64,
+    /// `sshd` processes, the listener included.
+    pub sshd_processes: u64,
+    /// Open IPv4 and IPv6 TCP sockets by state, e.g. `ESTABLISHED` or `LISTEN`.
+    pub tcp_connections: BTreeMap<String, u64>,
+    /// Kernel release, e.g. `6.1.0-18-amd64`.
+    pub kernel: String,
+    /// Name of the operating system from `os-release`, e.g. `Debian GNU/Linux 12 (bookworm)`.
+    pub os: Option<String>,
+    /// Number of accounts the node is meant to hold, from its `NodeInfo`.
+    pub capacity: Option<u64>,
+    /// Accounts managed on the node.
+    pub accounts: u64,
+}
@@ -0,0 +1,28 @@
+use api_types::v1::users::UserStatus;
+use api_types::v1::{Reply, UserResult};
+
+#[test]
+fn refusals_decode() {
+    let reply: Reply<UserStatus> =
+        serde_json::from_str(r#"{"Err": "Missing scope users:write"}"#).unwrap();
+
+    assert_eq!(
+        reply.into_result().unwrap_err().msg(),
+        "Missing scope users:write"
+    );
+}
+
+#[test]
+fn mismatched_responses_fail_to_decode() {
+    assert!(serde_json::from_str::<Reply<UserStatus>>(r#"{"unexpected": 1}"#).is_err());
+    assert!(serde_json::from_str::<Reply<UserStatus>>(r#"{"Err": null}"#).is_err());
+    assert!(serde_json::from_str::<Reply<UserStatus>>(r#"{"Err": "x", "other": 1}"#).is_err());
+}
+
+#[test]
+fn command_errors_are_not_refusals() {
+    let reply: Reply<UserResult<UserStatus>> =
+        serde_json::from_str(r#"{"Err": "InvalidUserOrGroup"}"#).unwrap();
+
+    assert!(matches!(reply, Reply::Ok(Err(_))));
+}
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+
+CREATE TABLE expiry_log (
+  id SERIAL PRIMARY KEY,
+  sell_id INTEGER NOT NULL REFERENCES sells(id),
+  node_id INTEGER NOT NULL,
+  username TEXT NOT NULL,
+  action TEXT NOT NULL,
+  dry_run BOOLEAN NOT NULL,
+  result TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+)
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+
+CREATE TABLE renewals (
+  id SERIAL PRIMARY KEY,
+  sell_id INTEGER NOT NULL REFERENCES sells(id),
+  service_id INTEGER NOT NULL,
+  days INTEGER NOT NULL,
+  price INTEGER NOT NULL,
+  previous_invoice_date TIMESTAMP,
+  invoice_date TIMESTAMP NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+)
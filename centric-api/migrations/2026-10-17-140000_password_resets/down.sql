@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE sells DROP COLUMN password_reset_at;
+ALTER TABLE sells DROP COLUMN password_resets;
+ALTER TABLE sells ADD COLUMN password TEXT;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+
+ALTER TABLE sells DROP COLUMN password;
+ALTER TABLE sells ADD COLUMN password_resets INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE sells ADD COLUMN password_reset_at TIMESTAMP;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+UPDATE nodes SET jwt_secret = '' WHERE jwt_secret IS NULL;
+ALTER TABLE nodes ALTER COLUMN jwt_secret SET NOT NULL;
+ALTER TABLE nodes RENAME COLUMN jwt_secret TO token;
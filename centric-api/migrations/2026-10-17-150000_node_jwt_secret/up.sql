@@ -0,0 +1,7 @@
+-- Your SQL goes here
+
+-- Old tokens were sent to nodes as bearer tokens, they can't become signing keys: every node
+-- has to be given a new key with /nodes/update_node before it can be reached again
+ALTER TABLE nodes RENAME COLUMN token TO jwt_secret;
+ALTER TABLE nodes ALTER COLUMN jwt_secret DROP NOT NULL;
+UPDATE nodes SET jwt_secret = NULL;
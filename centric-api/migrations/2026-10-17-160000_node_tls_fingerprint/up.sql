@@ -0,0 +1,3 @@
+-- Your SQL goes here
+
+ALTER TABLE nodes ADD COLUMN tls_fingerprint TEXT;
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE enrollment_codes;
+
+ALTER TABLE nodes DROP COLUMN capacity;
+ALTER TABLE nodes DROP COLUMN location;
+ALTER TABLE nodes DROP COLUMN name;
+ALTER TABLE nodes DROP COLUMN approved;
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+ALTER TABLE nodes ADD COLUMN approved BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE nodes ADD COLUMN name TEXT;
+ALTER TABLE nodes ADD COLUMN location TEXT;
+ALTER TABLE nodes ADD COLUMN capacity BIGINT;
+
+CREATE TABLE enrollment_codes (
+  id SERIAL PRIMARY KEY,
+  code_hash TEXT NOT NULL UNIQUE,
+  expires_at TIMESTAMP NOT NULL,
+  used_at TIMESTAMP,
+  node_id INTEGER REFERENCES nodes(id) ON DELETE SET NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+)
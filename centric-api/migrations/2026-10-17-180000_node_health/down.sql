@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE nodes DROP COLUMN latency_ms;
+ALTER TABLE nodes DROP COLUMN last_seen;
+ALTER TABLE nodes DROP CONSTRAINT nodes_status_check;
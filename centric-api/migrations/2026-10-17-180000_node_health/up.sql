@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+UPDATE nodes SET status = 0 WHERE status NOT IN (0, 1, 2);
+ALTER TABLE nodes ADD CONSTRAINT nodes_status_check CHECK (status IN (0, 1, 2));
+ALTER TABLE nodes ADD COLUMN last_seen TIMESTAMP;
+ALTER TABLE nodes ADD COLUMN latency_ms INTEGER;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE node_net_metrics;
+DROP TABLE node_metrics
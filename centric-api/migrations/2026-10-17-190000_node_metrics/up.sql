@@ -0,0 +1,31 @@
+-- Your SQL goes here
+
+CREATE TABLE node_metrics (
+  id SERIAL PRIMARY KEY,
+  node_id INTEGER NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+  resolution INTEGER NOT NULL,
+  sampled_at TIMESTAMP NOT NULL,
+  load_one REAL NOT NULL,
+  load_five REAL NOT NULL,
+  load_fifteen REAL NOT NULL,
+  memory_used BIGINT NOT NULL,
+  memory_total BIGINT NOT NULL,
+  swap_used BIGINT NOT NULL,
+  swap_total BIGINT NOT NULL,
+  disk_used BIGINT NOT NULL,
+  disk_total BIGINT NOT NULL
+);
+
+CREATE INDEX node_metrics_node_id_sampled_at ON node_metrics (node_id, sampled_at);
+
+CREATE TABLE node_net_metrics (
+  id SERIAL PRIMARY KEY,
+  node_id INTEGER NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+  resolution INTEGER NOT NULL,
+  sampled_at TIMESTAMP NOT NULL,
+  interface TEXT NOT NULL,
+  rx BIGINT NOT NULL,
+  tx BIGINT NOT NULL
+);
+
+CREATE INDEX node_net_metrics_node_id_sampled_at ON node_net_metrics (node_id, sampled_at)
@@ -0,0 +1,228 @@
+use crate::models::{ApiError, Node, NodeStatus};
+use crate::node_tls::NodeTls;
+use crate::{consts, Db};
+use node_client::{NodeClient, RetryPolicy};
+use rocket::fairing::{AdHoc, Fairing};
+use rocket::serde::Deserialize;
+use rocket::tokio::task::JoinSet;
+use rocket::tokio::time::{interval, Duration, Instant};
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+/// Structure representing the `health_monitor` section of Rocket.toml
+#[derive(Deserialize, Clone, Debug)]
+#[serde(crate = "rocket::serde", default)]
+pub struct HealthConfig {
+    /// Whether the background health monitor runs at all
+    pub enabled: bool,
+    /// Seconds between two checks of every node
+    pub interval: u64,
+    /// Seconds a ping or statistics request may take
+    pub timeout: u64,
+    /// Pings slower than this many milliseconds mark the node as degraded
+    pub degraded_latency_ms: u64,
+    /// Consecutive checks needed to move a node to a worse status
+    pub down_after: u32,
+    /// Consecutive checks needed to move a node back to a better status
+    pub up_after: u32,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig {
+            enabled: true,
+            interval: 30,
+            timeout: 5,
+            degraded_latency_ms: 1000,
+            down_after: 3,
+            up_after: 2,
+        }
+    }
+}
+
+/// Structure representing the outcome of a single check of a node
+#[derive(Clone, Copy, Debug)]
+pub struct Probe {
+    pub status: NodeStatus,
+    pub latency_ms: Option<i32>,
+}
+
+/// Structure tracking, per node, how many consecutive checks disagreed with its status
+#[derive(Default)]
+pub struct Hysteresis {
+    pending: HashMap<i32, (NodeStatus, u32)>,
+}
+
+impl Hysteresis {
+    /// Returns the status a node should have once a check observed `observed`, which only
+    /// replaces `current` after `down_after` (or `up_after`) consecutive checks agreeing on it
+    pub fn next(
+        &mut self,
+        node_id: i32,
+        current: NodeStatus,
+        observed: NodeStatus,
+        config: &HealthConfig,
+    ) -> NodeStatus {
+        if observed == current {
+            self.pending.remove(&node_id);
+            return current;
+        }
+
+        let (pending, count) = self.pending.entry(node_id).or_insert((observed, 0));
+        if *pending != observed {
+            *pending = observed;
+            *count = 0;
+        }
+        *count += 1;
+
+        let needed = if observed > current {
+            config.down_after
+        } else {
+            config.up_after
+        };
+
+        if *count >= needed {
+            self.pending.remove(&node_id);
+            observed
+        } else {
+            current
+        }
+    }
+}
+
+/// Asynchronously pings a node, then fetches its hardware statistics
+///
+/// Nodes that don't answer the ping are offline, nodes answering slowly or without
+/// statistics are degraded.
+pub async fn probe(client: Option<NodeClient>, degraded_latency: Duration) -> Probe {
+    let offline = Probe {
+        status: NodeStatus::Offline,
+        latency_ms: None,
+    };
+
+    let Some(client) = client else {
+        return offline;
+    };
+
+    let started = Instant::now();
+    if client.ping().await.is_err() {
+        return offline;
+    }
+    let latency = started.elapsed();
+
+    let status = if latency > degraded_latency || client.hw_stats().await.is_err() {
+        NodeStatus::Degraded
+    } else {
+        NodeStatus::Online
+    };
+
+    Probe {
+        status,
+        latency_ms: Some(latency.as_millis().min(i32::MAX as u128) as i32),
+    }
+}
+
+/// Asynchronously checks every approved node at once and records their latency, last-seen
+/// time and status
+pub async fn check(
+    db: &Db,
+    tls: &NodeTls,
+    config: &HealthConfig,
+    hysteresis: &mut Hysteresis,
+) -> Result<(), ApiError> {
+    let mut probes = JoinSet::new();
+
+    for node in Node::list(db).await? {
+        if !node.approved {
+            continue;
+        }
+
+        let client = node
+            .client(tls, consts::NODE_TOKEN_SUBJECT)
+            .ok()
+            .map(|client| {
+                client
+                    .with_timeout(Duration::from_secs(config.timeout))
+                    .with_retry(RetryPolicy::none())
+            });
+        let degraded_latency = Duration::from_millis(config.degraded_latency_ms);
+
+        probes.spawn(async move { (node, probe(client, degraded_latency).await) });
+    }
+
+    while let Some(joined) = probes.join_next().await {
+        let Ok((node, probe)) = joined else {
+            continue;
+        };
+
+        let status = hysteresis.next(node.id, node.status, probe.status, config);
+        if status != node.status {
+            eprintln!(
+                "health monitor: node {} is now {} (was {})",
+                node.id,
+                status.as_str(),
+                node.status.as_str()
+            );
+        }
+
+        let last_seen = probe.latency_ms.map(|_| SystemTime::now());
+        Node::record_health(db, node.id, status, probe.latency_ms, last_seen).await?;
+    }
+
+    Ok(())
+}
+
+/// Fairing managing the health monitor config and spawning the background monitor once
+/// rocket has lifted off
+pub fn fairing() -> impl Fairing {
+    AdHoc::on_ignite("Health Monitor Config", |rocket| async {
+        let mut config: HealthConfig = rocket
+            .figment()
+            .extract_inner("health_monitor")
+            .unwrap_or_default();
+        // A zero period would make the ticker panic
+        config.interval = config.interval.max(1);
+
+        rocket.manage(config).attach(liftoff())
+    })
+}
+
+fn liftoff() -> impl Fairing {
+    AdHoc::on_liftoff("Health Monitor", |rocket| {
+        Box::pin(async move {
+            let (Some(config), Some(tls)) = (
+                rocket.state::<HealthConfig>().cloned(),
+                rocket.state::<NodeTls>().cloned(),
+            ) else {
+                return;
+            };
+
+            if !config.enabled {
+                return;
+            }
+
+            let Some(pool) = Db::pool(rocket).cloned() else {
+                eprintln!("health monitor: database pool is not available");
+                return;
+            };
+
+            rocket::tokio::spawn(async move {
+                let mut ticker = interval(Duration::from_secs(config.interval));
+                let mut hysteresis = Hysteresis::default();
+
+                loop {
+                    ticker.tick().await;
+
+                    let Some(conn) = pool.get().await else {
+                        eprintln!("health monitor: couldn't get a database connection");
+                        continue;
+                    };
+
+                    if let Err((_, err)) = check(&Db(conn), &tls, &config, &mut hysteresis).await {
+                        eprintln!("health monitor: check failed: {err}");
+                    }
+                }
+            });
+        })
+    })
+}
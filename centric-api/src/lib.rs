@@ -1,21 +1,63 @@
+pub mod health;
+pub mod metrics;
 pub mod models;
 pub mod node_models;
+pub mod node_tls;
 pub mod schema;
+pub mod secrets;
+pub mod sweeper;
 pub mod token;
 
 pub mod consts {
-    pub const ADMIN_KEY: &str = "THIS_IS_THE_ADMIN_KEY";
-    pub const JWT_SECRET: &[u8] =
-        b"5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
-
-    pub const NODE_INFO_PATH: &str = "/api/stats/node_info";
-    pub const HW_STATS_PATH: &str = "/api/stats/hw_stats";
-    pub const NET_STATS_PATH: &str = "/api/stats/net_stats";
-    pub const USERADD_PATH: &str = "/api/cmd/useradd";
-
-    pub const PASS_PREFIX: &str = "SSHMGMTKIT_";
+    /// Secrets that used to be hard-coded here, refused as configuration values.
+    pub const LEGACY_SECRETS: &[&str] = &[
+        "THIS_IS_THE_ADMIN_KEY",
+        "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03",
+    ];
+    pub const MIN_SECRET_LEN: usize = 32;
+    pub const ENV_PREFIX: &str = "SSHMGMT_";
+
+    /// Days a sell is renewed for when no duration is given.
+    pub const RENEW_DAYS: i64 = 30;
+    /// Most days a sell can be renewed for at once.
+    pub const MAX_RENEW_DAYS: i64 = 3650;
+
+    /// Seconds an enrollment code stays valid when no TTL is given.
+    pub const ENROLLMENT_CODE_TTL: u64 = 24 * 60 * 60;
+    /// Longest TTL an enrollment code can be given.
+    pub const MAX_ENROLLMENT_CODE_TTL: u64 = 30 * 24 * 60 * 60;
+    pub const ENROLLMENT_CODE_LEN: usize = 32;
+    /// Length of the keys generated for enrolled nodes.
+    pub const NODE_SECRET_LEN: usize = 64;
+    /// Subject of the tokens minted for the node requests centric-api makes on its own (stats,
+    /// background jobs), recorded in the audit logs of nodes. Admins' commands carry their
+    /// username instead.
+    pub const NODE_TOKEN_SUBJECT: &str = "centric-api";
+
+    /// Seconds of metrics returned when no start is given.
+    pub const METRICS_RANGE: u64 = 24 * 60 * 60;
+    /// Most points a metrics query may return per series.
+    pub const MAX_METRICS_POINTS: u64 = 10_000;
+    /// Latest unix timestamp accepted in queries (9999-12-31 23:59:59 UTC), later ones don't
+    /// fit the database.
+    pub const MAX_TIMESTAMP: u64 = 253_402_300_799;
+
+    pub const SELL_ACTIVE: i32 = 0;
+    pub const SELL_UNVERIFIED: i32 = 1;
+    pub const SELL_EXPIRED: i32 = 2;
+
+    /// Expiry log result of the actions recorded by a dry-run sweep.
+    pub const SWEEP_PLANNED: &str = "planned";
+
+    /// Length and characters of the passwords generated for sold accounts, matching the
+    /// default password policy of node-api.
+    pub const PASSWD_LEN: usize = 16;
+    pub const PASSWD_ALPHABET: &str = "23456789abcdefghijkmnopqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ";
     pub const GROUP_PREFIX: &str = "grp";
     pub const PREFIX: &str = "sshmgmt";
+
+    /// Bytes per unit of `services.max_traffic` (GiB).
+    pub const TRAFFIC_UNIT: u64 = 1024 * 1024 * 1024;
 }
 
 use rocket_sync_db_pools::{database, diesel};
@@ -26,6 +68,7 @@ pub struct Db(diesel::PgConnection);
 use chrono::{Duration, Local, NaiveDate};
 use pwhash::sha512_crypt;
 use rand::prelude::*;
+use sha2::{Digest, Sha256};
 
 /// Hashes the given password using SHA-512 Crypt and returns the hashed password.
 pub fn hash_password(password: &str) -> String {
@@ -61,11 +104,35 @@ pub fn is_valid_username(username: &str) -> bool {
     true
 }
 
-/// Generates a random password following a specific pattern.
+/// Generates a random alphanumeric string from the OS CSPRNG, for one-time codes and keys.
+pub fn gen_secret(len: usize) -> String {
+    rand::rngs::OsRng
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// Hashes a one-time code with SHA-256, codes are only stored hashed.
+pub fn hash_code(code: &str) -> String {
+    format!("{:x}", Sha256::digest(code.as_bytes()))
+}
+
+/// Generates a random password from the OS CSPRNG.
 pub fn gen_password() -> String {
-    let mut rng = rand::thread_rng();
-    let random_number = rng.gen_range(0..100000);
-    format!("{0}{random_number:05}", consts::PASS_PREFIX)
+    let alphabet: Vec<char> = consts::PASSWD_ALPHABET.chars().collect();
+
+    (0..consts::PASSWD_LEN)
+        .map(|_| alphabet[rand::rngs::OsRng.gen_range(0..alphabet.len())])
+        .collect()
+}
+
+/// Formats a point in time as a `YYYY-MM-DD` date, as expected by node-api.
+pub fn format_system_time(time: std::time::SystemTime) -> String {
+    chrono::DateTime::<Local>::from(time)
+        .date_naive()
+        .format("%Y-%m-%d")
+        .to_string()
 }
 
 /// Formats the expiry date string to a specific date format.
@@ -1,23 +1,33 @@
 #[macro_use]
 extern crate rocket;
 
+use api_types::v1::stats::{HwUsage, NetworkUsage};
+use api_types::v2::stats::{HwStats, NetStats, SystemStats};
 use chrono::{Duration, Local, NaiveDate};
 use diesel::query_dsl::methods::*;
 use diesel::result::Error as DieselError;
 use diesel::ExpressionMethods;
 use diesel::RunQueryDsl;
+use lib::health;
+use lib::metrics::{self, MetricsConfig, NodeMetrics};
+use lib::node_models::NodeRenewal;
+use lib::node_tls::{self, NodeTls};
 use lib::schema::nodes::dsl::nodes;
 use lib::schema::sells::dsl::sells;
 use lib::schema::services::dsl::services;
 use lib::schema::users::dsl::users;
+use lib::secrets::{self, Secrets};
+use lib::sweeper::{self, SweeperConfig};
+use lib::token::{AdminAuth, Auth};
 use lib::{consts, models::*, schema};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
 use reqwest::Error as ReqwestError;
 use rocket::http::Status;
 use rocket::response::{status::Created, Debug};
 use rocket::serde::json::{json, Json, Value as JsonValue};
+use rocket::State;
 use rocket_sync_db_pools::database;
-use std::time::SystemTime;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use lib::Db;
 
@@ -29,7 +39,7 @@ macro_rules! api_err {
 
 type JsonRes<T> = Json<JsonOk<T>>;
 
-fn parse_addr(addr: &str) -> Result<String, (Status, JsonValue)> {
+fn parse_addr(addr: &str, allow_http: bool) -> Result<String, (Status, JsonValue)> {
     let node_addr = match url::Url::parse(&addr) {
         Ok(addr) => {
             if !(addr.scheme().to_lowercase() == "http" || addr.scheme().to_lowercase() == "https")
@@ -38,6 +48,13 @@ fn parse_addr(addr: &str) -> Result<String, (Status, JsonValue)> {
                     Status::UnprocessableEntity,
                     api_err!("internal: node-api's address should be over HTTP or HTTPS protocols"),
                 ));
+            } else if addr.scheme().to_lowercase() == "http" && !allow_http {
+                return Err((
+                    Status::UnprocessableEntity,
+                    api_err!(
+                        "internal: node-api's address should be over HTTPS, plain HTTP is disabled"
+                    ),
+                ));
             } else {
                 addr
             }
@@ -60,6 +77,35 @@ fn parse_addr(addr: &str) -> Result<String, (Status, JsonValue)> {
     Ok(fmt_addr)
 }
 
+fn check_node_secret(jwt_secret: &str) -> Result<(), (Status, JsonValue)> {
+    if jwt_secret.len() < consts::MIN_SECRET_LEN {
+        return Err((
+            Status::UnprocessableEntity,
+            api_err!(format!(
+                "internal: node's jwt_secret should be at least {} characters",
+                consts::MIN_SECRET_LEN
+            )),
+        ));
+    }
+
+    Ok(())
+}
+
+fn parse_tls_fingerprint(
+    fingerprint: &Option<String>,
+) -> Result<Option<String>, (Status, JsonValue)> {
+    match fingerprint {
+        Some(fingerprint) => match node_tls::parse_fingerprint(fingerprint) {
+            Some(fingerprint) => Ok(Some(fingerprint)),
+            None => Err((
+                Status::UnprocessableEntity,
+                api_err!("internal: node's tls_fingerprint should be a SHA-256 fingerprint in hex"),
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
 #[get("/ping")]
 async fn ping() -> &'static str {
     "pong"
@@ -69,19 +115,28 @@ async fn ping() -> &'static str {
 
 #[post("/update_node/<node_id>", data = "<node_info>")]
 async fn update_node(
+    _auth: AdminAuth,
     db: Db,
+    tls: &State<NodeTls>,
     node_id: i32,
     node_info: Json<UpdateNode>,
 ) -> Result<Created<JsonRes<Node>>, (Status, JsonValue)> {
     let node_addr = match &node_info.address {
-        Some(addr) => Some(parse_addr(&addr)?),
+        Some(addr) => Some(parse_addr(&addr, tls.allow_http)?),
         None => None,
     };
+    if let Some(jwt_secret) = &node_info.jwt_secret {
+        check_node_secret(jwt_secret)?;
+    }
 
     let node_value = UpdateNode {
         address: node_addr,
-        token: node_info.token.clone(),
+        jwt_secret: node_info.jwt_secret.clone(),
         status: node_info.status,
+        tls_fingerprint: parse_tls_fingerprint(&node_info.tls_fingerprint)?,
+        name: node_info.name.clone(),
+        location: node_info.location.clone(),
+        capacity: node_info.capacity,
     };
 
     let node_info = Node::update(&db, node_id, node_value).await?;
@@ -91,15 +146,23 @@ async fn update_node(
 
 #[post("/new_node", data = "<node_info>")]
 async fn new_node(
+    _auth: AdminAuth,
     db: Db,
+    tls: &State<NodeTls>,
     node_info: Json<NewNode>,
 ) -> Result<Created<JsonRes<Node>>, (Status, JsonValue)> {
-    let node_addr = parse_addr(&node_info.address)?;
+    let node_addr = parse_addr(&node_info.address, tls.allow_http)?;
+    check_node_secret(&node_info.jwt_secret)?;
 
     let node_value = NewNode {
         address: node_addr,
-        token: node_info.token.clone(),
+        jwt_secret: node_info.jwt_secret.clone(),
         status: node_info.status,
+        tls_fingerprint: parse_tls_fingerprint(&node_info.tls_fingerprint)?,
+        approved: None,
+        name: node_info.name.clone(),
+        location: node_info.location.clone(),
+        capacity: node_info.capacity,
     };
 
     let node_info = Node::insert(&db, node_value).await?;
@@ -107,29 +170,234 @@ async fn new_node(
     Ok(Created::new("/new_node").body(JsonOk::from(node_info)))
 }
 
+#[post("/new_enrollment_code?<ttl>")]
+async fn new_enrollment_code(
+    _auth: AdminAuth,
+    db: Db,
+    ttl: Option<u64>,
+) -> Result<Created<JsonRes<IssuedEnrollmentCode>>, (Status, JsonValue)> {
+    let ttl = ttl.unwrap_or(consts::ENROLLMENT_CODE_TTL);
+    if !(1..=consts::MAX_ENROLLMENT_CODE_TTL).contains(&ttl) {
+        return Err((
+            Status::BadRequest,
+            api_err!(format!(
+                "ttl should be between 1 and {}",
+                consts::MAX_ENROLLMENT_CODE_TTL
+            )),
+        ));
+    }
+
+    let code = lib::gen_secret(consts::ENROLLMENT_CODE_LEN);
+
+    let code_value = NewEnrollmentCode {
+        code_hash: lib::hash_code(&code),
+        expires_at: SystemTime::now() + std::time::Duration::from_secs(ttl),
+    };
+
+    let enrollment_code = EnrollmentCode::insert(&db, code_value).await?;
+
+    Ok(
+        Created::new("/new_enrollment_code").body(JsonOk::from(IssuedEnrollmentCode {
+            code,
+            enrollment_code,
+        })),
+    )
+}
+
+#[get("/enrollment_codes")]
+async fn enrollment_codes(
+    _auth: AdminAuth,
+    db: Db,
+) -> Result<JsonRes<Vec<EnrollmentCode>>, (Status, JsonValue)> {
+    let codes_list = EnrollmentCode::list(&db).await?;
+    Ok(JsonOk::from(codes_list))
+}
+
+#[post("/enroll", data = "<enroll_request>")]
+async fn enroll(
+    db: Db,
+    tls: &State<NodeTls>,
+    enroll_request: Json<EnrollRequest>,
+) -> Result<Created<JsonRes<EnrolledNode>>, (Status, JsonValue)> {
+    let node_addr = parse_addr(&enroll_request.address, tls.allow_http)?;
+    let jwt_secret = lib::gen_secret(consts::NODE_SECRET_LEN);
+
+    let node_value = NewNode {
+        address: node_addr,
+        jwt_secret: jwt_secret.clone(),
+        status: NodeStatus::Online,
+        tls_fingerprint: parse_tls_fingerprint(&enroll_request.tls_fingerprint)?,
+        approved: Some(false),
+        name: Some(enroll_request.node_info.name.clone()),
+        location: Some(enroll_request.node_info.location.clone()),
+        capacity: enroll_request
+            .node_info
+            .capacity
+            .map(|capacity| capacity as i64),
+    };
+
+    let node_info = Node::enroll(&db, lib::hash_code(&enroll_request.code), node_value).await?;
+
+    Ok(Created::new("/enroll").body(JsonOk::from(EnrolledNode {
+        node_id: node_info.id,
+        jwt_secret,
+    })))
+}
+
+#[post("/approve_node/<node_id>")]
+async fn approve_node(
+    _auth: AdminAuth,
+    db: Db,
+    node_id: i32,
+) -> Result<JsonRes<Node>, (Status, JsonValue)> {
+    let node_info = Node::approve(&db, node_id).await?;
+    Ok(JsonOk::from(node_info))
+}
+
 #[get("/node_info/<node_id>")]
-async fn node_info(db: Db, node_id: i32) -> Result<JsonRes<JsonValue>, (Status, JsonValue)> {
+async fn node_info(
+    _auth: Auth,
+    db: Db,
+    tls: &State<NodeTls>,
+    node_id: i32,
+) -> Result<JsonRes<NodeInfo>, (Status, JsonValue)> {
     let node_info = Node::find_by_id(&db, node_id).await?;
 
-    Ok(JsonOk::from(node_info.info().await?))
+    Ok(JsonOk::from(node_info.info(tls).await?))
 }
 
 #[get("/hw_stats/<node_id>")]
-async fn hw_stats(db: Db, node_id: i32) -> Result<JsonRes<JsonValue>, (Status, JsonValue)> {
+async fn hw_stats(
+    _auth: Auth,
+    db: Db,
+    tls: &State<NodeTls>,
+    node_id: i32,
+) -> Result<JsonRes<HwUsage>, (Status, JsonValue)> {
     let node_info = Node::find_by_id(&db, node_id).await?;
 
-    Ok(JsonOk::from(node_info.hw_stats().await?))
+    Ok(JsonOk::from(node_info.hw_stats(tls).await?))
 }
 
 #[get("/net_stats/<node_id>")]
-async fn net_stats(db: Db, node_id: i32) -> Result<JsonRes<JsonValue>, (Status, JsonValue)> {
+async fn net_stats(
+    _auth: Auth,
+    db: Db,
+    tls: &State<NodeTls>,
+    node_id: i32,
+) -> Result<JsonRes<Vec<NetworkUsage>>, (Status, JsonValue)> {
     let node_info = Node::find_by_id(&db, node_id).await?;
 
-    Ok(JsonOk::from(node_info.net_stats().await?))
+    Ok(JsonOk::from(node_info.net_stats(tls).await?))
+}
+
+#[get("/v2/hw_stats/<node_id>?<pretty>")]
+async fn hw_stats_v2(
+    _auth: Auth,
+    db: Db,
+    tls: &State<NodeTls>,
+    node_id: i32,
+    pretty: Option<bool>,
+) -> Result<JsonRes<HwStats>, (Status, JsonValue)> {
+    let node_info = Node::find_by_id(&db, node_id).await?;
+
+    Ok(JsonOk::from(
+        node_info.hw_stats_v2(tls, pretty.unwrap_or(false)).await?,
+    ))
+}
+
+#[get("/v2/net_stats/<node_id>?<pretty>")]
+async fn net_stats_v2(
+    _auth: Auth,
+    db: Db,
+    tls: &State<NodeTls>,
+    node_id: i32,
+    pretty: Option<bool>,
+) -> Result<JsonRes<NetStats>, (Status, JsonValue)> {
+    let node_info = Node::find_by_id(&db, node_id).await?;
+
+    Ok(JsonOk::from(
+        node_info.net_stats_v2(tls, pretty.unwrap_or(false)).await?,
+    ))
+}
+
+#[get("/v2/system_stats/<node_id>")]
+async fn system_stats(
+    _auth: Auth,
+    db: Db,
+    tls: &State<NodeTls>,
+    node_id: i32,
+) -> Result<JsonRes<SystemStats>, (Status, JsonValue)> {
+    let node_info = Node::find_by_id(&db, node_id).await?;
+
+    Ok(JsonOk::from(node_info.system_stats(tls).await?))
+}
+
+#[get("/metrics/<node_id>?<from>&<to>&<step>")]
+async fn node_metrics(
+    _auth: Auth,
+    db: Db,
+    metrics_config: &State<MetricsConfig>,
+    node_id: i32,
+    from: Option<u64>,
+    to: Option<u64>,
+    step: Option<u64>,
+) -> Result<JsonRes<NodeMetrics>, (Status, JsonValue)> {
+    Node::find_by_id(&db, node_id).await?;
+
+    let to = to.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs())
+    });
+    let from = from.unwrap_or(to.saturating_sub(consts::METRICS_RANGE));
+    let step = step.unwrap_or(metrics_config.interval);
+
+    if step == 0 || from > to {
+        return Err((
+            Status::UnprocessableEntity,
+            api_err!("internal: metrics need a positive step and a start before their end"),
+        ));
+    }
+
+    if (to - from) / step > consts::MAX_METRICS_POINTS {
+        return Err((
+            Status::UnprocessableEntity,
+            api_err!(format!(
+                "internal: metrics are limited to {} points, use a larger step",
+                consts::MAX_METRICS_POINTS
+            )),
+        ));
+    }
+
+    let timestamp = |secs: u64| {
+        (secs <= consts::MAX_TIMESTAMP)
+            .then(|| UNIX_EPOCH.checked_add(std::time::Duration::from_secs(secs)))
+            .flatten()
+    };
+    let (Some(from), Some(to)) = (timestamp(from), timestamp(to)) else {
+        return Err((
+            Status::UnprocessableEntity,
+            api_err!("internal: metrics range is out of bounds"),
+        ));
+    };
+    let (samples, interfaces) = NodeMetric::list(&db, node_id, from, to).await?;
+
+    Ok(JsonOk::from(metrics::series(
+        node_id,
+        from,
+        to,
+        step,
+        &samples,
+        &interfaces,
+    )))
 }
 
 #[post("/delete_node/<node_id>")]
-async fn delete_node(db: Db, node_id: i32) -> Result<JsonRes<JsonValue>, (Status, JsonValue)> {
+async fn delete_node(
+    _auth: AdminAuth,
+    db: Db,
+    node_id: i32,
+) -> Result<JsonRes<JsonValue>, (Status, JsonValue)> {
     let node_deleted = Node::delete_by_id(&db, node_id).await?;
 
     if node_deleted {
@@ -144,14 +412,14 @@ async fn delete_node(db: Db, node_id: i32) -> Result<JsonRes<JsonValue>, (Status
 }
 
 #[get("/get_node/<node_id>")]
-async fn get_node(db: Db, node_id: i32) -> Result<JsonRes<Node>, (Status, JsonValue)> {
+async fn get_node(_auth: Auth, db: Db, node_id: i32) -> Result<JsonRes<Node>, (Status, JsonValue)> {
     let node_info = Node::find_by_id(&db, node_id).await?;
 
     Ok(JsonOk::from(node_info))
 }
 
 #[get("/nodes_list")]
-async fn nodes_list(db: Db) -> Result<JsonRes<Vec<Node>>, (Status, JsonValue)> {
+async fn nodes_list(_auth: Auth, db: Db) -> Result<JsonRes<Vec<Node>>, (Status, JsonValue)> {
     let nodes_list = Node::list(&db).await?;
 
     Ok(JsonOk::from(nodes_list))
@@ -161,10 +429,12 @@ async fn nodes_list(db: Db) -> Result<JsonRes<Vec<Node>>, (Status, JsonValue)> {
 
 #[post("/new_service", data = "<service_info>")]
 async fn new_service(
+    _auth: AdminAuth,
     db: Db,
     service_info: Json<NewService>,
 ) -> Result<Created<JsonRes<Service>>, (Status, JsonValue)> {
     let service_value = service_info.0;
+    service_value.validate()?;
 
     let service_info = Service::insert(&db, service_value).await?;
 
@@ -173,11 +443,13 @@ async fn new_service(
 
 #[post("/update_service/<service_id>", data = "<service_info>")]
 async fn update_service(
+    _auth: AdminAuth,
     db: Db,
     service_id: i32,
     service_info: Json<UpdateService>,
 ) -> Result<Created<JsonRes<Service>>, (Status, JsonValue)> {
     let service_value = service_info.0;
+    service_value.validate()?;
 
     let service_info = Service::update(&db, service_id, service_value).await?;
 
@@ -186,6 +458,7 @@ async fn update_service(
 
 #[post("/delete_service/<service_id>")]
 async fn delete_service(
+    _auth: AdminAuth,
     db: Db,
     service_id: i32,
 ) -> Result<JsonRes<JsonValue>, (Status, JsonValue)> {
@@ -203,13 +476,17 @@ async fn delete_service(
 }
 
 #[get("/services_list")]
-async fn services_list(db: Db) -> Result<JsonRes<Vec<Service>>, (Status, JsonValue)> {
+async fn services_list(_auth: Auth, db: Db) -> Result<JsonRes<Vec<Service>>, (Status, JsonValue)> {
     let services_list = Service::list(&db).await?;
     Ok(JsonOk::from(services_list))
 }
 
 #[get("/get_service/<service_id>")]
-async fn get_service(db: Db, service_id: i32) -> Result<JsonRes<Service>, (Status, JsonValue)> {
+async fn get_service(
+    _auth: Auth,
+    db: Db,
+    service_id: i32,
+) -> Result<JsonRes<Service>, (Status, JsonValue)> {
     let service_info = Service::find_by_id(&db, service_id).await?;
 
     Ok(JsonOk::from(service_info))
@@ -219,6 +496,7 @@ async fn get_service(db: Db, service_id: i32) -> Result<JsonRes<Service>, (Statu
 
 #[post("/new_user", data = "<user_info>")]
 async fn new_user(
+    _auth: AdminAuth,
     db: Db,
     user_info: Json<NewUser>,
 ) -> Result<Created<JsonRes<User>>, (Status, JsonValue)> {
@@ -235,14 +513,18 @@ async fn new_user(
 }
 
 #[get("/user_refs/<user_id>")]
-async fn user_refs(db: Db, user_id: i64) -> Result<JsonRes<Vec<User>>, (Status, JsonValue)> {
+async fn user_refs(
+    _auth: Auth,
+    db: Db,
+    user_id: i64,
+) -> Result<JsonRes<Vec<User>>, (Status, JsonValue)> {
     let refs = User::refs(&db, user_id).await?;
 
     Ok(JsonOk::from(refs))
 }
 
 #[get("/users_list")]
-async fn users_list(db: Db) -> Result<JsonRes<Vec<User>>, (Status, JsonValue)> {
+async fn users_list(_auth: Auth, db: Db) -> Result<JsonRes<Vec<User>>, (Status, JsonValue)> {
     let users_list = User::list(&db).await?;
 
     Ok(JsonOk::from(users_list))
@@ -252,6 +534,7 @@ async fn users_list(db: Db) -> Result<JsonRes<Vec<User>>, (Status, JsonValue)> {
 
 #[post("/new_sell", data = "<sell_info>")]
 async fn new_sell(
+    _auth: AdminAuth,
     db: Db,
     sell_info: Json<NewSell>,
 ) -> Result<Created<JsonRes<Sell>>, (Status, JsonValue)> {
@@ -259,6 +542,14 @@ async fn new_sell(
 
     let user_info = User::find_by_id(&db, user_id).await?;
 
+    let node_info = Node::find_by_id(&db, sell_info.node_id).await?;
+    if !node_info.approved {
+        return Err((Status::BadRequest, api_err!("node is pending approval")));
+    }
+    if node_info.status == NodeStatus::Offline {
+        return Err((Status::BadRequest, api_err!("node is offline")));
+    }
+
     let sell_value = NewSell::new_unverified(
         sell_info.user_id,
         user_info.ref_id,
@@ -272,7 +563,9 @@ async fn new_sell(
 
 #[post("/verify_sell/<sell_id>", data = "<account_info>")]
 async fn verify_sell(
+    auth: AdminAuth,
     db: Db,
+    tls: &State<NodeTls>,
     sell_id: i32,
     account_info: Json<AccountInfo>,
 ) -> Result<JsonRes<Sell>, (Status, JsonValue)> {
@@ -283,7 +576,7 @@ async fn verify_sell(
     let node_info = Node::find_by_id(&db, sell_info.node_id).await?;
 
     let sshuser_info = node_info
-        .useradd(sell_id, &service_info, &account_info.0)
+        .useradd(tls, &auth.username, sell_id, &service_info, &account_info.0)
         .await?;
 
     let update_sell = UpdateSell::verify(sshuser_info);
@@ -294,26 +587,179 @@ async fn verify_sell(
     // WIP
 }
 
+#[post("/renew/<sell_id>", data = "<renew_info>")]
+async fn renew_sell(
+    auth: AdminAuth,
+    db: Db,
+    tls: &State<NodeTls>,
+    sell_id: i32,
+    renew_info: Json<RenewInfo>,
+) -> Result<JsonRes<Sell>, (Status, JsonValue)> {
+    let sell_info = Sell::find_by_id(&db, sell_id).await?;
+
+    let username = match &sell_info.username {
+        Some(username) if sell_info.status != consts::SELL_UNVERIFIED => username.clone(),
+        _ => return Err((Status::BadRequest, api_err!("sell is not verified"))),
+    };
+
+    let days = renew_info.days.unwrap_or(consts::RENEW_DAYS);
+    if !(1..=consts::MAX_RENEW_DAYS).contains(&days) {
+        return Err((
+            Status::BadRequest,
+            api_err!(format!(
+                "days should be between 1 and {}",
+                consts::MAX_RENEW_DAYS
+            )),
+        ));
+    }
+
+    let service_id = renew_info.service_id.unwrap_or(sell_info.service_id);
+    let service_changed = service_id != sell_info.service_id;
+
+    let service_info = Service::find_by_id(&db, service_id).await?;
+    if service_changed && !service_info.available {
+        return Err((Status::BadRequest, api_err!("service is not available")));
+    }
+
+    // Renewing before the due date extends the current period, otherwise it starts today.
+    let now = SystemTime::now();
+    let previous_invoice_date = sell_info.invoice_date;
+    let invoice_date = (days as u64)
+        .checked_mul(24 * 60 * 60)
+        .and_then(|secs| {
+            previous_invoice_date
+                .filter(|invoice_date| *invoice_date > now)
+                .unwrap_or(now)
+                .checked_add(std::time::Duration::from_secs(secs))
+        })
+        .ok_or((Status::BadRequest, api_err!("invoice date is out of range")))?;
+
+    let node_info = Node::find_by_id(&db, sell_info.node_id).await?;
+
+    // Only the expiry sweeper's lock is lifted, admin, abuse and quota locks stay
+    let previous_service_info = Service::find_by_id(&db, sell_info.service_id).await?;
+    let node_renewal = NodeRenewal::new(
+        &username,
+        &previous_service_info,
+        &service_info,
+        previous_invoice_date.map(lib::format_system_time),
+        lib::format_system_time(invoice_date),
+        sell_info.status == consts::SELL_EXPIRED,
+    )?;
+
+    // The database is updated first and reverted, like the node, if the node refuses the renewal
+    let update_sell = UpdateSell::renew(service_id, invoice_date);
+    let renewal_value = NewRenewal {
+        sell_id,
+        service_id,
+        days: days as i32,
+        price: service_info.price,
+        previous_invoice_date,
+        invoice_date,
+    };
+    let (new_sell_info, renewal_info) = Renewal::apply(&db, update_sell, renewal_value).await?;
+
+    let mut attempted = 0;
+    let mut applied = Ok(());
+    for step in &node_renewal.steps {
+        attempted += 1;
+        applied = node_info
+            .renewal_step(tls, &auth.username, &username, step)
+            .await;
+        if applied.is_err() {
+            break;
+        }
+    }
+
+    if let Err(err) = applied {
+        // Restores the previous expiry, group and limits on the node before the database
+        for step in node_renewal.undo(attempted) {
+            if let Err((_, undo_err)) = node_info
+                .renewal_step(tls, &auth.username, &username, &step)
+                .await
+            {
+                eprintln!("renew: couldn't undo {step:?} for {username}: {undo_err}");
+            }
+        }
+        Renewal::revert(&db, renewal_info.id, UpdateSell::restore(&sell_info)).await?;
+        return Err(err);
+    }
+
+    Ok(JsonOk::from(new_sell_info))
+}
+
+#[post("/reset_password/<sell_id>")]
+async fn reset_password(
+    auth: AdminAuth,
+    db: Db,
+    tls: &State<NodeTls>,
+    sell_id: i32,
+) -> Result<JsonRes<PasswordReset>, (Status, JsonValue)> {
+    let sell_info = Sell::find_by_id(&db, sell_id).await?;
+
+    let username = match &sell_info.username {
+        Some(username) if sell_info.status != consts::SELL_UNVERIFIED => username.clone(),
+        _ => return Err((Status::BadRequest, api_err!("sell is not verified"))),
+    };
+
+    let node_info = Node::find_by_id(&db, sell_info.node_id).await?;
+    let credentials = node_info
+        .reset_password(tls, &auth.username, &username)
+        .await?;
+
+    let sell_info = Sell::record_password_reset(&db, sell_id, credentials.password_hash).await?;
+
+    Ok(JsonOk::from(PasswordReset {
+        sell: sell_info,
+        password: credentials.password,
+    }))
+}
+
+#[get("/renewals_list")]
+async fn renewals_list(_auth: Auth, db: Db) -> Result<JsonRes<Vec<Renewal>>, (Status, JsonValue)> {
+    let renewals_list = Renewal::list(&db).await?;
+    Ok(JsonOk::from(renewals_list))
+}
+
+#[get("/renewals_list_by_sell/<sell_id>")]
+async fn renewals_list_by_sell(
+    _auth: Auth,
+    db: Db,
+    sell_id: i32,
+) -> Result<JsonRes<Vec<Renewal>>, (Status, JsonValue)> {
+    let renewals_list = Renewal::list_by_sell(&db, sell_id).await?;
+    Ok(JsonOk::from(renewals_list))
+}
+
 #[get("/sell_info/<sell_id>")]
-async fn sell_info(db: Db, sell_id: i32) -> Result<JsonRes<Sell>, (Status, JsonValue)> {
+async fn sell_info(
+    _auth: Auth,
+    db: Db,
+    sell_id: i32,
+) -> Result<JsonRes<Sell>, (Status, JsonValue)> {
     let sell_info = Sell::find_by_id(&db, sell_id).await?;
     Ok(JsonOk::from(sell_info))
 }
 
 #[get("/sells_list")]
-async fn sells_list(db: Db) -> Result<JsonRes<Vec<Sell>>, (Status, JsonValue)> {
+async fn sells_list(_auth: Auth, db: Db) -> Result<JsonRes<Vec<Sell>>, (Status, JsonValue)> {
     let sells_list = Sell::list(&db).await?;
     Ok(JsonOk::from(sells_list))
 }
 
 #[get("/sells_list_by_ref/<ref_id>")]
-async fn sells_list_by_ref(db: Db, ref_id: i64) -> Result<JsonRes<Vec<Sell>>, (Status, JsonValue)> {
+async fn sells_list_by_ref(
+    _auth: Auth,
+    db: Db,
+    ref_id: i64,
+) -> Result<JsonRes<Vec<Sell>>, (Status, JsonValue)> {
     let sells_list = Sell::list_by_ref(&db, ref_id).await?;
     Ok(JsonOk::from(sells_list))
 }
 
 #[get("/sells_list_by_user/<user_id>")]
 async fn sells_list_by_user(
+    _auth: Auth,
     db: Db,
     user_id: i64,
 ) -> Result<JsonRes<Vec<Sell>>, (Status, JsonValue)> {
@@ -321,6 +767,26 @@ async fn sells_list_by_user(
     Ok(JsonOk::from(sells_list))
 }
 
+#[post("/sweep_expired?<dry_run>")]
+async fn sweep_expired(
+    auth: AdminAuth,
+    db: Db,
+    tls: &State<NodeTls>,
+    sweeper_config: &State<SweeperConfig>,
+    dry_run: Option<bool>,
+) -> Result<JsonRes<Vec<ExpiryLog>>, (Status, JsonValue)> {
+    let dry_run = dry_run.unwrap_or(sweeper_config.dry_run);
+
+    let logs = sweeper::sweep(&db, tls, &auth.username, sweeper_config.action, dry_run).await?;
+    Ok(JsonOk::from(logs))
+}
+
+#[get("/expiry_log")]
+async fn expiry_log(_auth: Auth, db: Db) -> Result<JsonRes<Vec<ExpiryLog>>, (Status, JsonValue)> {
+    let logs = ExpiryLog::list(&db).await?;
+    Ok(JsonOk::from(logs))
+}
+
 // AUTH SECTION:
 //
 // WIP
@@ -328,16 +794,18 @@ async fn sells_list_by_user(
 #[post("/register", data = "<login_info>")]
 async fn register(
     db: Db,
+    secrets: &State<Secrets>,
     login_info: Json<LoginInfo>,
 ) -> Result<Created<JsonRes<String>>, (Status, JsonValue)> {
     if let None = login_info.0.admin_key {
         return Err((Status::BadRequest, api_err!("Admin_Key Missing!")));
     }
 
-    if Some(consts::ADMIN_KEY.to_string()) != login_info.0.admin_key {
+    if Some(&secrets.admin_key) != login_info.0.admin_key.as_ref() {
         return Err((Status::BadRequest, api_err!("Invalid admin_key")));
     }
 
+    let admin = login_info.0.admin;
     let password = login_info.0.password;
     let username = login_info.0.username;
 
@@ -354,7 +822,8 @@ async fn register(
     let login_value = NewLogin {
         username,
         password_hash,
-        admin: None,
+        // Only holders of the admin key can register, and choose whether the login is an admin
+        admin: Some(admin),
     };
 
     Login::insert(&db, login_value).await?;
@@ -365,6 +834,7 @@ async fn register(
 #[post("/login", data = "<login_info>")]
 async fn login(
     db: Db,
+    secrets: &State<Secrets>,
     login_info: Json<LoginInfo>,
 ) -> Result<Created<JsonRes<String>>, (Status, JsonValue)> {
     let password = login_info.0.password;
@@ -380,7 +850,7 @@ async fn login(
         return Err((Status::BadRequest, api_err!("password is wrong")));
     }
 
-    let token = lib::token::Token::generate();
+    let token = lib::token::Token::generate(&user_info, secrets);
 
     Ok(Created::new("/login").body(JsonOk::from(token)))
 }
@@ -394,7 +864,16 @@ async fn verify_token(token: lib::token::Token) -> Result<(), (Status, JsonValue
 #[launch]
 fn rocket() -> _ {
     rocket::build()
+        .attach(secrets::fairing())
         .attach(Db::fairing())
+        .attach(node_tls::fairing())
+        .register(
+            "/",
+            catchers![lib::token::unauthorized, lib::token::forbidden],
+        )
+        .attach(sweeper::fairing())
+        .attach(health::fairing())
+        .attach(metrics::fairing())
         .mount(
             "/nodes",
             routes![
@@ -405,8 +884,16 @@ fn rocket() -> _ {
                 update_node,
                 node_info,
                 new_node,
+                new_enrollment_code,
+                enrollment_codes,
+                enroll,
+                approve_node,
                 hw_stats,
                 net_stats,
+                hw_stats_v2,
+                net_stats_v2,
+                system_stats,
+                node_metrics,
             ],
         )
         .mount(
@@ -425,12 +912,17 @@ fn rocket() -> _ {
             routes![
                 new_sell,
                 verify_sell,
+                renew_sell,
+                reset_password,
+                renewals_list,
+                renewals_list_by_sell,
                 sell_info,
                 sells_list,
                 sells_list_by_ref,
-                sells_list_by_user
+                sells_list_by_user,
+                sweep_expired,
+                expiry_log
             ],
         )
         .mount("/auth", routes![register, login, verify_token])
 }
-
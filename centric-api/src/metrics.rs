@@ -0,0 +1,423 @@
+use crate::models::{
+    ApiError, NewNodeMetric, NewNodeNetMetric, Node, NodeMetric, NodeNetMetric, NodeStatus,
+};
+use crate::node_tls::NodeTls;
+use crate::{consts, Db};
+use api_types::v1::stats::{HwUsage, NetworkUsage};
+use node_client::{NodeClient, RetryPolicy};
+use rocket::fairing::{AdHoc, Fairing};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::task::JoinSet;
+use rocket::tokio::time::{interval, Duration};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds a rolled up sample stands for
+pub const ROLLUP_RESOLUTION: i32 = 60 * 60;
+
+/// Structure representing the `metrics` section of Rocket.toml
+#[derive(Deserialize, Clone, Debug)]
+#[serde(crate = "rocket::serde", default)]
+pub struct MetricsConfig {
+    /// Whether the background collector runs at all
+    pub enabled: bool,
+    /// Seconds between two samples of every node
+    pub interval: u64,
+    /// Seconds the statistics requests of a sample may take
+    pub timeout: u64,
+    /// Hours raw samples are kept before being rolled up into hourly averages
+    pub raw_retention: u64,
+    /// Days hourly averages are kept
+    pub rollup_retention: u64,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            enabled: true,
+            interval: 60,
+            timeout: 10,
+            raw_retention: 48,
+            rollup_retention: 90,
+        }
+    }
+}
+
+/// Structure representing the hardware statistics of a node averaged over a step
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct MetricPoint {
+    /// Start of the step
+    pub timestamp: SystemTime,
+    pub load_one: f32,
+    pub load_five: f32,
+    pub load_fifteen: f32,
+    pub memory_used: i64,
+    pub memory_total: i64,
+    pub swap_used: i64,
+    pub swap_total: i64,
+    pub disk_used: i64,
+    pub disk_total: i64,
+}
+
+/// Structure representing the counters of an interface at the end of a step
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct NetPoint {
+    /// Start of the step
+    pub timestamp: SystemTime,
+    pub rx: i64,
+    pub tx: i64,
+    /// Bytes per second since the previous point, missing for the first point and after
+    /// the counters were reset
+    pub rx_rate: Option<f64>,
+    pub tx_rate: Option<f64>,
+}
+
+/// Structure representing the time series of a node between two points in time
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct NodeMetrics {
+    pub node_id: i32,
+    pub from: SystemTime,
+    pub to: SystemTime,
+    /// Seconds between two points
+    pub step: u64,
+    pub samples: Vec<MetricPoint>,
+    pub interfaces: BTreeMap<String, Vec<NetPoint>>,
+}
+
+fn secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+fn bytes(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+/// Returns the start of the step of `step` seconds, counted from `origin`, containing `time`
+fn step_start(time: SystemTime, origin: u64, step: u64) -> u64 {
+    origin + secs(time).saturating_sub(origin) / step * step
+}
+
+/// Averages samples into a single point
+fn average(timestamp: SystemTime, samples: &[&NodeMetric]) -> MetricPoint {
+    let count = samples.len().max(1);
+    let mean_f32 = |field: fn(&NodeMetric) -> f32| {
+        samples.iter().map(|sample| field(sample)).sum::<f32>() / count as f32
+    };
+    let mean_i64 = |field: fn(&NodeMetric) -> i64| {
+        (samples
+            .iter()
+            .map(|sample| field(sample) as i128)
+            .sum::<i128>()
+            / count as i128) as i64
+    };
+
+    MetricPoint {
+        timestamp,
+        load_one: mean_f32(|sample| sample.load_one),
+        load_five: mean_f32(|sample| sample.load_five),
+        load_fifteen: mean_f32(|sample| sample.load_fifteen),
+        memory_used: mean_i64(|sample| sample.memory_used),
+        memory_total: mean_i64(|sample| sample.memory_total),
+        swap_used: mean_i64(|sample| sample.swap_used),
+        swap_total: mean_i64(|sample| sample.swap_total),
+        disk_used: mean_i64(|sample| sample.disk_used),
+        disk_total: mean_i64(|sample| sample.disk_total),
+    }
+}
+
+/// Groups the samples of a node into steps of `step` seconds starting at `from`, averaging
+/// hardware statistics and keeping the last counters of every interface
+pub fn series(
+    node_id: i32,
+    from: SystemTime,
+    to: SystemTime,
+    step: u64,
+    samples: &[NodeMetric],
+    interfaces: &[NodeNetMetric],
+) -> NodeMetrics {
+    let origin = secs(from);
+
+    let mut steps: BTreeMap<u64, Vec<&NodeMetric>> = BTreeMap::new();
+    for sample in samples {
+        steps
+            .entry(step_start(sample.sampled_at, origin, step))
+            .or_default()
+            .push(sample);
+    }
+
+    let samples = steps
+        .into_iter()
+        .map(|(start, samples)| average(UNIX_EPOCH + Duration::from_secs(start), &samples))
+        .collect();
+
+    // Interfaces are ordered by name then time, the last sample of a step overwrites the others
+    let mut counters: BTreeMap<&str, BTreeMap<u64, &NodeNetMetric>> = BTreeMap::new();
+    for sample in interfaces {
+        counters
+            .entry(&sample.interface)
+            .or_default()
+            .insert(step_start(sample.sampled_at, origin, step), sample);
+    }
+
+    let interfaces = counters
+        .into_iter()
+        .map(|(interface, steps)| {
+            let mut previous: Option<&NodeNetMetric> = None;
+            let points = steps
+                .into_iter()
+                .map(|(start, sample)| {
+                    let rate = |counter: fn(&NodeNetMetric) -> i64| {
+                        let previous = previous?;
+                        let elapsed = sample
+                            .sampled_at
+                            .duration_since(previous.sampled_at)
+                            .ok()?
+                            .as_secs_f64();
+
+                        (counter(sample) >= counter(previous) && elapsed > 0.0)
+                            .then(|| (counter(sample) - counter(previous)) as f64 / elapsed)
+                    };
+
+                    let point = NetPoint {
+                        timestamp: UNIX_EPOCH + Duration::from_secs(start),
+                        rx: sample.rx,
+                        tx: sample.tx,
+                        rx_rate: rate(|sample| sample.rx),
+                        tx_rate: rate(|sample| sample.tx),
+                    };
+                    previous = Some(sample);
+                    point
+                })
+                .collect();
+
+            (interface.to_string(), points)
+        })
+        .collect();
+
+    NodeMetrics {
+        node_id,
+        from,
+        to,
+        step,
+        samples,
+        interfaces,
+    }
+}
+
+/// Rolls raw samples up into hourly ones, averaging hardware statistics and keeping the last
+/// counters of every interface
+pub fn rollup(
+    samples: &[NodeMetric],
+    interfaces: &[NodeNetMetric],
+) -> (Vec<NewNodeMetric>, Vec<NewNodeNetMetric>) {
+    let hour = ROLLUP_RESOLUTION as u64;
+
+    let mut hours: BTreeMap<(i32, u64), Vec<&NodeMetric>> = BTreeMap::new();
+    for sample in samples {
+        hours
+            .entry((sample.node_id, step_start(sample.sampled_at, 0, hour)))
+            .or_default()
+            .push(sample);
+    }
+
+    let samples = hours
+        .into_iter()
+        .map(|((node_id, start), samples)| {
+            let point = average(UNIX_EPOCH + Duration::from_secs(start), &samples);
+
+            NewNodeMetric {
+                node_id,
+                resolution: ROLLUP_RESOLUTION,
+                sampled_at: point.timestamp,
+                load_one: point.load_one,
+                load_five: point.load_five,
+                load_fifteen: point.load_fifteen,
+                memory_used: point.memory_used,
+                memory_total: point.memory_total,
+                swap_used: point.swap_used,
+                swap_total: point.swap_total,
+                disk_used: point.disk_used,
+                disk_total: point.disk_total,
+            }
+        })
+        .collect();
+
+    let mut counters: BTreeMap<(i32, &str, u64), &NodeNetMetric> = BTreeMap::new();
+    for sample in interfaces {
+        let key = (
+            sample.node_id,
+            sample.interface.as_str(),
+            step_start(sample.sampled_at, 0, hour),
+        );
+
+        match counters.get(&key) {
+            Some(last) if last.sampled_at > sample.sampled_at => {}
+            _ => {
+                counters.insert(key, sample);
+            }
+        }
+    }
+
+    let interfaces = counters
+        .into_iter()
+        .map(|((node_id, interface, start), sample)| NewNodeNetMetric {
+            node_id,
+            resolution: ROLLUP_RESOLUTION,
+            sampled_at: UNIX_EPOCH + Duration::from_secs(start),
+            interface: interface.to_string(),
+            rx: sample.rx,
+            tx: sample.tx,
+        })
+        .collect();
+
+    (samples, interfaces)
+}
+
+/// Asynchronously fetches the hardware and network statistics of a node
+async fn sample(client: NodeClient) -> Option<(HwUsage, Vec<NetworkUsage>)> {
+    let hw_usage = client.hw_stats().await.ok()?;
+    let net_usage = client.net_stats().await.ok()?;
+
+    Some((hw_usage, net_usage))
+}
+
+/// Asynchronously samples every approved node that isn't offline at once and records the
+/// samples, nodes failing to answer are skipped until the next collection
+pub async fn collect(db: &Db, tls: &NodeTls, config: &MetricsConfig) -> Result<(), ApiError> {
+    let mut samples = JoinSet::new();
+
+    for node in Node::list(db).await? {
+        if !node.approved || node.status == NodeStatus::Offline {
+            continue;
+        }
+
+        let Ok(client) = node.client(tls, consts::NODE_TOKEN_SUBJECT) else {
+            continue;
+        };
+        let client = client
+            .with_timeout(Duration::from_secs(config.timeout))
+            .with_retry(RetryPolicy::none());
+
+        samples.spawn(async move { (node.id, SystemTime::now(), sample(client).await) });
+    }
+
+    let resolution = i32::try_from(config.interval).unwrap_or(i32::MAX);
+
+    while let Some(joined) = samples.join_next().await {
+        let Ok((node_id, sampled_at, Some((hw_usage, net_usage)))) = joined else {
+            continue;
+        };
+
+        let metric_value = NewNodeMetric {
+            node_id,
+            resolution,
+            sampled_at,
+            load_one: hw_usage.cpu_load.0,
+            load_five: hw_usage.cpu_load.1,
+            load_fifteen: hw_usage.cpu_load.2,
+            memory_used: bytes(hw_usage.memory_usage.used),
+            memory_total: bytes(hw_usage.memory_usage.total),
+            swap_used: bytes(hw_usage.swap_usage.used),
+            swap_total: bytes(hw_usage.swap_usage.total),
+            disk_used: bytes(hw_usage.disk_info.used),
+            disk_total: bytes(hw_usage.disk_info.total),
+        };
+
+        let interfaces = net_usage
+            .into_iter()
+            .map(|usage| NewNodeNetMetric {
+                node_id,
+                resolution,
+                sampled_at,
+                interface: usage.interface,
+                rx: bytes(usage.rx_bytes),
+                tx: bytes(usage.tx_bytes),
+            })
+            .collect();
+
+        NodeMetric::insert(db, metric_value, interfaces).await?;
+    }
+
+    Ok(())
+}
+
+/// Asynchronously rolls the raw samples older than `raw_retention` up into hourly ones,
+/// and deletes the samples older than `rollup_retention`
+pub async fn compact(db: &Db, config: &MetricsConfig) -> Result<(), ApiError> {
+    let now = secs(SystemTime::now());
+    let hour = ROLLUP_RESOLUTION as u64;
+
+    // Only whole hours are rolled up, the raw samples of an hour are never split
+    let before = now.saturating_sub(config.raw_retention * 60 * 60) / hour * hour;
+    let expire_before = now.saturating_sub(config.rollup_retention * 24 * 60 * 60);
+
+    NodeMetric::compact(
+        db,
+        UNIX_EPOCH + Duration::from_secs(before),
+        UNIX_EPOCH + Duration::from_secs(expire_before),
+    )
+    .await
+}
+
+/// Fairing managing the metrics config and spawning the background collector once
+/// rocket has lifted off
+pub fn fairing() -> impl Fairing {
+    AdHoc::on_ignite("Metrics Config", |rocket| async {
+        let mut config: MetricsConfig = rocket
+            .figment()
+            .extract_inner("metrics")
+            .unwrap_or_default();
+        // A zero period would make the ticker panic
+        config.interval = config.interval.max(1);
+
+        rocket.manage(config).attach(liftoff())
+    })
+}
+
+fn liftoff() -> impl Fairing {
+    AdHoc::on_liftoff("Metrics Collector", |rocket| {
+        Box::pin(async move {
+            let (Some(config), Some(tls)) = (
+                rocket.state::<MetricsConfig>().cloned(),
+                rocket.state::<NodeTls>().cloned(),
+            ) else {
+                return;
+            };
+
+            if !config.enabled {
+                return;
+            }
+
+            let Some(pool) = Db::pool(rocket).cloned() else {
+                eprintln!("metrics collector: database pool is not available");
+                return;
+            };
+
+            rocket::tokio::spawn(async move {
+                let mut ticker = interval(Duration::from_secs(config.interval));
+
+                loop {
+                    ticker.tick().await;
+
+                    let Some(conn) = pool.get().await else {
+                        eprintln!("metrics collector: couldn't get a database connection");
+                        continue;
+                    };
+                    let db = Db(conn);
+
+                    if let Err((_, err)) = collect(&db, &tls, &config).await {
+                        eprintln!("metrics collector: collection failed: {err}");
+                    }
+
+                    if let Err((_, err)) = compact(&db, &config).await {
+                        eprintln!("metrics collector: compaction failed: {err}");
+                    }
+                }
+            });
+        })
+    })
+}
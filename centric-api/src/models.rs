@@ -1,29 +1,78 @@
 use crate::consts;
+use crate::metrics;
 use crate::node_models;
+use crate::node_tls::NodeTls;
 use crate::schema::*;
 use crate::Db;
+use api_types::v1::accounting::UserQuota;
+pub use api_types::v1::node::{EnrollRequest, EnrolledNode, NodeInfo};
+use api_types::v1::stats::{HwUsage, NetworkUsage};
+use api_types::v1::users::{ChExpMsg, ChGrpMsg, UserErrors, UserPasswordHash, UserStatus};
+use api_types::v1::Refusal;
+use api_types::v2::stats::{HwStats, NetStats, SystemStats};
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use diesel::prelude::*;
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use node_client::{NodeClient, NodeClientError};
 use reqwest::Error as ReqwestError;
 use rocket::http::Status;
 use rocket::serde::{Deserialize, Serialize};
 use std::cmp::{Eq, Ord, PartialEq, PartialOrd};
 use std::time::SystemTime;
 
-use crate::schema::nodes::{dsl::nodes as nodes_dsl, table as nodes_table};
+use crate::schema::nodes::{
+    approved as nodes_approved, dsl::nodes as nodes_dsl, last_seen as nodes_last_seen,
+    latency_ms as nodes_latency_ms, status as nodes_status, table as nodes_table,
+};
 use crate::schema::sells::{
-    dsl::sells as sells_dsl, ref_id as sells_ref_id, table as sells_table, user_id as sells_user_id,
+    dsl::sells as sells_dsl, invoice_date as sells_invoice_date,
+    password_hash as sells_password_hash, password_reset_at as sells_password_reset_at,
+    password_resets as sells_password_resets, ref_id as sells_ref_id, status as sells_status,
+    table as sells_table, user_id as sells_user_id,
 };
 use crate::schema::services::{dsl::services as services_dsl, table as services_table};
 use crate::schema::users::{dsl::users as users_dsl, ref_id as field_ref_id, table as users_table};
 
+use crate::schema::enrollment_codes::{
+    code_hash as enrollment_codes_code_hash, created_at as enrollment_codes_created_at,
+    dsl::enrollment_codes as enrollment_codes_dsl, expires_at as enrollment_codes_expires_at,
+    node_id as enrollment_codes_node_id, table as enrollment_codes_table,
+    used_at as enrollment_codes_used_at,
+};
+
+use crate::schema::expiry_log::{
+    action as expiry_log_action, created_at as expiry_log_created_at,
+    dry_run as expiry_log_dry_run, dsl::expiry_log as expiry_log_dsl, result as expiry_log_result,
+    sell_id as expiry_log_sell_id, table as expiry_log_table,
+};
+
+use crate::schema::node_metrics::{
+    dsl::node_metrics as node_metrics_dsl, node_id as node_metrics_node_id,
+    resolution as node_metrics_resolution, sampled_at as node_metrics_sampled_at,
+    table as node_metrics_table,
+};
+
+use crate::schema::node_net_metrics::{
+    dsl::node_net_metrics as node_net_metrics_dsl, interface as node_net_metrics_interface,
+    node_id as node_net_metrics_node_id, resolution as node_net_metrics_resolution,
+    sampled_at as node_net_metrics_sampled_at, table as node_net_metrics_table,
+};
+
+use crate::schema::renewals::{
+    created_at as renewals_created_at, dsl::renewals as renewals_dsl, sell_id as renewals_sell_id,
+    table as renewals_table,
+};
+
 use crate::schema::logins::{
     dsl::logins as logins_dsl, table as logins_table, username as login_username,
 };
 
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
 use diesel::result::Error as DieselError;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Integer;
 use rocket::serde::json::{json, Json, Value as JsonValue};
 
 pub type ApiError = (Status, JsonValue);
@@ -39,7 +88,7 @@ impl<T> JsonOk<T> {
     }
 }
 
-trait JsonResponseError {
+pub(crate) trait JsonResponseError {
     fn jsonify(&self) -> ApiError;
 }
 
@@ -125,15 +174,135 @@ impl JsonResponseError for ReqwestError {
     }
 }
 
+impl JsonResponseError for Refusal {
+    fn jsonify(&self) -> ApiError {
+        (
+            Status::InternalServerError,
+            json!({
+                "Err": {
+                    "type": "node",
+                    "code": 500,
+                    "msg": "request refused by the node",
+                    "raw_msg": self.msg()
+                }
+            }),
+        )
+    }
+}
+
+impl JsonResponseError for NodeClientError {
+    fn jsonify(&self) -> ApiError {
+        match self {
+            NodeClientError::Request(err) | NodeClientError::Decode(err) => err.jsonify(),
+            NodeClientError::Refused(refusal) => refusal.jsonify(),
+            NodeClientError::User(err) => err.jsonify(),
+            NodeClientError::DryRunIgnored => (
+                Status::InternalServerError,
+                json!({
+                    "Err": {
+                        "type": "node",
+                        "code": 500,
+                        "msg": format!("{}", self)
+                    }
+                }),
+            ),
+        }
+    }
+}
+
+impl JsonResponseError for UserErrors {
+    fn jsonify(&self) -> ApiError {
+        (
+            Status::InternalServerError,
+            json!({
+                "Err": {
+                    "type": "node",
+                    "code": 500,
+                    "msg": format!("{:?}", self)
+                }
+            }),
+        )
+    }
+}
+
 // NODES TABLE
+/// Health of a node as seen by the health monitor, stored as an integer in `nodes.status`
+#[derive(
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Ord,
+    Eq,
+    PartialEq,
+    PartialOrd,
+    AsExpression,
+    FromSqlRow,
+)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+#[diesel(sql_type = Integer)]
+pub enum NodeStatus {
+    /// Answers pings and statistics in time (`0`)
+    #[default]
+    Online = 0,
+    /// Answers pings, but slowly or without statistics (`1`)
+    Degraded = 1,
+    /// Doesn't answer pings, new sells are refused (`2`)
+    Offline = 2,
+}
+
+impl NodeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeStatus::Online => "online",
+            NodeStatus::Degraded => "degraded",
+            NodeStatus::Offline => "offline",
+        }
+    }
+}
+
+impl ToSql<Integer, Pg> for NodeStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let status = *self as i32;
+        <i32 as ToSql<Integer, Pg>>::to_sql(&status, &mut out.reborrow())
+    }
+}
+
+impl FromSql<Integer, Pg> for NodeStatus {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <i32 as FromSql<Integer, Pg>>::from_sql(bytes)? {
+            0 => Ok(NodeStatus::Online),
+            1 => Ok(NodeStatus::Degraded),
+            2 => Ok(NodeStatus::Offline),
+            status => Err(format!("unknown node status {status}").into()),
+        }
+    }
+}
+
 /// Structure representing a node in the system
 #[derive(Queryable, Serialize, Deserialize, Ord, Eq, PartialEq, PartialOrd)]
 #[diesel(table_name = nodes)]
 pub struct Node {
     pub id: i32,
     pub address: String,
-    pub token: String,
-    pub status: i32,
+    /// Key the node verifies tokens with, never sent back to clients, missing for nodes added
+    /// before keys existed until they're re-keyed
+    #[serde(skip_serializing)]
+    pub jwt_secret: Option<String>,
+    pub status: NodeStatus,
+    /// SHA-256 fingerprint of the node's TLS certificate, the only certificate trusted for it
+    pub tls_fingerprint: Option<String>,
+    /// Enrolled nodes are pending until an admin approves them, and can't be sold until then
+    pub approved: bool,
+    pub name: Option<String>,
+    pub location: Option<String>,
+    pub capacity: Option<i64>,
+    /// Last time the node answered a ping of the health monitor
+    pub last_seen: Option<SystemTime>,
+    /// Round trip of the last ping, missing while the node doesn't answer
+    pub latency_ms: Option<i32>,
 }
 
 impl Node {
@@ -183,6 +352,87 @@ impl Node {
         Ok(node_info)
     }
 
+    /// Asynchronously approves a pending node
+    pub async fn approve(db: &Db, id: i32) -> Result<Node, ApiError> {
+        let node_info = db
+            .run(move |conn| {
+                diesel::update(nodes_dsl.find(id))
+                    .set(nodes_approved.eq(true))
+                    .get_result::<Node>(conn)
+                    .map_err(|err| err.jsonify())
+            })
+            .await?;
+
+        Ok(node_info)
+    }
+
+    /// Asynchronously redeems an enrollment code and inserts the node it was redeemed for,
+    /// the code can only be used once and before it expires
+    pub async fn enroll(db: &Db, code_hash: String, node_value: NewNode) -> Result<Node, ApiError> {
+        db.run(move |conn| {
+            conn.transaction(|conn| {
+                let now = SystemTime::now();
+                let code_info = diesel::update(
+                    enrollment_codes_dsl
+                        .filter(enrollment_codes_code_hash.eq(code_hash))
+                        .filter(enrollment_codes_used_at.is_null())
+                        .filter(enrollment_codes_expires_at.gt(now)),
+                )
+                .set(enrollment_codes_used_at.eq(Some(now)))
+                .get_result::<EnrollmentCode>(conn)?;
+
+                let node_info = diesel::insert_into(nodes_table)
+                    .values(node_value)
+                    .get_result::<Node>(conn)?;
+
+                diesel::update(enrollment_codes_dsl.find(code_info.id))
+                    .set(enrollment_codes_node_id.eq(Some(node_info.id)))
+                    .execute(conn)?;
+
+                Ok(node_info)
+            })
+            .map_err(|err: DieselError| match err {
+                DieselError::NotFound => (
+                    Status::Forbidden,
+                    json!({
+                    "Err": {
+                        "type": "auth",
+                        "code": 403,
+                        "msg": "Invalid, used or expired enrollment code",
+                    }}),
+                ),
+                err => err.jsonify(),
+            })
+        })
+        .await
+    }
+
+    /// Asynchronously records the outcome of a health check, `last_seen` being left as is
+    /// when the node didn't answer
+    pub async fn record_health(
+        db: &Db,
+        id: i32,
+        status: NodeStatus,
+        latency_ms: Option<i32>,
+        last_seen: Option<SystemTime>,
+    ) -> Result<(), ApiError> {
+        db.run(move |conn| {
+            diesel::update(nodes_dsl.find(id))
+                .set((nodes_status.eq(status), nodes_latency_ms.eq(latency_ms)))
+                .execute(conn)?;
+
+            if let Some(last_seen) = last_seen {
+                diesel::update(nodes_dsl.find(id))
+                    .set(nodes_last_seen.eq(last_seen))
+                    .execute(conn)?;
+            }
+
+            Ok(())
+        })
+        .await
+        .map_err(|err: DieselError| err.jsonify())
+    }
+
     /// Asynchronously retrieves a list of all nodes from the database
     pub async fn list(db: &Db) -> Result<Vec<Node>, ApiError> {
         let nodes_list = db
@@ -211,123 +461,230 @@ impl Node {
 
     // REQWEST SECTION
 
+    /// Builds a client of the node presenting the client certificate and trusting the node's
+    /// pinned certificate, sharing the connection pool of the other clients of the node, its
+    /// commands being audited as `subject`
+    pub fn client(&self, tls: &NodeTls, subject: &str) -> Result<NodeClient, ApiError> {
+        let http = tls
+            .client(self.tls_fingerprint.as_deref())
+            .map_err(|err| err.jsonify())?;
+        let Some(jwt_secret) = &self.jwt_secret else {
+            return Err((
+                Status::InternalServerError,
+                json!({
+                "Err": {
+                    "type": "node",
+                    "code": 500,
+                    "msg": "Node has no jwt_secret, set one with /nodes/update_node",
+                }}),
+            ));
+        };
+
+        Ok(NodeClient::new(http, &self.address, self.id, jwt_secret).with_subject(subject))
+    }
+
     /// Asynchronously fetches hardware statistics from the node
-    pub async fn hw_stats(&self) -> Result<JsonValue, ApiError> {
-        let client = reqwest::Client::new();
-
-        let request_builder = client
-            .get(format!("{}{}", &self.address, consts::HW_STATS_PATH))
-            .header(
-                AUTHORIZATION,
-                HeaderValue::from_str(&format!("Bearer {}", &self.token)).unwrap(),
-            );
-
-        let node_api_response = request_builder.send().await.map_err(|err| err.jsonify())?;
-        Ok(node_api_response
-            .json()
+    pub async fn hw_stats(&self, tls: &NodeTls) -> Result<HwUsage, ApiError> {
+        self.client(tls, consts::NODE_TOKEN_SUBJECT)?
+            .hw_stats()
             .await
-            .map_err(|err| err.jsonify())?)
+            .map_err(|err| err.jsonify())
     }
 
     /// Asynchronously fetches network statistics from the node
-    pub async fn net_stats(&self) -> Result<JsonValue, ApiError> {
-        let client = reqwest::Client::new();
-
-        let request_builder = client
-            .get(format!("{}{}", &self.address, consts::NET_STATS_PATH))
-            .header(
-                AUTHORIZATION,
-                HeaderValue::from_str(&format!("Bearer {}", &self.token)).unwrap(),
-            );
-
-        let node_api_response = request_builder.send().await.map_err(|err| err.jsonify())?;
-        Ok(node_api_response
-            .json()
+    pub async fn net_stats(&self, tls: &NodeTls) -> Result<Vec<NetworkUsage>, ApiError> {
+        self.client(tls, consts::NODE_TOKEN_SUBJECT)?
+            .net_stats()
+            .await
+            .map_err(|err| err.jsonify())
+    }
+
+    /// Asynchronously fetches the raw hardware statistics of the node, with human-readable
+    /// summaries if `pretty`
+    pub async fn hw_stats_v2(&self, tls: &NodeTls, pretty: bool) -> Result<HwStats, ApiError> {
+        self.client(tls, consts::NODE_TOKEN_SUBJECT)?
+            .hw_stats_v2(pretty)
+            .await
+            .map_err(|err| err.jsonify())
+    }
+
+    /// Asynchronously fetches the raw network statistics and rates of the node, with
+    /// human-readable summaries if `pretty`
+    pub async fn net_stats_v2(&self, tls: &NodeTls, pretty: bool) -> Result<NetStats, ApiError> {
+        self.client(tls, consts::NODE_TOKEN_SUBJECT)?
+            .net_stats_v2(pretty)
+            .await
+            .map_err(|err| err.jsonify())
+    }
+
+    /// Asynchronously fetches the CPU, process, connection and account load of the node
+    pub async fn system_stats(&self, tls: &NodeTls) -> Result<SystemStats, ApiError> {
+        self.client(tls, consts::NODE_TOKEN_SUBJECT)?
+            .system_stats()
             .await
-            .map_err(|err| err.jsonify())?)
+            .map_err(|err| err.jsonify())
     }
 
     /// Asynchronously retrieves information about the node
-    pub async fn info(&self) -> Result<JsonValue, ApiError> {
-        let node_api_response =
-            reqwest::get(format!("{}{}", &self.address, consts::NODE_INFO_PATH))
-                .await
-                .map_err(|err| err.jsonify())?;
-
-        Ok(node_api_response
-            .json()
+    pub async fn info(&self, tls: &NodeTls) -> Result<NodeInfo, ApiError> {
+        self.client(tls, consts::NODE_TOKEN_SUBJECT)?
+            .node_info()
             .await
-            .map_err(|err| err.jsonify())?)
+            .map_err(|err| err.jsonify())
     }
 
-    /// Asynchronously changes the password for a user on the node
-    pub async fn change_pass(
+    /// Asynchronously locks a user on the node
+    pub async fn userlock(
         &self,
-        user_id: i32,
-        service_info: &Service,
-        account_info: &AccountInfo,
-    ) -> Result<node_models::SSHUser, ApiError> {
-        let sshuser_json = node_models::InputSSHUser::auto_gen(
-            service_info.max_logins,
-            user_id,
-            account_info.days,
-        );
-
-        let client = reqwest::Client::new();
-        let request_builder = client
-            .post(format!("{}{}", &self.address, consts::USERADD_PATH))
-            .header(
-                AUTHORIZATION,
-                HeaderValue::from_str(&format!("Bearer {}", &self.token)).unwrap(),
-            );
-
-        let node_api_response = request_builder
-            .json(&sshuser_json)
-            .send()
+        tls: &NodeTls,
+        subject: &str,
+        username: &str,
+    ) -> Result<UserStatus, ApiError> {
+        self.client(tls, subject)?
+            .userlock(username)
             .await
-            .map_err(|err| err.jsonify())?;
+            .map_err(|err| err.jsonify())
+    }
 
-        let sshuser_info = node_api_response
-            .json::<Result<node_models::SSHUser, JsonValue>>()
+    /// Asynchronously unlocks a user on the node
+    pub async fn userunlock(
+        &self,
+        tls: &NodeTls,
+        subject: &str,
+        username: &str,
+    ) -> Result<UserStatus, ApiError> {
+        self.client(tls, subject)?
+            .userunlock(username)
             .await
-            .map_err(|err| err.jsonify())?;
+            .map_err(|err| err.jsonify())
+    }
 
-        sshuser_info.map_err(|err| (Status::InternalServerError, err))
+    /// Asynchronously deletes a user from the node
+    pub async fn userdel(
+        &self,
+        tls: &NodeTls,
+        subject: &str,
+        username: &str,
+    ) -> Result<UserStatus, ApiError> {
+        self.client(tls, subject)?
+            .userdel(username)
+            .await
+            .map_err(|err| err.jsonify())
+    }
+
+    /// Asynchronously changes the expiry date of a user on the node
+    pub async fn chexp(
+        &self,
+        tls: &NodeTls,
+        subject: &str,
+        username: &str,
+        exp_date: &str,
+    ) -> Result<ChExpMsg, ApiError> {
+        self.client(tls, subject)?
+            .chexp(username, exp_date)
+            .await
+            .map_err(|err| err.jsonify())
+    }
+
+    /// Asynchronously resets the password of a user on the node to a random one
+    pub async fn reset_password(
+        &self,
+        tls: &NodeTls,
+        subject: &str,
+        username: &str,
+    ) -> Result<node_models::UserRawCreds, ApiError> {
+        self.client(tls, subject)?
+            .reset_password(username)
+            .await
+            .map_err(|err| err.jsonify())
+    }
+
+    /// Asynchronously changes the group of a user on the node
+    pub async fn chgrp(
+        &self,
+        tls: &NodeTls,
+        subject: &str,
+        username: &str,
+        group: &str,
+    ) -> Result<ChGrpMsg, ApiError> {
+        self.client(tls, subject)?
+            .chgrp(username, group)
+            .await
+            .map_err(|err| err.jsonify())
+    }
+
+    /// Asynchronously applies a step of a sell's renewal to a user on the node
+    pub async fn renewal_step(
+        &self,
+        tls: &NodeTls,
+        subject: &str,
+        username: &str,
+        step: &node_models::RenewalStep,
+    ) -> Result<(), ApiError> {
+        let client = self.client(tls, subject)?;
+        let applied = match step {
+            node_models::RenewalStep::ChExp(exp_date) => {
+                client.chexp(username, exp_date).await.map(drop)
+            }
+            node_models::RenewalStep::ChGrp(group) => client.chgrp(username, group).await.map(drop),
+            node_models::RenewalStep::ChLimits(limits) => client.chlimits(limits).await.map(drop),
+            node_models::RenewalStep::UserUnlock => client.userunlock(username).await.map(drop),
+            node_models::RenewalStep::UserLock => client.userlock(username).await.map(drop),
+        };
+
+        applied.map_err(|err| err.jsonify())
+    }
+
+    /// Asynchronously applies a service's traffic quota and login limit to a user on the node
+    pub async fn chlimits(
+        &self,
+        tls: &NodeTls,
+        subject: &str,
+        username: &str,
+        service_info: &Service,
+    ) -> Result<UserQuota, ApiError> {
+        let limits = node_models::service_limits(username, service_info)?;
+
+        self.client(tls, subject)?
+            .chlimits(&limits)
+            .await
+            .map_err(|err| err.jsonify())
+    }
+
+    /// Asynchronously changes the password for a user on the node
+    pub async fn change_pass(
+        &self,
+        tls: &NodeTls,
+        subject: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<UserPasswordHash, ApiError> {
+        self.client(tls, subject)?
+            .passwd(username, password)
+            .await
+            .map_err(|err| err.jsonify())
     }
 
     /// Asynchronously adds a new user to the node
     pub async fn useradd(
         &self,
+        tls: &NodeTls,
+        subject: &str,
         user_id: i32,
         service_info: &Service,
         account_info: &AccountInfo,
     ) -> Result<node_models::SSHUser, ApiError> {
-        let sshuser_json = node_models::InputSSHUser::auto_gen(
+        let sshuser_json = node_models::auto_gen_user(
             service_info.max_logins,
+            service_info.max_traffic,
             user_id,
             account_info.days,
-        );
-
-        let client = reqwest::Client::new();
-        let request_builder = client
-            .post(format!("{}{}", &self.address, consts::USERADD_PATH))
-            .header(
-                AUTHORIZATION,
-                HeaderValue::from_str(&format!("Bearer {}", &self.token)).unwrap(),
-            );
-
-        let node_api_response = request_builder
-            .json(&sshuser_json)
-            .send()
-            .await
-            .map_err(|err| err.jsonify())?;
+        )?;
 
-        let sshuser_info = node_api_response
-            .json::<Result<node_models::SSHUser, JsonValue>>()
+        self.client(tls, subject)?
+            .useradd(&sshuser_json)
             .await
-            .map_err(|err| err.jsonify())?;
-
-        sshuser_info.map_err(|err| (Status::InternalServerError, err))
+            .map_err(|err| err.jsonify())
     }
 }
 
@@ -337,8 +694,18 @@ impl Node {
 #[diesel(table_name = nodes)]
 pub struct NewNode {
     pub address: String,
-    pub token: String,
-    pub status: i32,
+    pub jwt_secret: String,
+    /// Online unless given, until the health monitor checks the node
+    #[serde(default)]
+    pub status: NodeStatus,
+    /// SHA-256 fingerprint of the node's TLS certificate, the only certificate trusted for it
+    pub tls_fingerprint: Option<String>,
+    /// Left to the database default (approved) unless the node enrolled itself
+    #[serde(skip_deserializing)]
+    pub approved: Option<bool>,
+    pub name: Option<String>,
+    pub location: Option<String>,
+    pub capacity: Option<i64>,
 }
 
 /// Structure representing information for updating a node
@@ -347,8 +714,78 @@ pub struct NewNode {
 #[diesel(table_name = nodes)]
 pub struct UpdateNode {
     pub address: Option<String>,
-    pub token: Option<String>,
-    pub status: Option<i32>,
+    pub jwt_secret: Option<String>,
+    pub status: Option<NodeStatus>,
+    pub tls_fingerprint: Option<String>,
+    pub name: Option<String>,
+    pub location: Option<String>,
+    pub capacity: Option<i64>,
+}
+
+// ENROLLMENT CODES TABLE
+
+/// Structure representing a one-time code a node enrolls itself with
+#[derive(Queryable, Serialize, Deserialize, Ord, Eq, PartialEq, PartialOrd)]
+#[diesel(table_name = enrollment_codes)]
+pub struct EnrollmentCode {
+    pub id: i32,
+    /// SHA-256 of the code, the code itself is only shown once when it's created
+    #[serde(skip_serializing)]
+    pub code_hash: String,
+    pub expires_at: SystemTime,
+    pub used_at: Option<SystemTime>,
+    /// Node enrolled with the code
+    pub node_id: Option<i32>,
+    pub created_at: SystemTime,
+}
+
+impl EnrollmentCode {
+    /// Asynchronously inserts a new enrollment code into the database
+    pub async fn insert(
+        db: &Db,
+        code_value: NewEnrollmentCode,
+    ) -> Result<EnrollmentCode, ApiError> {
+        let code_info = db
+            .run(move |conn| {
+                diesel::insert_into(enrollment_codes_table)
+                    .values(code_value)
+                    .get_result(conn)
+                    .map_err(|err| err.jsonify())
+            })
+            .await?;
+
+        Ok(code_info)
+    }
+
+    /// Asynchronously retrieves all enrollment codes, newest first
+    pub async fn list(db: &Db) -> Result<Vec<EnrollmentCode>, ApiError> {
+        let codes_list = db
+            .run(|conn| {
+                enrollment_codes_dsl
+                    .order(enrollment_codes_created_at.desc())
+                    .load(conn)
+                    .map_err(|err| err.jsonify())
+            })
+            .await?;
+
+        Ok(codes_list)
+    }
+}
+
+/// Structure representing information for creating a new enrollment code
+#[derive(Insertable, Serialize, Deserialize, Clone)]
+#[serde(crate = "rocket::serde")]
+#[diesel(table_name = enrollment_codes)]
+pub struct NewEnrollmentCode {
+    pub code_hash: String,
+    pub expires_at: SystemTime,
+}
+
+/// Structure representing a newly created enrollment code, along with the code itself
+#[derive(Serialize, Deserialize)]
+pub struct IssuedEnrollmentCode {
+    pub code: String,
+    pub enrollment_code: EnrollmentCode,
 }
 
 // SERVICES TABLE
@@ -359,6 +796,7 @@ pub struct UpdateNode {
 pub struct Service {
     pub id: i32,
     pub max_logins: i32,
+    /// Traffic quota in GiB (see `consts::TRAFFIC_UNIT`), `None` for unlimited
     pub max_traffic: Option<i32>,
     pub price: i32,
     pub available: bool,
@@ -450,6 +888,15 @@ pub struct NewService {
     pub available: Option<bool>,
 }
 
+impl NewService {
+    /// Checks that the login limit and traffic quota can be applied on nodes
+    pub fn validate(&self) -> Result<(), ApiError> {
+        node_models::login_limit(self.max_logins)?;
+        node_models::traffic_quota(self.max_traffic)?;
+        Ok(())
+    }
+}
+
 /// Structure representing information for updating a service
 #[derive(Insertable, Serialize, Deserialize, Clone, AsChangeset)]
 #[serde(crate = "rocket::serde")]
@@ -461,6 +908,17 @@ pub struct UpdateService {
     pub available: Option<bool>,
 }
 
+impl UpdateService {
+    /// Checks that the login limit and traffic quota, if changed, can be applied on nodes
+    pub fn validate(&self) -> Result<(), ApiError> {
+        if let Some(max_logins) = self.max_logins {
+            node_models::login_limit(max_logins)?;
+        }
+        node_models::traffic_quota(self.max_traffic)?;
+        Ok(())
+    }
+}
+
 // USERS TABLE
 /// Structure representing a user in the system
 #[derive(Queryable, Serialize, Deserialize, Ord, Eq, PartialEq, PartialOrd)]
@@ -567,9 +1025,11 @@ pub struct Sell {
     pub firstbuy_date: Option<SystemTime>,
     pub invoice_date: Option<SystemTime>,
     pub username: Option<String>,
-    pub password: Option<String>,
     pub password_hash: Option<String>,
     pub status: i32,
+    /// Number of times the password was reset
+    pub password_resets: i32,
+    pub password_reset_at: Option<SystemTime>,
 }
 
 /// Implementation of operations related to sells in the system
@@ -639,6 +1099,43 @@ impl Sell {
         Ok(sells_list)
     }
 
+    /// Asynchronously stores the hash of a reset password and counts the reset
+    pub async fn record_password_reset(
+        db: &Db,
+        id: i32,
+        password_hash: String,
+    ) -> Result<Sell, ApiError> {
+        let sell_info = db
+            .run(move |conn| {
+                diesel::update(sells_dsl.find(id))
+                    .set((
+                        sells_password_hash.eq(Some(password_hash)),
+                        sells_password_resets.eq(sells_password_resets + 1),
+                        sells_password_reset_at.eq(Some(SystemTime::now())),
+                    ))
+                    .get_result(conn)
+                    .map_err(|err| err.jsonify())
+            })
+            .await?;
+
+        Ok(sell_info)
+    }
+
+    /// Asynchronously retrieves the active sells whose invoice date has passed
+    pub async fn list_overdue(db: &Db, now: SystemTime) -> Result<Vec<Sell>, ApiError> {
+        let sells_list = db
+            .run(move |conn| {
+                sells_table
+                    .filter(sells_status.eq(consts::SELL_ACTIVE))
+                    .filter(sells_invoice_date.lt(now))
+                    .load(conn)
+                    .map_err(|err| err.jsonify())
+            })
+            .await?;
+
+        Ok(sells_list)
+    }
+
     /// Asynchronously retrieves a list of sells by a given user ID
     pub async fn list_by_user(db: &Db, user_id: i64) -> Result<Vec<Sell>, ApiError> {
         let sells_list = db
@@ -666,7 +1163,6 @@ pub struct NewSell {
     pub firstbuy_date: Option<SystemTime>,
     pub invoice_date: Option<SystemTime>,
     pub username: Option<String>,
-    pub password: Option<String>,
     pub password_hash: Option<String>,
     pub status: Option<i32>,
 }
@@ -686,9 +1182,8 @@ impl NewSell {
             firstbuy_date: None,
             invoice_date: None,
             username: None,
-            password: None,
             password_hash: None,
-            status: Some(1),
+            status: Some(consts::SELL_UNVERIFIED),
         }
     }
 }
@@ -705,7 +1200,6 @@ pub struct UpdateSell {
     pub firstbuy_date: Option<SystemTime>,
     pub invoice_date: Option<SystemTime>,
     pub username: Option<String>,
-    pub password: Option<String>,
     pub password_hash: Option<String>,
     pub status: Option<i32>,
 }
@@ -723,13 +1217,449 @@ impl UpdateSell {
             firstbuy_date: Some(now),
             invoice_date: Some(invoice_date),
             username: Some(sshuser.username),
-            password: None,
             password_hash: Some(sshuser.password_hash),
-            status: Some(0),
+            status: Some(consts::SELL_ACTIVE),
+        }
+    }
+
+    pub fn renew(service_id: i32, invoice_date: SystemTime) -> UpdateSell {
+        UpdateSell {
+            user_id: None,
+            service_id: Some(service_id),
+            node_id: None,
+            ref_id: None,
+            firstbuy_date: None,
+            invoice_date: Some(invoice_date),
+            username: None,
+            password_hash: None,
+            status: Some(consts::SELL_ACTIVE),
+        }
+    }
+
+    /// Restores the service, invoice date and status a sell had before a renewal
+    pub fn restore(sell: &Sell) -> UpdateSell {
+        UpdateSell {
+            user_id: None,
+            service_id: Some(sell.service_id),
+            node_id: None,
+            ref_id: None,
+            firstbuy_date: None,
+            invoice_date: sell.invoice_date,
+            username: None,
+            password_hash: None,
+            status: Some(sell.status),
+        }
+    }
+
+    pub fn expire() -> UpdateSell {
+        UpdateSell {
+            user_id: None,
+            service_id: None,
+            node_id: None,
+            ref_id: None,
+            firstbuy_date: None,
+            invoice_date: None,
+            username: None,
+            password_hash: None,
+            status: Some(consts::SELL_EXPIRED),
         }
     }
 }
 
+// RENEWALS TABLE
+
+/// Structure representing a renewal of a sell
+#[derive(Queryable, Serialize, Deserialize, Ord, Eq, PartialEq, PartialOrd)]
+#[diesel(table_name = renewals)]
+pub struct Renewal {
+    pub id: i32,
+    pub sell_id: i32,
+    pub service_id: i32,
+    pub days: i32,
+    /// Price of the service at the time of the renewal
+    pub price: i32,
+    pub previous_invoice_date: Option<SystemTime>,
+    pub invoice_date: SystemTime,
+    pub created_at: SystemTime,
+}
+
+/// Implementation of operations related to renewals
+impl Renewal {
+    /// Asynchronously applies a renewal to its sell and records it, in a single transaction
+    pub async fn apply(
+        db: &Db,
+        update_sell: UpdateSell,
+        renewal_value: NewRenewal,
+    ) -> Result<(Sell, Renewal), ApiError> {
+        db.run(move |conn| {
+            conn.transaction(|conn| {
+                let sell_info = diesel::update(sells_dsl.find(renewal_value.sell_id))
+                    .set(update_sell)
+                    .get_result::<Sell>(conn)?;
+
+                let renewal_info = diesel::insert_into(renewals_table)
+                    .values(renewal_value)
+                    .get_result::<Renewal>(conn)?;
+
+                Ok((sell_info, renewal_info))
+            })
+        })
+        .await
+        .map_err(|err: DieselError| err.jsonify())
+    }
+
+    /// Asynchronously reverts an applied renewal, restoring its sell and deleting the record,
+    /// in a single transaction
+    pub async fn revert(
+        db: &Db,
+        renewal_id: i32,
+        restore_sell: UpdateSell,
+    ) -> Result<(), ApiError> {
+        db.run(move |conn| {
+            conn.transaction(|conn| {
+                let renewal_info =
+                    diesel::delete(renewals_dsl.find(renewal_id)).get_result::<Renewal>(conn)?;
+
+                diesel::update(sells_dsl.find(renewal_info.sell_id))
+                    .set(restore_sell)
+                    .execute(conn)?;
+
+                Ok(())
+            })
+        })
+        .await
+        .map_err(|err: DieselError| err.jsonify())
+    }
+
+    /// Asynchronously inserts a new renewal into the database
+    pub async fn insert(db: &Db, renewal_value: NewRenewal) -> Result<Renewal, ApiError> {
+        let renewal_info = db
+            .run(move |conn| {
+                diesel::insert_into(renewals_table)
+                    .values(renewal_value)
+                    .get_result(conn)
+                    .map_err(|err| err.jsonify())
+            })
+            .await?;
+
+        Ok(renewal_info)
+    }
+
+    /// Asynchronously retrieves a list of all renewals
+    pub async fn list(db: &Db) -> Result<Vec<Renewal>, ApiError> {
+        let renewals_list = db
+            .run(|conn| {
+                renewals_dsl
+                    .order(renewals_created_at.desc())
+                    .load(conn)
+                    .map_err(|err| err.jsonify())
+            })
+            .await?;
+
+        Ok(renewals_list)
+    }
+
+    /// Asynchronously retrieves the renewals of a given sell
+    pub async fn list_by_sell(db: &Db, sell_id: i32) -> Result<Vec<Renewal>, ApiError> {
+        let renewals_list = db
+            .run(move |conn| {
+                renewals_table
+                    .filter(renewals_sell_id.eq(sell_id))
+                    .order(renewals_created_at.desc())
+                    .load(conn)
+                    .map_err(|err| err.jsonify())
+            })
+            .await?;
+
+        Ok(renewals_list)
+    }
+}
+
+/// Structure representing information for creating a new renewal
+#[derive(Insertable, Serialize, Deserialize, Clone)]
+#[serde(crate = "rocket::serde")]
+#[diesel(table_name = renewals)]
+pub struct NewRenewal {
+    pub sell_id: i32,
+    pub service_id: i32,
+    pub days: i32,
+    pub price: i32,
+    pub previous_invoice_date: Option<SystemTime>,
+    pub invoice_date: SystemTime,
+}
+
+// EXPIRY LOG TABLE
+
+/// Structure representing an action taken (or planned, in dry-run) by the expiry sweeper
+#[derive(Queryable, Serialize, Deserialize, Ord, Eq, PartialEq, PartialOrd)]
+#[diesel(table_name = expiry_log)]
+pub struct ExpiryLog {
+    pub id: i32,
+    pub sell_id: i32,
+    pub node_id: i32,
+    pub username: String,
+    pub action: String,
+    pub dry_run: bool,
+    pub result: String,
+    pub created_at: SystemTime,
+}
+
+/// Implementation of operations related to the expiry log
+impl ExpiryLog {
+    /// Asynchronously inserts a new expiry log record into the database
+    pub async fn insert(db: &Db, log_value: NewExpiryLog) -> Result<ExpiryLog, ApiError> {
+        let log_info = db
+            .run(move |conn| {
+                diesel::insert_into(expiry_log_table)
+                    .values(log_value)
+                    .get_result(conn)
+                    .map_err(|err| err.jsonify())
+            })
+            .await?;
+
+        Ok(log_info)
+    }
+
+    /// Asynchronously checks if the given action was already planned for a sell in dry-run
+    pub async fn is_planned(db: &Db, sell_id: i32, action: String) -> Result<bool, ApiError> {
+        let logs_count = db
+            .run(move |conn| {
+                expiry_log_dsl
+                    .filter(expiry_log_sell_id.eq(sell_id))
+                    .filter(expiry_log_action.eq(&action))
+                    .filter(expiry_log_dry_run.eq(true))
+                    .filter(expiry_log_result.eq(consts::SWEEP_PLANNED))
+                    .execute(conn)
+                    .map_err(|err| err.jsonify())
+            })
+            .await?;
+
+        Ok(logs_count != 0)
+    }
+
+    /// Asynchronously retrieves the expiry log, newest first
+    pub async fn list(db: &Db) -> Result<Vec<ExpiryLog>, ApiError> {
+        let logs_list = db
+            .run(|conn| {
+                expiry_log_dsl
+                    .order(expiry_log_created_at.desc())
+                    .load(conn)
+                    .map_err(|err| err.jsonify())
+            })
+            .await?;
+
+        Ok(logs_list)
+    }
+}
+
+/// Structure representing information for creating a new expiry log record
+#[derive(Insertable, Serialize, Deserialize, Clone)]
+#[serde(crate = "rocket::serde")]
+#[diesel(table_name = expiry_log)]
+pub struct NewExpiryLog {
+    pub sell_id: i32,
+    pub node_id: i32,
+    pub username: String,
+    pub action: String,
+    pub dry_run: bool,
+    pub result: String,
+}
+
+// NODE METRICS TABLES
+
+/// Structure representing a sample of a node's hardware statistics, either taken by the
+/// metrics collector or averaged over `resolution` seconds
+#[derive(Queryable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = node_metrics)]
+pub struct NodeMetric {
+    pub id: i32,
+    pub node_id: i32,
+    /// Seconds the sample stands for
+    pub resolution: i32,
+    pub sampled_at: SystemTime,
+    pub load_one: f32,
+    pub load_five: f32,
+    pub load_fifteen: f32,
+    pub memory_used: i64,
+    pub memory_total: i64,
+    pub swap_used: i64,
+    pub swap_total: i64,
+    pub disk_used: i64,
+    pub disk_total: i64,
+}
+
+/// Implementation of operations related to node metrics
+impl NodeMetric {
+    /// Asynchronously inserts a sample of a node along with the counters of its interfaces
+    pub async fn insert(
+        db: &Db,
+        metric_value: NewNodeMetric,
+        interfaces: Vec<NewNodeNetMetric>,
+    ) -> Result<(), ApiError> {
+        db.run(move |conn| {
+            conn.transaction(|conn| {
+                diesel::insert_into(node_metrics_table)
+                    .values(metric_value)
+                    .execute(conn)?;
+
+                diesel::insert_into(node_net_metrics_table)
+                    .values(interfaces)
+                    .execute(conn)?;
+
+                Ok(())
+            })
+        })
+        .await
+        .map_err(|err: DieselError| err.jsonify())
+    }
+
+    /// Asynchronously retrieves the samples of a node and of its interfaces taken between
+    /// `from` and `to`, oldest first
+    pub async fn list(
+        db: &Db,
+        node_id: i32,
+        from: SystemTime,
+        to: SystemTime,
+    ) -> Result<(Vec<NodeMetric>, Vec<NodeNetMetric>), ApiError> {
+        db.run(move |conn| {
+            let samples = node_metrics_table
+                .filter(node_metrics_node_id.eq(node_id))
+                .filter(node_metrics_sampled_at.between(from, to))
+                .order(node_metrics_sampled_at.asc())
+                .load(conn)?;
+
+            let interfaces = node_net_metrics_table
+                .filter(node_net_metrics_node_id.eq(node_id))
+                .filter(node_net_metrics_sampled_at.between(from, to))
+                .order((
+                    node_net_metrics_interface.asc(),
+                    node_net_metrics_sampled_at.asc(),
+                ))
+                .load(conn)?;
+
+            Ok((samples, interfaces))
+        })
+        .await
+        .map_err(|err: DieselError| err.jsonify())
+    }
+
+    /// Asynchronously replaces the raw samples taken before `before` with their hourly
+    /// rollups, and deletes every sample taken before `expire_before`
+    pub async fn compact(
+        db: &Db,
+        before: SystemTime,
+        expire_before: SystemTime,
+    ) -> Result<(), ApiError> {
+        let resolution = metrics::ROLLUP_RESOLUTION;
+
+        db.run(move |conn| {
+            conn.transaction(|conn| {
+                let samples = node_metrics_table
+                    .filter(node_metrics_resolution.lt(resolution))
+                    .filter(node_metrics_sampled_at.lt(before))
+                    .load::<NodeMetric>(conn)?;
+
+                let interfaces = node_net_metrics_table
+                    .filter(node_net_metrics_resolution.lt(resolution))
+                    .filter(node_net_metrics_sampled_at.lt(before))
+                    .load::<NodeNetMetric>(conn)?;
+
+                let (samples, interfaces) = metrics::rollup(&samples, &interfaces);
+
+                diesel::insert_into(node_metrics_table)
+                    .values(samples)
+                    .execute(conn)?;
+
+                diesel::insert_into(node_net_metrics_table)
+                    .values(interfaces)
+                    .execute(conn)?;
+
+                diesel::delete(
+                    node_metrics_dsl
+                        .filter(node_metrics_resolution.lt(resolution))
+                        .filter(node_metrics_sampled_at.lt(before)),
+                )
+                .execute(conn)?;
+
+                diesel::delete(
+                    node_net_metrics_dsl
+                        .filter(node_net_metrics_resolution.lt(resolution))
+                        .filter(node_net_metrics_sampled_at.lt(before)),
+                )
+                .execute(conn)?;
+
+                diesel::delete(node_metrics_dsl.filter(node_metrics_sampled_at.lt(expire_before)))
+                    .execute(conn)?;
+
+                diesel::delete(
+                    node_net_metrics_dsl.filter(node_net_metrics_sampled_at.lt(expire_before)),
+                )
+                .execute(conn)?;
+
+                Ok(())
+            })
+        })
+        .await
+        .map_err(|err: DieselError| err.jsonify())
+    }
+}
+
+/// Structure representing information for creating a new node metrics sample
+#[derive(Insertable, Serialize, Deserialize, Clone)]
+#[serde(crate = "rocket::serde")]
+#[diesel(table_name = node_metrics)]
+pub struct NewNodeMetric {
+    pub node_id: i32,
+    pub resolution: i32,
+    pub sampled_at: SystemTime,
+    pub load_one: f32,
+    pub load_five: f32,
+    pub load_fifteen: f32,
+    pub memory_used: i64,
+    pub memory_total: i64,
+    pub swap_used: i64,
+    pub swap_total: i64,
+    pub disk_used: i64,
+    pub disk_total: i64,
+}
+
+/// Structure representing the rx/tx counters of a node's interface at the time of a sample
+#[derive(Queryable, Serialize, Deserialize, Clone, Debug)]
+#[diesel(table_name = node_net_metrics)]
+pub struct NodeNetMetric {
+    pub id: i32,
+    pub node_id: i32,
+    /// Seconds the sample stands for
+    pub resolution: i32,
+    pub sampled_at: SystemTime,
+    pub interface: String,
+    /// Bytes received since the interface came up
+    pub rx: i64,
+    /// Bytes sent since the interface came up
+    pub tx: i64,
+}
+
+/// Structure representing information for creating a new interface counters sample
+#[derive(Insertable, Serialize, Deserialize, Clone)]
+#[serde(crate = "rocket::serde")]
+#[diesel(table_name = node_net_metrics)]
+pub struct NewNodeNetMetric {
+    pub node_id: i32,
+    pub resolution: i32,
+    pub sampled_at: SystemTime,
+    pub interface: String,
+    pub rx: i64,
+    pub tx: i64,
+}
+
+/// Structure representing a renewal request, either extending the current service or
+/// switching to another one
+#[derive(Serialize, Deserialize)]
+pub struct RenewInfo {
+    pub days: Option<i64>,
+    pub service_id: Option<i32>,
+}
+
 /// Structure representing login information
 #[derive(Queryable, Serialize, Deserialize, Ord, Eq, PartialEq, PartialOrd)]
 #[diesel(table_name = logins)]
@@ -847,6 +1777,14 @@ pub struct UpdateLogin {
     pub admin: Option<bool>,
 }
 
+/// Structure representing a sell along with its freshly reset password, which is only
+/// returned here and never stored
+#[derive(Serialize, Deserialize)]
+pub struct PasswordReset {
+    pub sell: Sell,
+    pub password: String,
+}
+
 /// Structure representing account information
 #[derive(Serialize, Deserialize)]
 pub struct AccountInfo {
@@ -859,4 +1797,7 @@ pub struct LoginInfo {
     pub admin_key: Option<String>,
     pub username: String,
     pub password: String,
+    /// Registers an admin login rather than a read-only one, ignored when logging in
+    #[serde(default)]
+    pub admin: bool,
 }
@@ -1,43 +1,144 @@
 use crate::consts;
-use rocket::serde::{Deserialize, Serialize};
-
-/// Represents an SSH user with serialization and deserialization support
-#[derive(Deserialize, Serialize)]
-pub struct SSHUser {
-    pub username: String,
-    pub password_hash: String,
-    pub shell: String,
-    pub usergroup: String,
-    pub exp_date: String,
-}
-
-/// Represents an input SSH user with serialization and deserialization support
-#[derive(Deserialize, Serialize)]
-pub struct InputSSHUser {
-    pub username: String,
-    pub password: String,
-    pub exp_date: String,
-    pub group: String,
-    pub shell: Option<String>,
-}
-
-/// Implementation of operations related to InputSSHUser
-impl InputSSHUser {
-    /// Generates an InputSSHUser with auto-generated values
-    pub fn auto_gen(max_logins: i32, user_id: i32, days: Option<i64>) -> InputSSHUser {
-        let username = format!("{0}{max_logins}x{user_id:03}", consts::PREFIX);
-        let group = format!("{0}{max_logins}", consts::GROUP_PREFIX);
-        let password = crate::gen_password();
-
-        let exp_days = days.unwrap_or(30);
-        let exp_date = crate::add_to_time(exp_days);
-
-        InputSSHUser {
-            username,
-            password,
-            exp_date,
-            group,
-            shell: None,
+use crate::models::{ApiError, Service};
+use rocket::http::Status;
+use rocket::serde::json::json;
+
+pub use api_types::v1::requests::{InputSSHUser, UserLimits};
+pub use api_types::v1::users::{SSHUser, UserRawCreds};
+
+/// Generates an InputSSHUser with auto-generated values
+pub fn auto_gen_user(
+    max_logins: i32,
+    max_traffic: Option<i32>,
+    user_id: i32,
+    days: Option<i64>,
+) -> Result<InputSSHUser, ApiError> {
+    let username = format!("{0}{max_logins}x{user_id:03}", consts::PREFIX);
+    let group = service_group(max_logins);
+    let password = crate::gen_password();
+
+    let exp_days = days.unwrap_or(30);
+    let exp_date = crate::add_to_time(exp_days);
+
+    Ok(InputSSHUser {
+        username,
+        password,
+        exp_date,
+        group,
+        shell: None,
+        traffic_quota: traffic_quota(max_traffic)?,
+        max_logins: Some(login_limit(max_logins)?),
+    })
+}
+
+/// Builds the traffic quota and login limit of a user from the given service
+pub fn service_limits(username: &str, service_info: &Service) -> Result<UserLimits, ApiError> {
+    Ok(UserLimits {
+        username: username.to_string(),
+        traffic_quota: traffic_quota(service_info.max_traffic)?,
+        max_logins: Some(login_limit(service_info.max_logins)?),
+    })
+}
+
+/// Converts a service's `max_traffic` in units of `consts::TRAFFIC_UNIT` to bytes
+pub fn traffic_quota(max_traffic: Option<i32>) -> Result<Option<u64>, ApiError> {
+    max_traffic
+        .map(|max_traffic| {
+            u64::try_from(max_traffic)
+                .ok()
+                .and_then(|max_traffic| max_traffic.checked_mul(consts::TRAFFIC_UNIT))
+                .ok_or_else(|| invalid("max_traffic can't be negative"))
+        })
+        .transpose()
+}
+
+/// Converts a service's `max_logins` to the login limit of its users
+pub fn login_limit(max_logins: i32) -> Result<u32, ApiError> {
+    u32::try_from(max_logins)
+        .ok()
+        .filter(|max_logins| *max_logins > 0)
+        .ok_or_else(|| invalid("max_logins should be positive"))
+}
+
+fn invalid(msg: &str) -> ApiError {
+    (
+        Status::UnprocessableEntity,
+        json!({ "Err": { "msg": msg } }),
+    )
+}
+
+/// Returns the group name users of a service with the given login limit belong to
+pub fn service_group(max_logins: i32) -> String {
+    format!("{0}{max_logins}", consts::GROUP_PREFIX)
+}
+
+/// A change made to a user on their node while renewing their sell
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenewalStep {
+    ChExp(String),
+    ChGrp(String),
+    ChLimits(UserLimits),
+    UserUnlock,
+    UserLock,
+}
+
+/// The changes renewing a sell makes on its node, with the ones restoring the user as it was
+pub struct NodeRenewal {
+    pub steps: Vec<RenewalStep>,
+    /// Reverts each of `steps`, at the same index
+    undo_steps: Vec<RenewalStep>,
+}
+
+impl NodeRenewal {
+    /// Plans the renewal of `username` from `previous_service` and `previous_exp_date` (`None`
+    /// for no expiry) to `service` and `exp_date`, lifting the expiry sweeper's lock if `unlock`
+    pub fn new(
+        username: &str,
+        previous_service: &Service,
+        service: &Service,
+        previous_exp_date: Option<String>,
+        exp_date: String,
+        unlock: bool,
+    ) -> Result<NodeRenewal, ApiError> {
+        let mut renewal = NodeRenewal {
+            steps: Vec::new(),
+            undo_steps: Vec::new(),
+        };
+
+        // `chexp` takes -1 to remove the expiry
+        renewal.push(
+            RenewalStep::ChExp(exp_date),
+            RenewalStep::ChExp(previous_exp_date.unwrap_or_else(|| "-1".to_string())),
+        );
+        if service.max_logins != previous_service.max_logins {
+            renewal.push(
+                RenewalStep::ChGrp(service_group(service.max_logins)),
+                RenewalStep::ChGrp(service_group(previous_service.max_logins)),
+            );
         }
+        renewal.push(
+            RenewalStep::ChLimits(service_limits(username, service)?),
+            RenewalStep::ChLimits(service_limits(username, previous_service)?),
+        );
+        if unlock {
+            renewal.push(RenewalStep::UserUnlock, RenewalStep::UserLock);
+        }
+
+        Ok(renewal)
+    }
+
+    fn push(&mut self, step: RenewalStep, undo_step: RenewalStep) {
+        self.steps.push(step);
+        self.undo_steps.push(undo_step);
+    }
+
+    /// Returns the changes reverting the first `attempted` steps, the last one first. The step
+    /// that failed is reverted as well, since the node may have applied it before failing
+    pub fn undo(&self, attempted: usize) -> Vec<RenewalStep> {
+        self.undo_steps[..attempted.min(self.undo_steps.len())]
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
     }
 }
@@ -0,0 +1,207 @@
+use reqwest::Error as ReqwestError;
+use rocket::fairing::{AdHoc, Fairing};
+use rocket::serde::Deserialize;
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, ServerName};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Structure representing the `node_tls` section of Rocket.toml
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(crate = "rocket::serde", default)]
+pub struct NodeTlsConfig {
+    /// Path of the PEM client certificate chain presented to nodes
+    pub client_cert: Option<String>,
+    /// Path of the PEM private key of the client certificate
+    pub client_key: Option<String>,
+    /// Path of the PEM CA certificates node certificates are verified against when no
+    /// fingerprint is pinned
+    pub ca_certs: Option<String>,
+    /// Whether `http://` node addresses are accepted
+    pub allow_http: bool,
+}
+
+/// Structure holding the certificates loaded from `NodeTlsConfig`
+#[derive(Clone)]
+pub struct NodeTls {
+    client_auth: Option<(Vec<Certificate>, PrivateKey)>,
+    roots: RootCertStore,
+    pub allow_http: bool,
+    /// HTTP clients by pinned fingerprint, so requests to a node reuse its connections
+    clients: Arc<Mutex<HashMap<Option<String>, reqwest::Client>>>,
+}
+
+impl NodeTls {
+    /// Loads the client certificate and CA certificates from the configured paths
+    pub fn load(config: &NodeTlsConfig) -> Result<NodeTls, String> {
+        let client_auth = match (&config.client_cert, &config.client_key) {
+            (Some(cert), Some(key)) => Some((read_certs(cert)?, read_key(key)?)),
+            (None, None) => None,
+            _ => return Err("client_cert and client_key should be set together".to_string()),
+        };
+
+        if let Some((certs, key)) = &client_auth {
+            ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(RootCertStore::empty())
+                .with_client_auth_cert(certs.clone(), key.clone())
+                .map_err(|err| format!("invalid client certificate or key: {err}"))?;
+        }
+
+        let mut roots = RootCertStore::empty();
+        if let Some(ca_certs) = &config.ca_certs {
+            for cert in read_certs(ca_certs)? {
+                roots
+                    .add(&cert)
+                    .map_err(|err| format!("invalid CA certificate in {ca_certs}: {err}"))?;
+            }
+        }
+
+        Ok(NodeTls {
+            client_auth,
+            roots,
+            allow_http: config.allow_http,
+            clients: Default::default(),
+        })
+    }
+
+    /// Returns the HTTP client presenting the client certificate, if any, and trusting only the
+    /// certificate with the given fingerprint, or any certificate signed by the configured CA
+    /// when none is pinned. Clients are built once per fingerprint and share their connection
+    /// pool afterwards
+    pub fn client(&self, fingerprint: Option<&str>) -> Result<reqwest::Client, ReqwestError> {
+        let key = fingerprint.map(str::to_string);
+
+        if let Some(client) = self.clients.lock().unwrap().get(&key) {
+            return Ok(client.clone());
+        }
+
+        let client = self.build_client(fingerprint)?;
+        self.clients.lock().unwrap().insert(key, client.clone());
+
+        Ok(client)
+    }
+
+    fn build_client(&self, fingerprint: Option<&str>) -> Result<reqwest::Client, ReqwestError> {
+        let builder = ClientConfig::builder().with_safe_defaults();
+
+        let builder = match fingerprint {
+            Some(fingerprint) => builder.with_custom_certificate_verifier(Arc::new(PinnedCert {
+                fingerprint: fingerprint.to_string(),
+            })),
+            None => builder.with_custom_certificate_verifier(Arc::new(WebPkiVerifier::new(
+                self.roots.clone(),
+                None,
+            ))),
+        };
+
+        let tls_config = match &self.client_auth {
+            Some((certs, key)) => builder
+                .with_client_auth_cert(certs.clone(), key.clone())
+                .expect("client certificate was checked on load"),
+            None => builder.with_no_client_auth(),
+        };
+
+        node_client::client_builder()
+            .use_preconfigured_tls(tls_config)
+            .build()
+    }
+}
+
+/// Certificate verifier accepting only the certificate with the pinned SHA-256 fingerprint
+struct PinnedCert {
+    fingerprint: String,
+}
+
+impl ServerCertVerifier for PinnedCert {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if fingerprint(&end_entity.0) == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(
+                "node certificate doesn't match the pinned fingerprint".to_string(),
+            ))
+        }
+    }
+}
+
+/// Returns the lowercase hex SHA-256 fingerprint of a DER certificate
+pub fn fingerprint(der: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(der))
+}
+
+/// Normalizes a SHA-256 fingerprint given as hex, with or without `:` separators (as printed
+/// by `openssl x509 -fingerprint -sha256`)
+pub fn parse_fingerprint(fingerprint: &str) -> Option<String> {
+    let fingerprint = fingerprint
+        .trim()
+        .trim_start_matches("sha256 Fingerprint=")
+        .replace(':', "")
+        .to_lowercase();
+
+    if fingerprint.len() == 64 && fingerprint.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(fingerprint)
+    } else {
+        None
+    }
+}
+
+fn read_certs(path: &str) -> Result<Vec<Certificate>, String> {
+    let file = File::open(path).map_err(|err| format!("couldn't open {path}: {err}"))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .map_err(|err| format!("couldn't read certificates from {path}: {err}"))?;
+
+    if certs.is_empty() {
+        return Err(format!("no certificates in {path}"));
+    }
+
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn read_key(path: &str) -> Result<PrivateKey, String> {
+    let file = File::open(path).map_err(|err| format!("couldn't open {path}: {err}"))?;
+    let mut reader = BufReader::new(file);
+
+    loop {
+        match rustls_pemfile::read_one(&mut reader)
+            .map_err(|err| format!("couldn't read the private key from {path}: {err}"))?
+        {
+            Some(rustls_pemfile::Item::PKCS8Key(key))
+            | Some(rustls_pemfile::Item::RSAKey(key))
+            | Some(rustls_pemfile::Item::ECKey(key)) => return Ok(PrivateKey(key)),
+            Some(_) => continue,
+            None => return Err(format!("no private key in {path}")),
+        }
+    }
+}
+
+/// Fairing loading the certificates of the `node_tls` section, aborting the launch if they
+/// can't be read
+pub fn fairing() -> impl Fairing {
+    AdHoc::try_on_ignite("Node TLS", |rocket| async {
+        let config: NodeTlsConfig = rocket
+            .figment()
+            .extract_inner("node_tls")
+            .unwrap_or_default();
+
+        match NodeTls::load(&config) {
+            Ok(node_tls) => Ok(rocket.manage(node_tls)),
+            Err(err) => {
+                eprintln!("Couldn't load the node TLS certificates: {err}");
+                Err(rocket)
+            }
+        }
+    })
+}
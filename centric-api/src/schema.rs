@@ -1,5 +1,29 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    enrollment_codes (id) {
+        id -> Int4,
+        code_hash -> Text,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        node_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    expiry_log (id) {
+        id -> Int4,
+        sell_id -> Int4,
+        node_id -> Int4,
+        username -> Text,
+        action -> Text,
+        dry_run -> Bool,
+        result -> Text,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     logins (id) {
         id -> Int4,
@@ -14,8 +38,58 @@ diesel::table! {
     nodes (id) {
         id -> Int4,
         address -> Text,
-        token -> Text,
+        jwt_secret -> Nullable<Text>,
         status -> Int4,
+        tls_fingerprint -> Nullable<Text>,
+        approved -> Bool,
+        name -> Nullable<Text>,
+        location -> Nullable<Text>,
+        capacity -> Nullable<Int8>,
+        last_seen -> Nullable<Timestamp>,
+        latency_ms -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    node_metrics (id) {
+        id -> Int4,
+        node_id -> Int4,
+        resolution -> Int4,
+        sampled_at -> Timestamp,
+        load_one -> Float4,
+        load_five -> Float4,
+        load_fifteen -> Float4,
+        memory_used -> Int8,
+        memory_total -> Int8,
+        swap_used -> Int8,
+        swap_total -> Int8,
+        disk_used -> Int8,
+        disk_total -> Int8,
+    }
+}
+
+diesel::table! {
+    node_net_metrics (id) {
+        id -> Int4,
+        node_id -> Int4,
+        resolution -> Int4,
+        sampled_at -> Timestamp,
+        interface -> Text,
+        rx -> Int8,
+        tx -> Int8,
+    }
+}
+
+diesel::table! {
+    renewals (id) {
+        id -> Int4,
+        sell_id -> Int4,
+        service_id -> Int4,
+        days -> Int4,
+        price -> Int4,
+        previous_invoice_date -> Nullable<Timestamp>,
+        invoice_date -> Timestamp,
+        created_at -> Timestamp,
     }
 }
 
@@ -29,9 +103,10 @@ diesel::table! {
         firstbuy_date -> Nullable<Timestamp>,
         invoice_date -> Nullable<Timestamp>,
         username -> Nullable<Text>,
-        password -> Nullable<Text>,
         password_hash -> Nullable<Text>,
         status -> Int4,
+        password_resets -> Int4,
+        password_reset_at -> Nullable<Timestamp>,
     }
 }
 
@@ -53,9 +128,20 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(enrollment_codes -> nodes (node_id));
+diesel::joinable!(expiry_log -> sells (sell_id));
+diesel::joinable!(node_metrics -> nodes (node_id));
+diesel::joinable!(node_net_metrics -> nodes (node_id));
+diesel::joinable!(renewals -> sells (sell_id));
+
 diesel::allow_tables_to_appear_in_same_query!(
+    enrollment_codes,
+    expiry_log,
     logins,
+    node_metrics,
+    node_net_metrics,
     nodes,
+    renewals,
     sells,
     services,
     users,
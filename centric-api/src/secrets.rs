@@ -0,0 +1,78 @@
+use crate::consts;
+use rocket::fairing::{AdHoc, Fairing};
+use rocket::figment::providers::Env;
+use rocket::serde::Deserialize;
+
+/// Structure representing the `secrets` section of Rocket.toml
+#[derive(Deserialize, Clone)]
+#[serde(crate = "rocket::serde")]
+pub struct Secrets {
+    /// Key required to register new logins
+    pub admin_key: String,
+    /// Key API tokens are signed and verified with
+    pub jwt_secret: String,
+    /// Key being rotated out, tokens signed with it are accepted until it's removed
+    pub jwt_previous_secret: Option<String>,
+}
+
+impl Secrets {
+    /// Returns the keys API tokens are verified with, the current one first
+    pub fn jwt_secrets(&self) -> Vec<String> {
+        std::iter::once(self.jwt_secret.clone())
+            .chain(self.jwt_previous_secret.clone())
+            .collect()
+    }
+
+    /// Checks that no secret is too weak, a sample placeholder or one of the previously
+    /// hard-coded ones
+    pub fn check(&self) -> Result<(), String> {
+        let keys = [
+            ("admin_key", Some(&self.admin_key)),
+            ("jwt_secret", Some(&self.jwt_secret)),
+            ("jwt_previous_secret", self.jwt_previous_secret.as_ref()),
+        ];
+
+        for (name, key) in keys {
+            let Some(key) = key else { continue };
+
+            if consts::LEGACY_SECRETS.contains(&key.as_str()) {
+                return Err(format!("{name} is a published default, generate a new one"));
+            }
+            if key.starts_with('<') {
+                return Err(format!(
+                    "{name} is a sample placeholder, generate a new one"
+                ));
+            }
+            if key.len() < consts::MIN_SECRET_LEN {
+                return Err(format!(
+                    "{name} should be at least {} characters long",
+                    consts::MIN_SECRET_LEN
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Fairing loading the secrets from Rocket.toml and `SSHMGMT_<KEY>` environment variables,
+/// aborting the launch if they're missing or insecure
+pub fn fairing() -> impl Fairing {
+    AdHoc::try_on_ignite("Secrets", |rocket| async {
+        let secrets = rocket
+            .figment()
+            .focus("secrets")
+            .merge(Env::prefixed(consts::ENV_PREFIX))
+            .extract::<Secrets>()
+            .map_err(|err| err.to_string())
+            .and_then(|secrets| secrets.check().map(|_| secrets));
+
+        match secrets {
+            Ok(secrets) => Ok(rocket.manage(secrets)),
+            Err(err) => {
+                eprintln!("Refusing to start with missing or insecure secrets: {err}");
+                Err(rocket)
+            }
+        }
+    })
+}
@@ -0,0 +1,179 @@
+use crate::models::{ApiError, ExpiryLog, NewExpiryLog, Node, Sell, UpdateSell};
+use crate::node_tls::NodeTls;
+use crate::{consts, Db};
+use rocket::fairing::{AdHoc, Fairing};
+use rocket::serde::{Deserialize, Serialize};
+use rocket::tokio::time::{interval, Duration};
+use std::time::SystemTime;
+
+/// Action taken on the node account of an overdue sell
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum SweepAction {
+    Lock,
+    Delete,
+}
+
+impl SweepAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SweepAction::Lock => "lock",
+            SweepAction::Delete => "delete",
+        }
+    }
+}
+
+/// Structure representing the `expiry_sweeper` section of Rocket.toml
+#[derive(Deserialize, Clone, Debug)]
+#[serde(crate = "rocket::serde", default)]
+pub struct SweeperConfig {
+    /// Whether the background sweeper runs at all
+    pub enabled: bool,
+    /// Only record what would be done, without touching nodes or sells
+    pub dry_run: bool,
+    /// Seconds between two sweeps
+    pub interval: u64,
+    pub action: SweepAction,
+}
+
+impl Default for SweeperConfig {
+    fn default() -> Self {
+        SweeperConfig {
+            enabled: false,
+            dry_run: true,
+            interval: 3600,
+            action: SweepAction::Lock,
+        }
+    }
+}
+
+/// Asynchronously locks or deletes the node accounts of every overdue active sell
+///
+/// Sells are only marked as expired once the node confirmed the action, failures are
+/// logged and retried on the next sweep. Every action taken is logged, planned ones only once.
+/// Node commands are audited as `subject`.
+pub async fn sweep(
+    db: &Db,
+    tls: &NodeTls,
+    subject: &str,
+    action: SweepAction,
+    dry_run: bool,
+) -> Result<Vec<ExpiryLog>, ApiError> {
+    let overdue_sells = Sell::list_overdue(db, SystemTime::now()).await?;
+    let mut logs = Vec::new();
+
+    for sell in overdue_sells {
+        let Some(username) = sell.username.clone() else {
+            continue;
+        };
+
+        let result = if dry_run {
+            // Sells stay overdue on a dry-run, only their first sweep is recorded
+            if ExpiryLog::is_planned(db, sell.id, action.as_str().to_string()).await? {
+                continue;
+            }
+            consts::SWEEP_PLANNED.to_string()
+        } else {
+            match expire_sell(db, tls, subject, &sell, &username, action).await {
+                Ok(_) => "ok".to_string(),
+                Err((_, err)) => err.to_string(),
+            }
+        };
+
+        let log_value = NewExpiryLog {
+            sell_id: sell.id,
+            node_id: sell.node_id,
+            username,
+            action: action.as_str().to_string(),
+            dry_run,
+            result,
+        };
+
+        logs.push(ExpiryLog::insert(db, log_value).await?);
+    }
+
+    Ok(logs)
+}
+
+/// Asynchronously applies the action on the node and marks the sell as expired
+async fn expire_sell(
+    db: &Db,
+    tls: &NodeTls,
+    subject: &str,
+    sell: &Sell,
+    username: &str,
+    action: SweepAction,
+) -> Result<(), ApiError> {
+    let node_info = Node::find_by_id(db, sell.node_id).await?;
+
+    match action {
+        SweepAction::Lock => node_info.userlock(tls, subject, username).await?,
+        SweepAction::Delete => node_info.userdel(tls, subject, username).await?,
+    };
+
+    Sell::update(db, sell.id, UpdateSell::expire()).await?;
+
+    Ok(())
+}
+
+/// Fairing managing the sweeper config and spawning the background sweeper once
+/// rocket has lifted off
+pub fn fairing() -> impl Fairing {
+    AdHoc::on_ignite("Expiry Sweeper Config", |rocket| async {
+        let mut config: SweeperConfig = rocket
+            .figment()
+            .extract_inner("expiry_sweeper")
+            .unwrap_or_default();
+        // A zero period would make the ticker panic
+        config.interval = config.interval.max(1);
+
+        rocket.manage(config).attach(liftoff())
+    })
+}
+
+fn liftoff() -> impl Fairing {
+    AdHoc::on_liftoff("Expiry Sweeper", |rocket| {
+        Box::pin(async move {
+            let (Some(config), Some(tls)) = (
+                rocket.state::<SweeperConfig>().cloned(),
+                rocket.state::<NodeTls>().cloned(),
+            ) else {
+                return;
+            };
+
+            if !config.enabled {
+                return;
+            }
+
+            let Some(pool) = Db::pool(rocket).cloned() else {
+                eprintln!("expiry sweeper: database pool is not available");
+                return;
+            };
+
+            rocket::tokio::spawn(async move {
+                let mut ticker = interval(Duration::from_secs(config.interval));
+
+                loop {
+                    ticker.tick().await;
+
+                    let Some(conn) = pool.get().await else {
+                        eprintln!("expiry sweeper: couldn't get a database connection");
+                        continue;
+                    };
+
+                    if let Err((_, err)) = sweep(
+                        &Db(conn),
+                        &tls,
+                        consts::NODE_TOKEN_SUBJECT,
+                        config.action,
+                        config.dry_run,
+                    )
+                    .await
+                    {
+                        eprintln!("expiry sweeper: sweep failed: {err}");
+                    }
+                }
+            });
+        })
+    })
+}
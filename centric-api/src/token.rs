@@ -1,29 +1,86 @@
 use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rocket::catch;
 use rocket::http::Status;
 use rocket::request::Outcome;
 use rocket::request::{self, FromRequest, Request};
-use rocket::serde::json::{json, Json, Value as JsonValue};
+use rocket::serde::json::{json, Value as JsonValue};
 use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::consts::JWT_SECRET;
+use crate::models::{ApiError, JsonResponseError, Login};
+use crate::secrets::Secrets;
+use crate::Db;
 
 /// Represents the claims included in the JWT token
 #[derive(Deserialize, Serialize)]
 pub struct Claims {
-    pub role: String,
+    /// ID of the login (`logins.id`) the token was issued to
+    pub sub: i32,
+    pub admin: bool,
     pub exp: u64,
 }
 
-/// Represents a JWT token
+impl Claims {
+    /// Returns the role granted by the claims
+    pub fn role(&self) -> Role {
+        if self.admin {
+            Role::Privileged
+        } else {
+            Role::Normal
+        }
+    }
+}
+
+/// Represents a JWT token along with the keys it can be verified with
 #[derive(Debug)]
-pub struct Token(String);
+pub struct Token {
+    token: String,
+    jwt_secrets: Vec<String>,
+}
 
 /// Represents possible errors when dealing with API tokens
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ApiTokenError {
     Missing,
-    Invalid,
+    Invalid(String),
+    Forbidden,
+}
+
+impl JsonResponseError for ApiTokenError {
+    fn jsonify(&self) -> ApiError {
+        match self {
+            ApiTokenError::Missing => (
+                Status::Unauthorized,
+                json!({
+                "Err": {
+                    "type": "auth",
+                    "code": 401,
+                    "msg": "Missing token",
+                }}),
+            ),
+
+            ApiTokenError::Invalid(raw_msg) => (
+                Status::Unauthorized,
+                json!({
+                "Err": {
+                    "type": "auth",
+                    "code": 401,
+                    "msg": "Invalid token",
+                    "raw_msg": raw_msg
+                }}),
+            ),
+
+            ApiTokenError::Forbidden => (
+                Status::Forbidden,
+                json!({
+                "Err": {
+                    "type": "auth",
+                    "code": 403,
+                    "msg": "Admin permission required",
+                }}),
+            ),
+        }
+    }
 }
 
 #[rocket::async_trait]
@@ -31,52 +88,174 @@ impl<'r> FromRequest<'r> for Token {
     type Error = ApiTokenError;
 
     async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let jwt_secrets = match request.rocket().state::<Secrets>() {
+            Some(secrets) => secrets.jwt_secrets(),
+            None => {
+                return Outcome::Failure((
+                    Status::InternalServerError,
+                    ApiTokenError::Invalid("secrets aren't loaded".to_string()),
+                ))
+            }
+        };
+
         let token = request.headers().get_one("Authorization");
         match token {
-            Some(token) => Outcome::Success(Token(token.replace("Bearer ", "").to_string())),
+            Some(token) => Outcome::Success(Token {
+                token: token.replace("Bearer ", "").to_string(),
+                jwt_secrets,
+            }),
             None => Outcome::Failure((Status::Unauthorized, ApiTokenError::Missing)),
         }
     }
 }
 
 impl Token {
-    /// Generates a new JWT token
-    pub fn generate() -> String {
+    /// Generates a new JWT token for the given login, signed with the current key
+    pub fn generate(login: &Login, secrets: &Secrets) -> String {
         let now = SystemTime::now();
         let unix_time = now.duration_since(UNIX_EPOCH).unwrap().as_secs();
 
         let claims = Claims {
-            role: "normal".to_string(),
+            sub: login.id,
+            admin: login.admin,
             exp: unix_time + (((60 * 60) * 24) * 14/*2 week*/),
         };
         encode(
             &Header::default(),
             &claims,
-            &EncodingKey::from_secret(JWT_SECRET),
+            &EncodingKey::from_secret(secrets.jwt_secret.as_bytes()),
         )
         .unwrap()
     }
 
     /// Converts the token to a string
     pub fn to_string(&self) -> String {
-        self.0.to_string()
+        self.token.to_string()
+    }
+
+    /// Decodes the token with the current key, then the previous one if any, and returns
+    /// its claims
+    pub fn claims(&self) -> Result<Claims, ApiTokenError> {
+        let mut last_err = ApiTokenError::Missing;
+
+        for secret in &self.jwt_secrets {
+            match decode::<Claims>(
+                &self.token,
+                &DecodingKey::from_secret(secret.as_bytes()),
+                &Validation::new(Algorithm::HS256),
+            ) {
+                Ok(token) => return Ok(token.claims),
+                Err(e) => last_err = ApiTokenError::Invalid(e.to_string()),
+            }
+        }
+
+        Err(last_err)
     }
 
     /// Validates the token and returns the user role
     pub fn validate(&self) -> Result<Role, (Status, JsonValue)> {
-        let decoded_token = match decode::<Claims>(
-            &self.0,
-            &DecodingKey::from_secret(JWT_SECRET),
-            &Validation::new(Algorithm::HS256),
-        ) {
-            Ok(token) => token,
-            Err(e) => return Err((Status::Unauthorized, json!({"Err": e.to_string()}))),
+        self.claims()
+            .map(|claims| claims.role())
+            .map_err(|err| err.jsonify())
+    }
+}
+
+/// Request guard for routes any logged in user can access
+pub struct Auth {
+    pub login_id: i32,
+    pub role: Role,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Auth {
+    type Error = ApiTokenError;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let claims = match request.guard::<Token>().await {
+            Outcome::Success(token) => token.claims(),
+            _ => Err(ApiTokenError::Missing),
+        };
+
+        match claims {
+            Ok(claims) => Outcome::Success(Auth {
+                login_id: claims.sub,
+                role: claims.role(),
+            }),
+            Err(err) => fail(request, err),
+        }
+    }
+}
+
+/// Request guard for routes only admins can access
+///
+/// The admin flag of the token is checked against the database again, so demoted or deleted
+/// logins lose access before their token expires.
+pub struct AdminAuth {
+    pub auth: Auth,
+    /// Username of the login, recorded as the subject of the commands sent to nodes
+    pub username: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminAuth {
+    type Error = ApiTokenError;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let auth = match request.guard::<Auth>().await {
+            Outcome::Success(auth) => auth,
+            Outcome::Failure(failure) => return Outcome::Failure(failure),
+            Outcome::Forward(forward) => return Outcome::Forward(forward),
         };
 
-        Ok(Role::from_str(&decoded_token.claims.role))
+        if !matches!(auth.role, Role::Privileged) {
+            return fail(request, ApiTokenError::Forbidden);
+        }
+
+        let db = match request.guard::<Db>().await {
+            Outcome::Success(db) => db,
+            _ => {
+                return Outcome::Failure((
+                    Status::ServiceUnavailable,
+                    ApiTokenError::Invalid("database is not available".to_string()),
+                ))
+            }
+        };
+
+        match Login::find_by_id(&db, auth.login_id).await {
+            Ok(login) if login.admin => Outcome::Success(AdminAuth {
+                auth,
+                username: login.username,
+            }),
+            _ => fail(request, ApiTokenError::Forbidden),
+        }
     }
 }
 
+/// Fails the request, keeping the error around for the catchers
+fn fail<T>(request: &Request<'_>, err: ApiTokenError) -> request::Outcome<T, ApiTokenError> {
+    let (status, _) = err.jsonify();
+    request.local_cache(|| Some(err.clone()));
+
+    Outcome::Failure((status, err))
+}
+
+/// Catches requests rejected with 401 by the auth guards
+#[catch(401)]
+pub fn unauthorized(request: &Request<'_>) -> JsonValue {
+    let err = request
+        .local_cache(|| None::<ApiTokenError>)
+        .clone()
+        .unwrap_or(ApiTokenError::Missing);
+
+    err.jsonify().1
+}
+
+/// Catches requests rejected with 403 by the auth guards
+#[catch(403)]
+pub fn forbidden(_request: &Request<'_>) -> JsonValue {
+    ApiTokenError::Forbidden.jsonify().1
+}
+
 /// Represents user roles
 pub enum Role {
     Privileged,
@@ -0,0 +1,78 @@
+use lib::models::Login;
+use lib::secrets::Secrets;
+use lib::token::{self, AdminAuth, Auth, Token};
+use rocket::http::{Header, Status};
+use rocket::local::blocking::Client;
+use rocket::{catchers, get, routes};
+use std::time::SystemTime;
+
+#[get("/read")]
+fn read(_auth: Auth) -> &'static str {
+    "ok"
+}
+
+#[get("/write")]
+fn write(_auth: AdminAuth) -> &'static str {
+    "ok"
+}
+
+fn secrets() -> Secrets {
+    Secrets {
+        admin_key: "a".repeat(32),
+        jwt_secret: "s".repeat(32),
+        jwt_previous_secret: None,
+    }
+}
+
+fn client() -> Client {
+    let rocket = rocket::build()
+        .manage(secrets())
+        .mount("/", routes![read, write])
+        .register("/", catchers![token::unauthorized, token::forbidden]);
+
+    Client::tracked(rocket).unwrap()
+}
+
+fn bearer(admin: bool) -> Header<'static> {
+    let login = Login {
+        id: 1,
+        username: "viewer".to_string(),
+        password_hash: String::new(),
+        admin,
+        register_date: SystemTime::now(),
+    };
+
+    Header::new(
+        "Authorization",
+        format!("Bearer {}", Token::generate(&login, &secrets())),
+    )
+}
+
+#[test]
+fn read_only_logins_are_refused_on_admin_routes() {
+    let client = client();
+
+    let response = client.get("/read").header(bearer(false)).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client.get("/write").header(bearer(false)).dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    assert!(response
+        .into_string()
+        .unwrap()
+        .contains("Admin permission required"));
+}
+
+#[test]
+fn missing_tokens_are_refused() {
+    let client = client();
+
+    assert_eq!(
+        client.get("/read").dispatch().status(),
+        Status::Unauthorized
+    );
+    assert_eq!(
+        client.get("/write").dispatch().status(),
+        Status::Unauthorized
+    );
+}
@@ -0,0 +1,76 @@
+use lib::models::Service;
+use lib::node_models::{self, NodeRenewal, RenewalStep};
+
+fn service(max_logins: i32, max_traffic: Option<i32>) -> Service {
+    Service {
+        id: max_logins,
+        max_logins,
+        max_traffic,
+        price: 10,
+        available: true,
+    }
+}
+
+#[test]
+fn undoes_the_expiry_when_a_later_step_fails() {
+    let previous = service(1, Some(50));
+    let renewal = NodeRenewal::new(
+        "user",
+        &previous,
+        &previous,
+        Some("2026-10-01".to_string()),
+        "2026-11-01".to_string(),
+        false,
+    )
+    .unwrap();
+
+    assert_eq!(
+        renewal.steps[0],
+        RenewalStep::ChExp("2026-11-01".to_string())
+    );
+    assert!(matches!(renewal.steps[1], RenewalStep::ChLimits(_)));
+    assert_eq!(renewal.steps.len(), 2);
+
+    // chlimits failed after chexp succeeded
+    assert_eq!(
+        renewal.undo(2),
+        [
+            RenewalStep::ChLimits(node_models::service_limits("user", &previous).unwrap()),
+            RenewalStep::ChExp("2026-10-01".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn undoes_a_service_change_and_unlock() {
+    let previous = service(1, Some(50));
+    let next = service(2, None);
+    let renewal = NodeRenewal::new(
+        "user",
+        &previous,
+        &next,
+        None,
+        "2026-11-01".to_string(),
+        true,
+    )
+    .unwrap();
+
+    assert_eq!(renewal.steps.len(), 4);
+    assert_eq!(
+        renewal.steps[1],
+        RenewalStep::ChGrp(node_models::service_group(2))
+    );
+    assert_eq!(renewal.steps[3], RenewalStep::UserUnlock);
+
+    assert_eq!(
+        renewal.undo(4),
+        [
+            RenewalStep::UserLock,
+            RenewalStep::ChLimits(node_models::service_limits("user", &previous).unwrap()),
+            RenewalStep::ChGrp(node_models::service_group(1)),
+            RenewalStep::ChExp("-1".to_string()),
+        ]
+    );
+    assert_eq!(renewal.undo(1), [RenewalStep::ChExp("-1".to_string())]);
+    assert!(renewal.undo(0).is_empty());
+}
@@ -0,0 +1,346 @@
+use crate::backend::AccountBackend;
+use crate::consts;
+use crate::users::models::UserErrors;
+use crate::users::SSHUser;
+pub use api_types::v1::accounting::{TrafficTotals, UserQuota, UserTraffic};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// iptables chain counting bytes sent by managed users (owner match on `OUTPUT`).
+pub const CHAIN_OUT: &str = "SSHMGMT_OUT";
+/// iptables chain counting bytes received by managed users (connmark match on `INPUT`).
+pub const CHAIN_IN: &str = "SSHMGMT_IN";
+/// Comment prefix used to tag per-uid rules so their counters can be attributed.
+const RULE_TAG: &str = "sshmgmt:";
+
+/// Struct representing a traffic quota as kept in the store.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+struct TrafficQuota {
+    limit: u64,
+    exhausted: bool,
+}
+
+/// On-disk layout of the accounting store.
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+struct TrafficStore {
+    #[serde(flatten)]
+    totals: TrafficTotals,
+    #[serde(default)]
+    quotas: HashMap<String, TrafficQuota>,
+}
+
+/// Per-user traffic accounting based on iptables owner/connmark counters.
+///
+/// Every poll re-adds missing per-uid rules, reads and zeroes their counters and adds them to
+/// the totals, which are persisted to a small JSON store so they survive restarts of both
+/// node-api and the firewall. Users with a quota are locked once their rx + tx reaches it.
+pub struct TrafficAccounting {
+    store_path: PathBuf,
+    backend: Box<dyn AccountBackend>,
+    store: Mutex<TrafficStore>,
+}
+
+impl TrafficAccounting {
+    /// Creates a new `TrafficAccounting`, loading previous totals from `store_path` if present.
+    pub fn new<P: Into<PathBuf>>(
+        store_path: P,
+        backend: Box<dyn AccountBackend>,
+    ) -> TrafficAccounting {
+        let store_path = store_path.into();
+        let store = fs::read_to_string(&store_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        TrafficAccounting {
+            store_path,
+            backend,
+            store: Mutex::new(store),
+        }
+    }
+
+    /// Spawns the background thread polling the counters every `interval`.
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> thread::JoinHandle<()> {
+        thread::spawn(move || loop {
+            if let Err(e) = self.poll() {
+                eprintln!("traffic accounting poll failed: {e:?}");
+            }
+            thread::sleep(interval);
+        })
+    }
+
+    /// Makes sure every managed user has counting rules, then folds the counters into the totals
+    /// and removes the rules of deleted users.
+    ///
+    /// Rules are checked on every poll rather than remembered, so they come back after the
+    /// firewall was flushed or restarted.
+    pub fn poll(&self) -> Result<(), UserErrors> {
+        Self::setup_chains()?;
+
+        let accounts: HashMap<u32, String> = self
+            .backend
+            .all_accounts()
+            .into_iter()
+            .filter(|account| account.uid >= consts::UID_MIN)
+            .map(|account| (account.uid, account.username))
+            .collect();
+
+        for uid in accounts.keys() {
+            Self::track_uid(*uid)?;
+        }
+
+        let tx_counters = Self::read_counters(CHAIN_OUT)?;
+        let rx_counters = Self::read_counters(CHAIN_IN)?;
+
+        let deleted: HashSet<u32> = tx_counters
+            .keys()
+            .chain(rx_counters.keys())
+            .filter(|uid| !accounts.contains_key(uid))
+            .copied()
+            .collect();
+        for uid in deleted {
+            Self::untrack_uid(uid)?;
+        }
+
+        let mut store = self.store.lock().map_err(|_| UserErrors::UnexpectedError)?;
+        let TrafficStore { totals, quotas } = &mut *store;
+
+        for (uid, username) in accounts.iter() {
+            let traffic = totals.users.entry(username.to_string()).or_default();
+            traffic.tx += tx_counters.get(uid).copied().unwrap_or(0);
+            traffic.rx += rx_counters.get(uid).copied().unwrap_or(0);
+
+            if let Some(quota) = quotas.get_mut(username) {
+                if !quota.exhausted && traffic.rx + traffic.tx >= quota.limit {
+                    match SSHUser::usermod_lock(&*self.backend, username) {
+                        Ok(_) => quota.exhausted = true,
+                        Err(e) => eprintln!("couldn't lock {username} over quota: {e:?}"),
+                    }
+                }
+            }
+        }
+        totals.timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.as_secs());
+
+        self.save(&store)
+    }
+
+    /// Sets (or removes) the traffic quota of a user and starts counting from zero,
+    /// as done for freshly created accounts.
+    pub fn set_quota(&self, username: &str, quota: Option<u64>) -> Result<(), UserErrors> {
+        let mut store = self.store.lock().map_err(|_| UserErrors::UnexpectedError)?;
+
+        store.totals.users.remove(username);
+        match quota {
+            Some(limit) => store.quotas.insert(
+                username.to_string(),
+                TrafficQuota {
+                    limit,
+                    exhausted: false,
+                },
+            ),
+            None => store.quotas.remove(username),
+        };
+
+        self.save(&store)
+    }
+
+    /// Clears the exhausted flag of a user's quota once their account was unlocked, so it's
+    /// locked again by the next poll if the usage is still over the quota.
+    pub fn rearm_quota(&self, username: &str) -> Result<(), UserErrors> {
+        let mut store = self.store.lock().map_err(|_| UserErrors::UnexpectedError)?;
+
+        match store.quotas.get_mut(username) {
+            Some(quota) if quota.exhausted => quota.exhausted = false,
+            _ => return Ok(()),
+        }
+
+        self.save(&store)
+    }
+
+    /// Returns the quota of a user along with the used and remaining bytes.
+    pub fn quota(&self, username: &str) -> Result<UserQuota, UserErrors> {
+        let store = self.store.lock().map_err(|_| UserErrors::UnexpectedError)?;
+
+        let used = store
+            .totals
+            .users
+            .get(username)
+            .map_or(0, |traffic| traffic.rx + traffic.tx);
+        let quota = store.quotas.get(username);
+
+        Ok(UserQuota {
+            username: username.to_string(),
+            quota: quota.map(|quota| quota.limit),
+            used,
+            remaining: quota.map(|quota| quota.limit.saturating_sub(used)),
+            exhausted: quota.map_or(false, |quota| quota.exhausted),
+        })
+    }
+
+    /// Returns the quota a user would be left with once `quota` is set, their usage starting
+    /// over.
+    pub fn fresh_quota(username: &str, quota: Option<u64>) -> UserQuota {
+        UserQuota {
+            username: username.to_string(),
+            quota,
+            used: 0,
+            remaining: quota,
+            exhausted: false,
+        }
+    }
+
+    /// Returns the cumulative traffic of the given users, users never seen are reported as zero.
+    pub fn totals(&self, users: &[String]) -> Result<TrafficTotals, UserErrors> {
+        let store = self.store.lock().map_err(|_| UserErrors::UnexpectedError)?;
+        let totals = &store.totals;
+
+        Ok(TrafficTotals {
+            timestamp: totals.timestamp,
+            users: users
+                .iter()
+                .map(|user| {
+                    (
+                        user.to_string(),
+                        totals.users.get(user).copied().unwrap_or_default(),
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    /// Writes the store to disk, going through a temporary file so a crash can't truncate it.
+    fn save(&self, store: &TrafficStore) -> Result<(), UserErrors> {
+        if let Some(parent) = self.store_path.parent() {
+            fs::create_dir_all(parent).map_err(|_| UserErrors::PermissionDenied)?;
+        }
+
+        let content = serde_json::to_string(store).map_err(|_| UserErrors::UnexpectedError)?;
+        let tmp_path = self.store_path.with_extension("tmp");
+
+        fs::write(&tmp_path, content).map_err(|_| UserErrors::PermissionDenied)?;
+        fs::rename(&tmp_path, &self.store_path).map_err(|_| UserErrors::PermissionDenied)
+    }
+
+    /// Creates the accounting chains and hooks them into `OUTPUT` and `INPUT` if needed.
+    fn setup_chains() -> Result<(), UserErrors> {
+        for (chain, parent) in [(CHAIN_OUT, "OUTPUT"), (CHAIN_IN, "INPUT")] {
+            if !Self::iptables(&["-n", "-L", chain])? {
+                Self::iptables(&["-N", chain])?;
+            }
+            if !Self::iptables(&["-C", parent, "-j", chain])? {
+                Self::iptables(&["-I", parent, "-j", chain])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the tx (owner match, marks the connection) and rx (connmark match) rules of a uid.
+    fn uid_rules(uid: u32) -> [Vec<String>; 2] {
+        let uid = uid.to_string();
+        let tag = format!("{RULE_TAG}{uid}");
+
+        let tx_rule = [
+            CHAIN_OUT,
+            "-m",
+            "owner",
+            "--uid-owner",
+            &uid,
+            "-m",
+            "comment",
+            "--comment",
+            &tag,
+            "-j",
+            "CONNMARK",
+            "--set-mark",
+            &uid,
+        ];
+        let rx_rule = [
+            CHAIN_IN,
+            "-m",
+            "connmark",
+            "--mark",
+            &uid,
+            "-m",
+            "comment",
+            "--comment",
+            &tag,
+        ];
+
+        [&tx_rule[..], &rx_rule[..]].map(|rule| rule.iter().map(|arg| arg.to_string()).collect())
+    }
+
+    /// Adds the rules of a uid that are missing.
+    fn track_uid(uid: u32) -> Result<(), UserErrors> {
+        for rule in Self::uid_rules(uid) {
+            let rule: Vec<&str> = rule.iter().map(String::as_str).collect();
+            if !Self::iptables(&[&["-C"], &rule[..]].concat())? {
+                Self::iptables(&[&["-A"], &rule[..]].concat())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes every copy of the rules of a uid.
+    fn untrack_uid(uid: u32) -> Result<(), UserErrors> {
+        for rule in Self::uid_rules(uid) {
+            let rule: Vec<&str> = rule.iter().map(String::as_str).collect();
+            while Self::iptables(&[&["-D"], &rule[..]].concat())? {}
+        }
+
+        Ok(())
+    }
+
+    /// Lists and zeroes a chain's counters, returning the bytes counted per uid.
+    fn read_counters(chain: &str) -> Result<HashMap<u32, u64>, UserErrors> {
+        let output = Command::new("iptables")
+            .args(["-w", "-L", chain, "-v", "-n", "-x", "-Z"])
+            .output()
+            .map_err(|_| UserErrors::CommandNotFound)?;
+
+        if !output.status.success() {
+            return Err(UserErrors::PermissionDenied);
+        }
+
+        let mut counters: HashMap<u32, u64> = HashMap::new();
+
+        for line in String::from_utf8_lossy(&output.stdout).lines().skip(2) {
+            let bytes = line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|bytes| bytes.parse::<u64>().ok());
+            let uid = line
+                .split(RULE_TAG)
+                .nth(1)
+                .and_then(|rest| rest.split_whitespace().next())
+                .and_then(|uid| uid.parse::<u32>().ok());
+
+            if let (Some(bytes), Some(uid)) = (bytes, uid) {
+                *counters.entry(uid).or_insert(0) += bytes;
+            }
+        }
+
+        Ok(counters)
+    }
+
+    /// Runs an iptables command, returns whether it succeeded.
+    fn iptables(args: &[&str]) -> Result<bool, UserErrors> {
+        let status = Command::new("iptables")
+            .arg("-w")
+            .args(args)
+            .output()
+            .map_err(|_| UserErrors::CommandNotFound)?
+            .status;
+
+        Ok(status.success())
+    }
+}
@@ -0,0 +1,298 @@
+use crate::models::{Scope, Token};
+use crate::users::models::UserErrors;
+pub use api_types::v1::audit::AuditEntry;
+use api_types::v1::auth::Claims;
+use api_types::v1::bulk::BulkResult;
+use api_types::v1::requests::{Command, DRY_RUN_HEADER};
+use api_types::v1::{Refusal, UserResult};
+use rocket::fairing::{AdHoc, Fairing};
+use rocket::http::{Header, Status};
+use rocket::outcome::Outcome;
+use rocket::request::{self, FromRequest, Request};
+use rocket::serde::json::Json;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Keys whose values are replaced before arguments are recorded.
+const REDACTED_KEYS: &[&str] = &["password"];
+
+/// Append-only log of the commands handled by the node, one JSON entry per line.
+pub struct AuditLog {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    /// Creates a new `AuditLog` appending to `path`, created on the first entry.
+    pub fn new<P: Into<PathBuf>>(path: P) -> AuditLog {
+        AuditLog {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Appends an entry to the log.
+    pub fn append(&self, entry: &AuditEntry) -> Result<(), UserErrors> {
+        let _guard = self.lock.lock().map_err(|_| UserErrors::UnexpectedError)?;
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|_| UserErrors::PermissionDenied)?;
+        }
+
+        let mut line = serde_json::to_string(entry).map_err(|_| UserErrors::UnexpectedError)?;
+        line.push('\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|_| UserErrors::PermissionDenied)
+    }
+
+    /// Returns the entries recorded between `from` and `to` (unix timestamps, inclusive),
+    /// oldest first, optionally only those targeting one user.
+    pub fn entries(
+        &self,
+        from: Option<u64>,
+        to: Option<u64>,
+        username: Option<&str>,
+    ) -> Result<Vec<AuditEntry>, UserErrors> {
+        let _guard = self.lock.lock().map_err(|_| UserErrors::UnexpectedError)?;
+
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(_) => return Err(UserErrors::PermissionDenied),
+        };
+
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<AuditEntry>(line).ok())
+            .filter(|entry| from.map_or(true, |from| entry.timestamp >= from))
+            .filter(|entry| to.map_or(true, |to| entry.timestamp <= to))
+            .filter(|entry| {
+                username.map_or(true, |username| entry.username.as_deref() == Some(username))
+            })
+            .collect())
+    }
+}
+
+/// Request guard recording the command handled by the current route in the audit log, along
+/// with refusals of the command.
+///
+/// The `dry_run` flag of the recorded command is echoed in the `DRY_RUN_HEADER` of the
+/// response, so clients can tell the node honoured it.
+pub struct Audit<'r> {
+    log: &'r AuditLog,
+    route: String,
+    dry_run: &'r DryRunEcho,
+}
+
+/// `dry_run` flag of the command recorded for the current request.
+#[derive(Default)]
+struct DryRunEcho(OnceLock<bool>);
+
+/// Fairing setting the `DRY_RUN_HEADER` of the responses to recorded commands.
+pub fn fairing() -> impl Fairing {
+    AdHoc::on_response("Dry Run Echo", |request, response| {
+        Box::pin(async move {
+            if let Some(dry_run) = request.local_cache(DryRunEcho::default).0.get() {
+                response.set_header(Header::new(DRY_RUN_HEADER, dry_run.to_string()));
+            }
+        })
+    })
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Audit<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match request.rocket().state::<AuditLog>() {
+            Some(log) => Outcome::Success(Audit {
+                log,
+                route: request.uri().path().to_string(),
+                dry_run: request.local_cache(DryRunEcho::default),
+            }),
+            None => Outcome::Failure((Status::InternalServerError, ())),
+        }
+    }
+}
+
+impl Audit<'_> {
+    /// Records a command targeting `username` (or no single account) and its result.
+    pub fn record<P: Serialize, T>(
+        &self,
+        claims: &Claims,
+        username: Option<&str>,
+        command: &Command<P>,
+        result: &UserResult<T>,
+    ) {
+        let _ = self.dry_run.0.set(command.dry_run);
+        self.write(claims, username, command, result, false);
+    }
+
+    /// Records a bulk command, one entry per account it was applied to or a single one if it
+    /// selected none.
+    pub fn record_bulk<P: Serialize, T>(
+        &self,
+        claims: &Claims,
+        command: &Command<P>,
+        result: &BulkResult<T>,
+    ) {
+        let _ = self.dry_run.0.set(command.dry_run);
+        if result.results.is_empty() {
+            self.write(claims, None, command, &Ok(()), false);
+        }
+
+        for item in &result.results {
+            let rolled_back = result.rolled_back
+                && item.result.is_ok()
+                && !result.rollback_failed.contains(&item.username);
+
+            self.write(
+                claims,
+                Some(&item.username),
+                command,
+                &item.result,
+                rolled_back,
+            );
+        }
+    }
+
+    /// Validates the token of a command, recording the refusal if it's invalid or misses
+    /// `scope`.
+    pub fn validate<P: Serialize>(
+        &self,
+        token: &Token,
+        scope: Scope,
+        command: &Command<P>,
+    ) -> Result<Claims, Json<Refusal>> {
+        token.validate(scope).map_err(|refusal| {
+            self.log
+                .refused(None, &self.route, Some(command), refusal.msg());
+            refusal
+        })
+    }
+
+    /// Records the refusal of a command and returns it.
+    pub fn refuse<P: Serialize>(
+        &self,
+        claims: &Claims,
+        command: &Command<P>,
+        msg: impl Into<String>,
+    ) -> Json<Refusal> {
+        let refusal = Refusal::new(msg);
+        self.log.refused(
+            claims.sub.clone(),
+            &self.route,
+            Some(command),
+            refusal.msg(),
+        );
+
+        Json(refusal)
+    }
+
+    fn write<P: Serialize, T>(
+        &self,
+        claims: &Claims,
+        username: Option<&str>,
+        command: &Command<P>,
+        result: &UserResult<T>,
+        rolled_back: bool,
+    ) {
+        self.log.record(AuditEntry {
+            timestamp: now(),
+            subject: claims.sub.clone(),
+            route: self.route.clone(),
+            username: username.map(str::to_string),
+            args: args(command),
+            dry_run: command.dry_run,
+            result: Some(result.as_ref().map(|_| ()).map_err(|err| *err)),
+            refused: None,
+            rolled_back,
+        });
+    }
+}
+
+impl AuditLog {
+    /// Records a refused command, `command` being missing when its body couldn't be read.
+    fn refused<P: Serialize>(
+        &self,
+        subject: Option<String>,
+        route: &str,
+        command: Option<&Command<P>>,
+        msg: &str,
+    ) {
+        self.record(AuditEntry {
+            timestamp: now(),
+            subject,
+            route: route.to_string(),
+            username: None,
+            args: command.map_or(Value::Null, args),
+            dry_run: command.map_or(false, |command| command.dry_run),
+            result: None,
+            refused: Some(msg.to_string()),
+            rolled_back: false,
+        });
+    }
+
+    /// Appends an entry, only reporting failures since the command was already handled.
+    fn record(&self, entry: AuditEntry) {
+        if let Err(e) = self.append(&entry) {
+            eprintln!("Couldn't write the audit log: {e:?}");
+        }
+    }
+}
+
+/// Catches the requests to command routes refused before reaching them (missing token,
+/// malformed body, unknown route) and records them.
+#[rocket::catch(default)]
+pub fn refused(status: Status, request: &Request<'_>) -> Json<Refusal> {
+    let refusal = Refusal::new(status.to_string());
+
+    if let Some(log) = request.rocket().state::<AuditLog>() {
+        log.refused::<()>(None, request.uri().path().as_str(), None, refusal.msg());
+    }
+
+    Json(refusal)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// Returns the parameters of a command as recorded, passwords redacted.
+fn args<P: Serialize>(command: &Command<P>) -> Value {
+    redact(serde_json::to_value(&command.params).unwrap_or(Value::Null))
+}
+
+/// Replaces the values of `REDACTED_KEYS` anywhere in `value`.
+fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| {
+                    if REDACTED_KEYS.contains(&key.as_str()) {
+                        (key, Value::String("<redacted>".to_string()))
+                    } else {
+                        (key, redact(value))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.into_iter().map(redact).collect()),
+        value => value,
+    }
+}
@@ -0,0 +1,648 @@
+use crate::consts;
+use crate::shadow::{self, ShadowEntry};
+use crate::users::models::UserErrors;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+use users::os::unix::UserExt;
+
+/// Struct representing a unix account as reported by an account backend.
+pub struct Account {
+    pub username: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub groups: Vec<String>,
+    pub shell: String,
+}
+
+/// Operations `SSHUser` needs from the system's account database.
+pub trait AccountBackend: Send + Sync {
+    /// Creates an account with an already hashed password, expiring on `exp_date`
+    /// (`YYYY-MM-DD` or days since the unix epoch, empty for no expiry).
+    fn useradd(
+        &self,
+        username: &str,
+        password_hash: &str,
+        shell: &str,
+        group: &str,
+        exp_date: &str,
+    ) -> Result<(), UserErrors>;
+
+    /// Deletes an account.
+    fn userdel(&self, username: &str) -> Result<(), UserErrors>;
+
+    /// Replaces the password hash of an account.
+    fn set_password_hash(&self, username: &str, password_hash: &str) -> Result<(), UserErrors>;
+
+    /// Changes the primary group of an account.
+    fn set_group(&self, username: &str, group: &str) -> Result<(), UserErrors>;
+
+    /// Changes the expiry date (`YYYY-MM-DD` or days since the unix epoch, `-1` for no expiry)
+    /// of an account.
+    fn set_exp_date(&self, username: &str, exp_date: &str) -> Result<(), UserErrors>;
+
+    /// Locks the password of an account.
+    fn lock(&self, username: &str) -> Result<(), UserErrors>;
+
+    /// Unlocks the password of an account.
+    fn unlock(&self, username: &str) -> Result<(), UserErrors>;
+
+    /// Returns the `shadow` record (expiry, password aging and lock state) of an account.
+    fn get_shadow(&self, username: &str) -> Result<ShadowEntry, UserErrors>;
+
+    /// Looks up an account by username.
+    fn get_account(&self, username: &str) -> Option<Account>;
+
+    /// Looks up a group name by its id.
+    fn get_group_name(&self, gid: u32) -> Option<String>;
+
+    /// Checks whether a group exists.
+    fn group_exists(&self, group: &str) -> bool;
+
+    /// Lists every account known to the backend.
+    fn all_accounts(&self) -> Vec<Account>;
+
+    /// Whether commands are only validated instead of applied.
+    fn dry_run(&self) -> bool {
+        false
+    }
+
+    /// Whether accounts live under a fake root instead of the live system.
+    fn fake_root(&self) -> bool {
+        false
+    }
+}
+
+/// Account backend that manages the live system through shadow-utils (`useradd`, `usermod`, `chage`).
+pub struct ShadowUtilsBackend {
+    shadow_path: PathBuf,
+}
+
+impl ShadowUtilsBackend {
+    /// Creates a new `ShadowUtilsBackend` reading account records from the given shadow file.
+    pub fn new<P: Into<PathBuf>>(shadow_path: P) -> ShadowUtilsBackend {
+        ShadowUtilsBackend {
+            shadow_path: shadow_path.into(),
+        }
+    }
+
+    /// Runs a shadow-utils command and maps its exit code to an error.
+    fn run(command: &mut Command) -> Result<(), UserErrors> {
+        match command.status() {
+            Ok(status) => match Self::unixuser_code_to_err(status.code()) {
+                Some(error) => Err(error),
+                None => Ok(()),
+            },
+            Err(_) => Err(UserErrors::CommandNotFound),
+        }
+    }
+
+    /// Converts Unix user command exit codes to corresponding errors.
+    fn unixuser_code_to_err(code: Option<i32>) -> Option<UserErrors> {
+        if let Some(code) = code {
+            match code {
+                0 => None,
+                1 => Some(UserErrors::PermissionDenied),
+                3 => Some(UserErrors::InvalidShell),
+                6 => Some(UserErrors::InvalidUserOrGroup),
+                9 => Some(UserErrors::UserAlreadyExists),
+                _ => Some(UserErrors::UnexpectedError),
+            }
+        } else {
+            Some(UserErrors::ProcessTerminated)
+        }
+    }
+}
+
+impl AccountBackend for ShadowUtilsBackend {
+    fn useradd(
+        &self,
+        username: &str,
+        password_hash: &str,
+        shell: &str,
+        group: &str,
+        exp_date: &str,
+    ) -> Result<(), UserErrors> {
+        Self::run(
+            Command::new("useradd")
+                .arg("-p")
+                .arg(password_hash)
+                .arg("-s")
+                .arg(shell)
+                .arg("-g")
+                .arg(group)
+                .arg("-e")
+                .arg(exp_date)
+                .arg(username),
+        )
+    }
+
+    fn userdel(&self, username: &str) -> Result<(), UserErrors> {
+        Self::run(Command::new("userdel").arg(username))
+    }
+
+    fn set_password_hash(&self, username: &str, password_hash: &str) -> Result<(), UserErrors> {
+        Self::run(
+            Command::new("usermod")
+                .arg("-p")
+                .arg(password_hash)
+                .arg(username),
+        )
+    }
+
+    fn set_group(&self, username: &str, group: &str) -> Result<(), UserErrors> {
+        Self::run(Command::new("usermod").arg("-g").arg(group).arg(username))
+    }
+
+    fn set_exp_date(&self, username: &str, exp_date: &str) -> Result<(), UserErrors> {
+        Self::run(Command::new("chage").arg("-E").arg(exp_date).arg(username))
+    }
+
+    fn lock(&self, username: &str) -> Result<(), UserErrors> {
+        Self::run(Command::new("usermod").arg("-L").arg(username))
+    }
+
+    fn unlock(&self, username: &str) -> Result<(), UserErrors> {
+        Self::run(Command::new("usermod").arg("-U").arg(username))
+    }
+
+    fn get_shadow(&self, username: &str) -> Result<ShadowEntry, UserErrors> {
+        shadow::get_entry(&self.shadow_path, username)
+    }
+
+    fn get_account(&self, username: &str) -> Option<Account> {
+        users::get_user_by_name(username).map(|user| Account {
+            username: username.to_string(),
+            uid: user.uid(),
+            gid: user.primary_group_id(),
+            groups: user
+                .groups()
+                .unwrap_or_default()
+                .iter()
+                .map(|group| group.name().to_string_lossy().to_string())
+                .collect(),
+            shell: user.shell().to_string_lossy().to_string(),
+        })
+    }
+
+    fn get_group_name(&self, gid: u32) -> Option<String> {
+        users::get_group_by_gid(gid).map(|group| group.name().to_string_lossy().to_string())
+    }
+
+    fn group_exists(&self, group: &str) -> bool {
+        users::get_group_by_name(group).is_some()
+    }
+
+    fn all_accounts(&self) -> Vec<Account> {
+        let iter = unsafe { users::all_users() };
+
+        iter.map(|user| Account {
+            username: user.name().to_string_lossy().to_string(),
+            uid: user.uid(),
+            gid: user.primary_group_id(),
+            groups: user
+                .groups()
+                .unwrap_or_default()
+                .iter()
+                .map(|group| group.name().to_string_lossy().to_string())
+                .collect(),
+            shell: user.shell().to_string_lossy().to_string(),
+        })
+        .collect()
+    }
+}
+
+/// Account backend that edits a scratch copy of `etc/passwd`, `etc/shadow` and `etc/group`
+/// under a fake root directory, so user management can run without root on a real box.
+pub struct FakeRootBackend {
+    root: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FakeRootBackend {
+    /// Creates a new `FakeRootBackend` operating on the given root directory.
+    pub fn new<P: Into<PathBuf>>(root: P) -> FakeRootBackend {
+        FakeRootBackend {
+            root: root.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Reads a colon separated database file (e.g. `passwd`) from the fake root.
+    fn read_table(&self, name: &str) -> Result<Vec<Vec<String>>, UserErrors> {
+        let content = fs::read_to_string(self.root.join("etc").join(name))
+            .map_err(|_| UserErrors::UnexpectedError)?;
+
+        Ok(content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.split(':').map(|field| field.to_string()).collect())
+            .collect())
+    }
+
+    /// Writes a colon separated database file back into the fake root.
+    fn write_table(&self, name: &str, rows: &[Vec<String>]) -> Result<(), UserErrors> {
+        let mut content = String::new();
+        for row in rows {
+            content.push_str(&row.join(":"));
+            content.push('\n');
+        }
+
+        fs::write(self.root.join("etc").join(name), content)
+            .map_err(|_| UserErrors::PermissionDenied)
+    }
+
+    /// Applies `edit` to the row of `username` in the given table, rows with less than
+    /// `min_fields` fields being refused as malformed.
+    fn edit_row<F>(
+        &self,
+        name: &str,
+        username: &str,
+        min_fields: usize,
+        edit: F,
+    ) -> Result<(), UserErrors>
+    where
+        F: FnOnce(&mut Vec<String>) -> Result<(), UserErrors>,
+    {
+        let _guard = self.lock.lock().map_err(|_| UserErrors::UnexpectedError)?;
+
+        let mut rows = self.read_table(name)?;
+        let row = rows
+            .iter_mut()
+            .find(|row| row[0] == username)
+            .ok_or(UserErrors::InvalidUserOrGroup)?;
+        if row.len() < min_fields {
+            return Err(UserErrors::UnexpectedError);
+        }
+
+        edit(row)?;
+        self.write_table(name, &rows)
+    }
+
+    /// Refuses a value that would split its field or row once written to a table, with `err`.
+    fn check_field(value: &str, err: UserErrors) -> Result<(), UserErrors> {
+        if value.contains([':', '\n']) {
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Looks up a group id by its name.
+    fn get_gid(&self, group: &str) -> Option<u32> {
+        self.read_table("group")
+            .ok()?
+            .iter()
+            .find(|row| row[0] == group)
+            .and_then(|row| row.get(2)?.parse().ok())
+    }
+
+    /// Builds an `Account` from a `passwd` row.
+    fn row_to_account(&self, row: &[String], group_rows: &[Vec<String>]) -> Option<Account> {
+        let uid = row.get(2)?.parse().ok()?;
+        let gid = row.get(3)?.parse().ok()?;
+        let username = row[0].clone();
+
+        let groups = group_rows
+            .iter()
+            .filter(|group| {
+                group.get(2).and_then(|id| id.parse::<u32>().ok()) == Some(gid)
+                    || group
+                        .get(3)
+                        .map_or(false, |members| members.split(',').any(|m| m == username))
+            })
+            .map(|group| group[0].clone())
+            .collect();
+
+        Some(Account {
+            username,
+            uid,
+            gid,
+            groups,
+            shell: row.get(6).cloned().unwrap_or_default(),
+        })
+    }
+}
+
+impl AccountBackend for FakeRootBackend {
+    fn useradd(
+        &self,
+        username: &str,
+        password_hash: &str,
+        shell: &str,
+        group: &str,
+        exp_date: &str,
+    ) -> Result<(), UserErrors> {
+        if shell.is_empty() {
+            return Err(UserErrors::InvalidShell);
+        }
+        Self::check_field(username, UserErrors::InvalidUserOrGroup)?;
+        Self::check_field(password_hash, UserErrors::InvalidPasswordHash)?;
+        Self::check_field(shell, UserErrors::InvalidShell)?;
+
+        let gid = self.get_gid(group).ok_or(UserErrors::InvalidUserOrGroup)?;
+        let exp_days = shadow::parse_exp_date(exp_date)?;
+
+        let _guard = self.lock.lock().map_err(|_| UserErrors::UnexpectedError)?;
+
+        let mut passwd = self.read_table("passwd")?;
+        let mut shadow = self.read_table("shadow")?;
+
+        if passwd.iter().any(|row| row[0] == username) {
+            return Err(UserErrors::UserAlreadyExists);
+        }
+
+        let uid = passwd
+            .iter()
+            .filter_map(|row| row.get(2)?.parse::<u32>().ok())
+            .filter(|uid| *uid >= consts::UID_MIN && *uid < 60000)
+            .max()
+            .map_or(consts::UID_MIN, |uid| uid + 1);
+
+        let today = shadow::today();
+
+        passwd.push(
+            [
+                username,
+                "x",
+                &uid.to_string(),
+                &gid.to_string(),
+                "",
+                &format!("/home/{username}"),
+                shell,
+            ]
+            .iter()
+            .map(|field| field.to_string())
+            .collect(),
+        );
+        shadow.push(
+            [
+                username,
+                password_hash,
+                &today.to_string(),
+                "0",
+                "99999",
+                "7",
+                "",
+                &exp_days.map_or(String::new(), |days| days.to_string()),
+                "",
+            ]
+            .iter()
+            .map(|field| field.to_string())
+            .collect(),
+        );
+
+        self.write_table("passwd", &passwd)?;
+        self.write_table("shadow", &shadow)
+    }
+
+    fn userdel(&self, username: &str) -> Result<(), UserErrors> {
+        let _guard = self.lock.lock().map_err(|_| UserErrors::UnexpectedError)?;
+
+        let mut passwd = self.read_table("passwd")?;
+        if !passwd.iter().any(|row| row[0] == username) {
+            return Err(UserErrors::InvalidUserOrGroup);
+        }
+        passwd.retain(|row| row[0] != username);
+
+        let mut shadow = self.read_table("shadow")?;
+        shadow.retain(|row| row[0] != username);
+
+        let mut group = self.read_table("group")?;
+        for row in group.iter_mut() {
+            if let Some(members) = row.get_mut(3) {
+                *members = members
+                    .split(',')
+                    .filter(|member| !member.is_empty() && *member != username)
+                    .collect::<Vec<&str>>()
+                    .join(",");
+            }
+        }
+
+        self.write_table("passwd", &passwd)?;
+        self.write_table("shadow", &shadow)?;
+        self.write_table("group", &group)
+    }
+
+    fn set_password_hash(&self, username: &str, password_hash: &str) -> Result<(), UserErrors> {
+        Self::check_field(password_hash, UserErrors::InvalidPasswordHash)?;
+
+        self.edit_row("shadow", username, 2, |row| {
+            row[1] = password_hash.to_string();
+            Ok(())
+        })
+    }
+
+    fn set_group(&self, username: &str, group: &str) -> Result<(), UserErrors> {
+        let gid = self.get_gid(group).ok_or(UserErrors::InvalidUserOrGroup)?;
+
+        self.edit_row("passwd", username, 4, |row| {
+            row[3] = gid.to_string();
+            Ok(())
+        })
+    }
+
+    fn set_exp_date(&self, username: &str, exp_date: &str) -> Result<(), UserErrors> {
+        let exp_days = shadow::parse_exp_date(exp_date)?;
+
+        self.edit_row("shadow", username, 2, |row| {
+            row.resize(9, String::new());
+            row[7] = exp_days.map_or(String::new(), |days| days.to_string());
+            Ok(())
+        })
+    }
+
+    fn lock(&self, username: &str) -> Result<(), UserErrors> {
+        self.edit_row("shadow", username, 2, |row| {
+            if !row[1].starts_with('!') {
+                row[1] = format!("!{}", row[1]);
+            }
+            Ok(())
+        })
+    }
+
+    fn unlock(&self, username: &str) -> Result<(), UserErrors> {
+        self.edit_row("shadow", username, 2, |row| {
+            if let Some(password_hash) = row[1].strip_prefix('!') {
+                row[1] = password_hash.to_string();
+            }
+            Ok(())
+        })
+    }
+
+    fn get_shadow(&self, username: &str) -> Result<ShadowEntry, UserErrors> {
+        shadow::get_entry(self.root.join("etc").join("shadow"), username)
+    }
+
+    fn get_account(&self, username: &str) -> Option<Account> {
+        let passwd = self.read_table("passwd").ok()?;
+        let group = self.read_table("group").unwrap_or_default();
+
+        passwd
+            .iter()
+            .find(|row| row[0] == username)
+            .and_then(|row| self.row_to_account(row, &group))
+    }
+
+    fn get_group_name(&self, gid: u32) -> Option<String> {
+        self.read_table("group")
+            .ok()?
+            .into_iter()
+            .find(|row| row.get(2).and_then(|id| id.parse::<u32>().ok()) == Some(gid))
+            .map(|row| row[0].clone())
+    }
+
+    fn group_exists(&self, group: &str) -> bool {
+        self.get_gid(group).is_some()
+    }
+
+    fn all_accounts(&self) -> Vec<Account> {
+        let passwd = self.read_table("passwd").unwrap_or_default();
+        let group = self.read_table("group").unwrap_or_default();
+
+        passwd
+            .iter()
+            .filter_map(|row| self.row_to_account(row, &group))
+            .collect()
+    }
+
+    fn fake_root(&self) -> bool {
+        true
+    }
+}
+
+/// Account backend wrapping another one, only checking that commands would succeed when
+/// `enabled` is set (`dry_run` commands) instead of running them.
+///
+/// Expiry dates aren't checked here, `SSHUser` parses them before reaching the backend.
+pub struct DryRun<'a> {
+    backend: &'a dyn AccountBackend,
+    enabled: bool,
+}
+
+impl<'a> DryRun<'a> {
+    /// Creates a new `DryRun` over `backend`, passing commands through unless `enabled`.
+    pub fn new(backend: &'a dyn AccountBackend, enabled: bool) -> DryRun<'a> {
+        DryRun { backend, enabled }
+    }
+
+    /// Checks that an account exists.
+    fn check_account(&self, username: &str) -> Result<(), UserErrors> {
+        self.backend
+            .get_account(username)
+            .map(|_| ())
+            .ok_or(UserErrors::InvalidUserOrGroup)
+    }
+
+    /// Checks that a group exists.
+    fn check_group(&self, group: &str) -> Result<(), UserErrors> {
+        if self.backend.group_exists(group) {
+            Ok(())
+        } else {
+            Err(UserErrors::InvalidUserOrGroup)
+        }
+    }
+}
+
+impl AccountBackend for DryRun<'_> {
+    fn useradd(
+        &self,
+        username: &str,
+        password_hash: &str,
+        shell: &str,
+        group: &str,
+        exp_date: &str,
+    ) -> Result<(), UserErrors> {
+        if !self.enabled {
+            return self
+                .backend
+                .useradd(username, password_hash, shell, group, exp_date);
+        }
+
+        if self.backend.get_account(username).is_some() {
+            return Err(UserErrors::UserAlreadyExists);
+        }
+        if shell.is_empty() {
+            return Err(UserErrors::InvalidShell);
+        }
+
+        self.check_group(group)
+    }
+
+    fn userdel(&self, username: &str) -> Result<(), UserErrors> {
+        if !self.enabled {
+            return self.backend.userdel(username);
+        }
+
+        self.check_account(username)
+    }
+
+    fn set_password_hash(&self, username: &str, password_hash: &str) -> Result<(), UserErrors> {
+        if !self.enabled {
+            return self.backend.set_password_hash(username, password_hash);
+        }
+
+        self.check_account(username)
+    }
+
+    fn set_group(&self, username: &str, group: &str) -> Result<(), UserErrors> {
+        if !self.enabled {
+            return self.backend.set_group(username, group);
+        }
+
+        self.check_account(username)?;
+        self.check_group(group)
+    }
+
+    fn set_exp_date(&self, username: &str, exp_date: &str) -> Result<(), UserErrors> {
+        if !self.enabled {
+            return self.backend.set_exp_date(username, exp_date);
+        }
+
+        self.check_account(username)
+    }
+
+    fn lock(&self, username: &str) -> Result<(), UserErrors> {
+        if !self.enabled {
+            return self.backend.lock(username);
+        }
+
+        self.check_account(username)
+    }
+
+    fn unlock(&self, username: &str) -> Result<(), UserErrors> {
+        if !self.enabled {
+            return self.backend.unlock(username);
+        }
+
+        self.check_account(username)
+    }
+
+    fn get_shadow(&self, username: &str) -> Result<ShadowEntry, UserErrors> {
+        self.backend.get_shadow(username)
+    }
+
+    fn get_account(&self, username: &str) -> Option<Account> {
+        self.backend.get_account(username)
+    }
+
+    fn get_group_name(&self, gid: u32) -> Option<String> {
+        self.backend.get_group_name(gid)
+    }
+
+    fn group_exists(&self, group: &str) -> bool {
+        self.backend.group_exists(group)
+    }
+
+    fn all_accounts(&self) -> Vec<Account> {
+        self.backend.all_accounts()
+    }
+
+    fn dry_run(&self) -> bool {
+        self.enabled
+    }
+
+    fn fake_root(&self) -> bool {
+        self.backend.fake_root()
+    }
+}
@@ -0,0 +1,363 @@
+use crate::accounting::TrafficAccounting;
+use crate::backend::AccountBackend;
+use crate::consts;
+use crate::passwords::PasswordPolicy;
+use crate::sessions::{self, LoginLimiter};
+use crate::users::{models::*, SSHUser, UserCredentials};
+use api_types::v1;
+use api_types::v1::bulk::{BulkAutoUseradd, BulkItem, BulkResult, UserSelection};
+use api_types::v1::requests::InputSSHUser;
+
+/// Reverts a command applied to a single account.
+type Undo<'a> = Box<dyn FnOnce() -> Result<(), UserErrors> + 'a>;
+
+/// Resolves the accounts a bulk command applies to, which are only ever managed accounts
+/// (uid of `consts::UID_MIN` or above).
+///
+/// # Errors
+///
+/// Returns a message when the lookup has neither a username, a prefix nor a group, when the
+/// prefix is empty or when a listed account isn't managed.
+pub fn select(
+    backend: &dyn AccountBackend,
+    selection: &UserSelection,
+) -> Result<Vec<String>, &'static str> {
+    let usernames = match selection {
+        UserSelection::Usernames(usernames) => usernames.clone(),
+        UserSelection::Lookup(lookup_params) => {
+            if let Some(username) = &lookup_params.username {
+                vec![username.clone()]
+            } else if lookup_params.prefix.is_none() && lookup_params.group.is_none() {
+                return Err("Please provide a username, username's prefix or groupname");
+            } else if lookup_params.prefix.as_deref() == Some("") {
+                return Err("The username's prefix can't be empty");
+            } else {
+                // Lookups silently leave out the system accounts
+                return Ok(SSHUser::get_users_core(
+                    backend,
+                    lookup_params.prefix.as_deref().unwrap_or_default(),
+                    lookup_params.group.as_deref(),
+                )
+                .into_iter()
+                .filter(|username| is_managed(backend, username))
+                .collect());
+            }
+        }
+    };
+
+    // Unknown accounts are left to fail one by one, system ones refuse the whole command
+    let system_account = usernames.iter().any(|username| {
+        backend
+            .get_account(username)
+            .map_or(false, |account| account.uid < consts::UID_MIN)
+    });
+    if system_account {
+        return Err("Bulk commands only apply to managed accounts");
+    }
+
+    Ok(usernames)
+}
+
+/// Checks whether an account exists and is managed.
+fn is_managed(backend: &dyn AccountBackend, username: &str) -> bool {
+    backend
+        .get_account(username)
+        .map_or(false, |account| account.uid >= consts::UID_MIN)
+}
+
+/// Checks that a bulk creation of `count` accounts stays within `consts::MAX_BULK_ACCOUNTS`.
+///
+/// # Errors
+///
+/// Returns a message when there are too many accounts.
+pub fn check_count(count: u64) -> Result<(), &'static str> {
+    if count > consts::MAX_BULK_ACCOUNTS {
+        Err("Too many accounts in a single bulk command")
+    } else {
+        Ok(())
+    }
+}
+
+/// Creates several accounts along with their traffic quota and login limit, the limits being
+/// left untouched on a dry run backend.
+pub fn useradd<'a>(
+    backend: &'a dyn AccountBackend,
+    policy: &PasswordPolicy,
+    accounting: &'a TrafficAccounting,
+    limiter: &'a LoginLimiter,
+    users: Vec<InputSSHUser>,
+    rollback: bool,
+) -> BulkResult<v1::users::SSHUser> {
+    let users = users
+        .into_iter()
+        .map(|user| (user.username.clone(), user))
+        .collect();
+
+    run(users, rollback, |username, user| {
+        sessions::check_limit(user.max_logins)?;
+        let sshuser = SSHUser::add(
+            backend,
+            policy,
+            user.username,
+            user.shell
+                .unwrap_or_else(|| consts::DEFAULT_SHELL.to_string()),
+            user.group,
+            user.exp_date,
+            user.password,
+        )?;
+
+        if backend.dry_run() {
+            return Ok((sshuser.into(), None));
+        }
+
+        let username = username.to_string();
+        let undo: Undo = Box::new(move || {
+            backend.userdel(&username)?;
+            accounting.set_quota(&username, None)?;
+            limiter.set_limit(&username, None)
+        });
+
+        let limits = accounting
+            .set_quota(sshuser.user_credentials.get_username(), user.traffic_quota)
+            .and_then(|_| {
+                limiter.set_limit(sshuser.user_credentials.get_username(), user.max_logins)
+            });
+
+        match limits {
+            Ok(()) => Ok((sshuser.into(), Some(undo))),
+            Err(err) => {
+                if rollback {
+                    let _ = undo();
+                }
+                Err(err)
+            }
+        }
+    })
+}
+
+/// Creates `count` accounts named after the prefix, with random passwords.
+pub fn auto_useradd<'a>(
+    backend: &'a dyn AccountBackend,
+    policy: &PasswordPolicy,
+    accounting: &'a TrafficAccounting,
+    limiter: &'a LoginLimiter,
+    params: BulkAutoUseradd,
+) -> BulkResult<v1::users::SSHUser> {
+    let users = (1..=params.count)
+        .map(|number| InputSSHUser {
+            username: format!(
+                "{}{}",
+                params.prefix,
+                params.users_count.saturating_add(number)
+            ),
+            password: UserCredentials::gen_password(policy),
+            exp_date: params.exp_date.clone(),
+            group: params.group.clone(),
+            shell: None,
+            traffic_quota: params.traffic_quota,
+            max_logins: params.max_logins,
+        })
+        .collect();
+
+    useradd(backend, policy, accounting, limiter, users, params.rollback)
+}
+
+/// Deletes several accounts, restored with the same password, group, shell and expiry date
+/// when rolled back. Restored accounts get a new uid and are listed in `partially_restored`.
+pub fn userdel(
+    backend: &dyn AccountBackend,
+    usernames: Vec<String>,
+    rollback: bool,
+) -> BulkResult<UserStatus> {
+    let mut result = run(for_each(usernames), rollback, |username, _| {
+        let undo = if rollback {
+            let account = backend
+                .get_account(username)
+                .ok_or(UserErrors::InvalidUserOrGroup)?;
+            let shadow = backend.get_shadow(username)?;
+            let group = backend
+                .get_group_name(account.gid)
+                .ok_or(UserErrors::InvalidUserOrGroup)?;
+
+            // `useradd -e` takes days since the epoch, an empty value for no expiry
+            let exp_date = shadow.expiry.map_or(String::new(), |days| days.to_string());
+
+            let username = username.to_string();
+            let undo: Undo = Box::new(move || {
+                backend.useradd(
+                    &username,
+                    shadow.password_hash.as_deref().unwrap_or("!"),
+                    &account.shell,
+                    &group,
+                    &exp_date,
+                )
+            });
+            Some(undo)
+        } else {
+            None
+        };
+
+        SSHUser::userdel(backend, username).map(|status| (status, undo))
+    });
+
+    // Recreated accounts get a new uid, the files of the old one aren't given back to them
+    if result.rolled_back {
+        result.partially_restored = result
+            .results
+            .iter()
+            .filter(|item| item.result.is_ok())
+            .filter(|item| !result.rollback_failed.contains(&item.username))
+            .map(|item| item.username.clone())
+            .collect();
+    }
+
+    result
+}
+
+/// Locks several accounts.
+pub fn userlock(
+    backend: &dyn AccountBackend,
+    usernames: Vec<String>,
+    rollback: bool,
+) -> BulkResult<UserStatus> {
+    run(for_each(usernames), rollback, |username, _| {
+        let undo = if rollback && !backend.get_shadow(username)?.locked {
+            let username = username.to_string();
+            let undo: Undo = Box::new(move || backend.unlock(&username));
+            Some(undo)
+        } else {
+            None
+        };
+
+        SSHUser::usermod_lock(backend, username).map(|status| (status, undo))
+    })
+}
+
+/// Unlocks several accounts.
+pub fn userunlock(
+    backend: &dyn AccountBackend,
+    usernames: Vec<String>,
+    rollback: bool,
+) -> BulkResult<UserStatus> {
+    run(for_each(usernames), rollback, |username, _| {
+        let undo = if rollback && backend.get_shadow(username)?.locked {
+            let username = username.to_string();
+            let undo: Undo = Box::new(move || backend.lock(&username));
+            Some(undo)
+        } else {
+            None
+        };
+
+        SSHUser::usermod_unlock(backend, username).map(|status| (status, undo))
+    })
+}
+
+/// Changes the expiry date of several accounts.
+pub fn chexp(
+    backend: &dyn AccountBackend,
+    usernames: Vec<String>,
+    exp_date: &str,
+    rollback: bool,
+) -> BulkResult<ChExpMsg> {
+    run(for_each(usernames), rollback, |username, _| {
+        let undo = if rollback {
+            // `chage -E` takes days since the epoch, `-1` for no expiry
+            let previous = backend
+                .get_shadow(username)?
+                .expiry
+                .map_or("-1".to_string(), |days| days.to_string());
+            let username = username.to_string();
+            let undo: Undo = Box::new(move || backend.set_exp_date(&username, &previous));
+            Some(undo)
+        } else {
+            None
+        };
+
+        SSHUser::usermod_change_exp(backend, username, exp_date).map(|msg| (msg, undo))
+    })
+}
+
+/// Changes the primary group of several accounts.
+pub fn chgrp(
+    backend: &dyn AccountBackend,
+    usernames: Vec<String>,
+    group: &str,
+    rollback: bool,
+) -> BulkResult<ChGrpMsg> {
+    run(for_each(usernames), rollback, |username, _| {
+        let undo = if rollback {
+            let previous = backend
+                .get_account(username)
+                .and_then(|account| backend.get_group_name(account.gid))
+                .ok_or(UserErrors::InvalidUserOrGroup)?;
+            let username = username.to_string();
+            let undo: Undo = Box::new(move || backend.set_group(&username, &previous));
+            Some(undo)
+        } else {
+            None
+        };
+
+        SSHUser::usermod_change_grp(backend, username, group).map(|msg| (msg, undo))
+    })
+}
+
+fn for_each(usernames: Vec<String>) -> Vec<(String, ())> {
+    usernames
+        .into_iter()
+        .map(|username| (username, ()))
+        .collect()
+}
+
+/// Applies `command` to every account in order. With `rollback` set, stops at the first
+/// failure and reverts the accounts already changed, the last one first.
+fn run<'a, I, T>(
+    items: Vec<(String, I)>,
+    rollback: bool,
+    mut command: impl FnMut(&str, I) -> Result<(T, Option<Undo<'a>>), UserErrors>,
+) -> BulkResult<T> {
+    let mut results = Vec::new();
+    let mut undos: Vec<(String, Undo)> = Vec::new();
+
+    for (username, item) in items {
+        match command(&username, item) {
+            Ok((reply, undo)) => {
+                if let Some(undo) = undo.filter(|_| rollback) {
+                    undos.push((username.clone(), undo));
+                }
+
+                results.push(BulkItem {
+                    username,
+                    result: Ok(reply),
+                });
+            }
+            Err(err) => {
+                results.push(BulkItem {
+                    username,
+                    result: Err(err),
+                });
+
+                if rollback {
+                    let rollback_failed = undos
+                        .into_iter()
+                        .rev()
+                        .filter_map(|(username, undo)| undo().err().map(|_| username))
+                        .collect();
+
+                    return BulkResult {
+                        results,
+                        rolled_back: true,
+                        rollback_failed,
+                        partially_restored: Vec::new(),
+                    };
+                }
+            }
+        }
+    }
+
+    BulkResult {
+        results,
+        rolled_back: false,
+        rollback_failed: Vec::new(),
+        partially_restored: Vec::new(),
+    }
+}
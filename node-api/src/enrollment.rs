@@ -0,0 +1,136 @@
+use crate::config::{ConfigFile, NodeInfo, TlsSettings};
+use crate::consts;
+use api_types::v1::node::{EnrollRequest, EnrolledNode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{BufReader, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// Struct representing the settings node-api enrolls itself in centric-api with, usually set
+/// through `SSHMGMT_ENROLL_CODE`, `SSHMGMT_CENTRIC_URL` and `SSHMGMT_PUBLIC_ADDRESS`.
+#[derive(Deserialize)]
+pub struct EnrollmentSettings {
+    /// One-time code created by an admin in centric-api.
+    pub enroll_code: String,
+    pub centric_url: String,
+    /// PEM CA certificates centric-api's certificate is verified against, besides the system ones.
+    pub centric_ca: Option<String>,
+    /// Address centric-api reaches this node at, e.g. `https://node01.example.com:8010`.
+    pub public_address: String,
+    pub node_info: NodeInfo,
+    pub tls: Option<TlsSettings>,
+}
+
+/// Struct representing the credentials received on enrollment, stored as a configuration
+/// source so the node keeps them across restarts.
+#[derive(Serialize, Deserialize)]
+pub struct EnrollmentCredentials {
+    pub node_id: String,
+    pub jwt_secret: String,
+}
+
+/// Enrolls the node in centric-api if an enrollment code is configured and the node isn't
+/// enrolled yet, then stores the node ID and key it was given.
+///
+/// # Errors
+///
+/// Returns a message describing why the enrollment failed.
+pub async fn enroll_if_requested() -> Result<(), String> {
+    let settings = ConfigFile::settings().map_err(|e| e.to_string())?;
+    if settings.get_string("enroll_code").is_err() {
+        return Ok(());
+    }
+
+    let enrollment_path = ConfigFile::enrollment_path().map_err(|e| e.to_string())?;
+    if Path::new(&enrollment_path).exists() {
+        eprintln!("Already enrolled ({enrollment_path} exists), ignoring the enrollment code");
+        return Ok(());
+    }
+
+    let enrollment = settings
+        .try_deserialize::<EnrollmentSettings>()
+        .map_err(|e| format!("incomplete enrollment settings: {e}"))?;
+
+    let enrolled = enroll(&enrollment).await?;
+    let credentials = EnrollmentCredentials {
+        node_id: enrolled.node_id.to_string(),
+        jwt_secret: enrolled.jwt_secret,
+    };
+    store(&enrollment_path, &credentials)?;
+
+    eprintln!(
+        "Enrolled as node {}, pending until an admin approves it",
+        credentials.node_id
+    );
+
+    Ok(())
+}
+
+/// Sends the enrollment request and returns the credentials centric-api issued.
+async fn enroll(enrollment: &EnrollmentSettings) -> Result<EnrolledNode, String> {
+    let tls_fingerprint = match &enrollment.tls {
+        Some(tls) => Some(cert_fingerprint(&tls.certs)?),
+        None => None,
+    };
+
+    let mut client = reqwest::Client::builder();
+    if let Some(centric_ca) = &enrollment.centric_ca {
+        let pem = fs::read(centric_ca).map_err(|e| format!("couldn't read {centric_ca}: {e}"))?;
+        let ca = reqwest::Certificate::from_pem(&pem).map_err(|e| e.to_string())?;
+        client = client.add_root_certificate(ca);
+    }
+    let client = client.build().map_err(|e| e.to_string())?;
+
+    let response = client
+        .post(format!(
+            "{}{}",
+            enrollment.centric_url.trim_end_matches('/'),
+            consts::CENTRIC_ENROLL_PATH
+        ))
+        .json(&EnrollRequest {
+            code: enrollment.enroll_code.clone(),
+            address: enrollment.public_address.clone(),
+            tls_fingerprint,
+            node_info: enrollment.node_info.clone(),
+        })
+        .send()
+        .await
+        .map_err(|e| format!("couldn't reach centric-api: {e}"))?
+        .json::<Result<EnrolledNode, Value>>()
+        .await
+        .map_err(|e| format!("unexpected response from centric-api: {e}"))?;
+
+    response.map_err(|err| format!("centric-api refused the enrollment: {err}"))
+}
+
+/// Stores the enrollment credentials, only readable by the user node-api runs as.
+fn store(path: &str, credentials: &EnrollmentCredentials) -> Result<(), String> {
+    if let Some(parent) = Path::new(path).parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("couldn't create {parent:?}: {e}"))?;
+    }
+
+    let json = serde_json::to_string_pretty(credentials).map_err(|e| e.to_string())?;
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .map_err(|e| format!("couldn't write {path}: {e}"))?;
+
+    file.write_all(json.as_bytes())
+        .map_err(|e| format!("couldn't write {path}: {e}"))
+}
+
+/// Returns the lowercase hex SHA-256 fingerprint of the first certificate of a PEM file.
+pub fn cert_fingerprint(path: &str) -> Result<String, String> {
+    let file = File::open(path).map_err(|e| format!("couldn't open {path}: {e}"))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .map_err(|e| format!("couldn't read certificates from {path}: {e}"))?;
+
+    let cert = certs.first().ok_or(format!("no certificates in {path}"))?;
+
+    Ok(format!("{:x}", Sha256::digest(cert)))
+}
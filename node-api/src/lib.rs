@@ -1,49 +1,270 @@
 #![feature(exit_status_error)]
+pub mod accounting;
+pub mod audit;
+pub mod backend;
+pub mod bulk;
+pub mod enrollment;
+pub mod metrics;
 pub mod models;
+pub mod passwords;
+pub mod sessions;
+pub mod shadow;
 pub mod stats;
 pub mod users;
 
 pub mod consts {
-    // Use Config file in future
-    pub const PASSWD_IV: &[u8] = b"18334ba316694d5e5917ce520420cb1018adfcb2";
-    pub const PASSWD_PREFIX: &str = "SSHMGMTKIT_";
-    pub const PASSWD_PARAMS: &str = "$6$mENJascSdtQuhrXH";
-    pub const JWT_SECRET: &[u8] = b"1bdf1a42-4966-1bd0-6f69-04c34f2eaa29";
-    pub const NETHOGS_TRACE_PATH: &str = "/tmp/log";
+    pub const PASSWD_LEN: usize = 16;
+    pub const PASSWD_MIN_LEN: usize = 8;
+    /// Alphanumerics without look-alikes (`0`/`O`, `1`/`l`/`I`).
+    pub const PASSWD_ALPHABET: &str = "23456789abcdefghijkmnopqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ";
+    /// Salt every password hash used to be created with.
+    pub const LEGACY_PASSWD_SALT: &str = "mENJascSdtQuhrXH";
+    /// Secrets that used to be hard-coded here, refused as configuration values.
+    pub const LEGACY_SECRETS: &[&str] = &[
+        "18334ba316694d5e5917ce520420cb1018adfcb2",
+        "mENJascSdtQuhrXH",
+        "1bdf1a42-4966-1bd0-6f69-04c34f2eaa29",
+    ];
+    pub const MIN_SECRET_LEN: usize = 32;
+    pub const CONFIG_PATH: &str = "/etc/sshmgmt_config.json";
+    pub const ENV_PREFIX: &str = "SSHMGMT";
+    pub const ENROLLMENT_PATH: &str = "/var/lib/sshmgmt/enrollment.json";
+    pub const CENTRIC_ENROLL_PATH: &str = "/nodes/enroll";
+    pub const TRAFFIC_STORE_PATH: &str = "/var/lib/sshmgmt/traffic.json";
+    pub const ACCOUNTING_INTERVAL_SECS: u64 = 60;
+    pub const LOGIN_LIMITS_PATH: &str = "/var/lib/sshmgmt/login_limits.json";
+    pub const LOGIN_LIMIT_INTERVAL_SECS: u64 = 10;
+    pub const AUDIT_LOG_PATH: &str = "/var/lib/sshmgmt/audit.log";
+    pub const NET_SAMPLE_INTERVAL_SECS: u64 = 10;
+    pub const CPU_SAMPLE_INTERVAL_SECS: u64 = 10;
+    pub const SSH_PORT: u16 = 22;
+    pub const UID_MIN: u32 = 1000;
+    pub const MAX_BULK_ACCOUNTS: u64 = 500;
+    pub const SHADOW_PATH: &str = "/etc/shadow";
     pub const DEFAULT_SHELL: &str = "/bin/rbash";
 }
 
 pub mod config {
-    use config::Config;
+    use crate::backend::{AccountBackend, FakeRootBackend, ShadowUtilsBackend};
+    use crate::consts;
+    use crate::passwords::PasswordPolicy;
+    pub use api_types::v1::node::NodeInfo;
+    use config::{Config, ConfigError, FileFormat};
+    use rocket::config::{MutualTls, TlsConfig};
     use serde::{Deserialize, Serialize};
+    use std::path::Path;
 
-    /// Represents information about an SSH management node.
+    /// Represents the secrets used to verify API tokens.
     #[derive(Serialize, Deserialize, Clone)]
-    pub struct NodeInfo {
-        name: String,
-        location: String,
-        capacity: Option<u64>,
+    pub struct Secrets {
+        /// Key API tokens are verified with.
+        pub jwt_secret: String,
+        /// Key being rotated out, tokens signed with it are accepted until it's removed.
+        pub jwt_previous_secret: Option<String>,
+        /// Fixed salt legacy password hashes were created with, accounts using it are flagged.
+        pub passwd_salt: Option<String>,
+        /// Static bearer token Prometheus scrapes `/metrics` with, as API tokens are short-lived.
+        pub metrics_token: Option<String>,
+    }
+
+    impl Secrets {
+        /// Returns the keys API tokens are verified with, the current one first.
+        pub fn jwt_secrets(&self) -> Vec<String> {
+            std::iter::once(self.jwt_secret.clone())
+                .chain(self.jwt_previous_secret.clone())
+                .collect()
+        }
+
+        /// Returns the fixed salts of the legacy password scheme.
+        pub fn legacy_salts(&self) -> Vec<&str> {
+            std::iter::once(consts::LEGACY_PASSWD_SALT)
+                .chain(self.passwd_salt.as_deref())
+                .collect()
+        }
+
+        /// Checks that no secret is missing, too weak, a sample placeholder or one of the
+        /// previously hard-coded ones.
+        ///
+        /// # Errors
+        ///
+        /// Returns a message naming the first offending secret.
+        pub fn check(&self) -> Result<(), String> {
+            let keys = [
+                ("jwt_secret", Some(&self.jwt_secret)),
+                ("jwt_previous_secret", self.jwt_previous_secret.as_ref()),
+                ("metrics_token", self.metrics_token.as_ref()),
+            ];
+
+            for (name, key) in keys {
+                let Some(key) = key else { continue };
+
+                if consts::LEGACY_SECRETS.contains(&key.as_str()) {
+                    return Err(format!("{name} is a published default, generate a new one"));
+                }
+                if key.starts_with('<') {
+                    return Err(format!(
+                        "{name} is a sample placeholder, generate a new one"
+                    ));
+                }
+                if key.len() < consts::MIN_SECRET_LEN {
+                    return Err(format!(
+                        "{name} should be at least {} characters long",
+                        consts::MIN_SECRET_LEN
+                    ));
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Represents the TLS settings the API is served with.
+    #[derive(Serialize, Deserialize, Clone)]
+    pub struct TlsSettings {
+        /// Path of the PEM certificate chain served to clients.
+        pub certs: String,
+        /// Path of the PEM private key of the certificate.
+        pub key: String,
+        /// Path of the PEM CA certificates client certificates are verified against.
+        pub client_ca: Option<String>,
+        /// Refuses connections without a client certificate signed by `client_ca`.
+        #[serde(default)]
+        pub require_client_cert: bool,
+    }
+
+    impl TlsSettings {
+        /// Checks that the certificate files exist and client certificates can be verified.
+        ///
+        /// # Errors
+        ///
+        /// Returns a message describing the problem.
+        pub fn check(&self) -> Result<(), String> {
+            if self.require_client_cert && self.client_ca.is_none() {
+                return Err("require_client_cert is set without a client_ca".to_string());
+            }
+
+            let files = [
+                ("certs", Some(&self.certs)),
+                ("key", Some(&self.key)),
+                ("client_ca", self.client_ca.as_ref()),
+            ];
+            for (name, path) in files {
+                let Some(path) = path else { continue };
+
+                if !Path::new(path).is_file() {
+                    return Err(format!("{name} file {path} doesn't exist"));
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Converts the settings to Rocket's TLS configuration.
+        pub fn rocket_config(&self) -> TlsConfig {
+            let tls = TlsConfig::from_paths(&self.certs, &self.key);
+
+            match &self.client_ca {
+                Some(client_ca) => tls.with_mutual(
+                    MutualTls::from_path(client_ca).mandatory(self.require_client_cert),
+                ),
+                None => tls,
+            }
+        }
     }
 
     /// Represents the configuration file structure for SSH management.
     #[derive(Serialize, Deserialize, Clone)]
     pub struct ConfigFile {
+        /// ID the node is registered with in centric-api, API tokens minted for any other
+        /// node are refused.
+        pub node_id: String,
         pub node_info: NodeInfo,
+        #[serde(flatten)]
+        pub secrets: Secrets,
+        /// How account passwords are generated and hashed.
+        #[serde(default)]
+        pub passwords: PasswordPolicy,
+        /// Directory holding a scratch `etc/passwd`, `etc/shadow` and `etc/group` to manage
+        /// instead of the live system (used for testing).
+        pub fake_root: Option<String>,
+        /// Overrides the shadow file read for account records (defaults to `/etc/shadow`).
+        pub shadow_path: Option<String>,
+        /// Overrides where cumulative per-user traffic is stored.
+        pub traffic_store_path: Option<String>,
+        /// Overrides where per-user concurrent login limits are stored.
+        pub login_limits_path: Option<String>,
+        /// Overrides where the audit log of commands is appended to.
+        pub audit_log_path: Option<String>,
+        /// Ports sshd listens on, used to find the client address of sessions (defaults to 22).
+        pub ssh_ports: Option<Vec<u16>>,
+        /// Serves the API over TLS, plain HTTP is used when it's missing.
+        pub tls: Option<TlsSettings>,
+        /// Overrides where the credentials received on enrollment are stored.
+        pub enrollment_path: Option<String>,
     }
 
     impl ConfigFile {
-        /// Loads the configuration from the specified file path ("/etc/sshmgmt_config.json").
+        /// Loads the configuration from the specified file path ("/etc/sshmgmt_config.json"),
+        /// top-level keys can be overridden by `SSHMGMT_<KEY>` environment variables
+        /// (e.g. `SSHMGMT_JWT_SECRET`).
         ///
         /// # Errors
         ///
         /// Returns a `Result` with `ConfigFile` if successful, or a `Box<dyn std::error::Error>`
         /// on failure.
         pub fn load() -> Result<ConfigFile, Box<dyn std::error::Error>> {
+            Ok(Self::settings()?.try_deserialize::<ConfigFile>()?)
+        }
+
+        /// Reads the raw settings: the configuration file, then the credentials stored on
+        /// enrollment (if any), then the `SSHMGMT_<KEY>` environment variables.
+        ///
+        /// # Errors
+        ///
+        /// Returns a `ConfigError` if a source can't be read.
+        pub fn settings() -> Result<Config, ConfigError> {
+            let enrollment_path = Self::enrollment_path()?;
+
+            Config::builder()
+                .add_source(config::File::with_name(consts::CONFIG_PATH))
+                .add_source(config::File::new(&enrollment_path, FileFormat::Json).required(false))
+                .add_source(config::Environment::with_prefix(consts::ENV_PREFIX))
+                .build()
+        }
+
+        /// Returns where the credentials received on enrollment are stored.
+        ///
+        /// # Errors
+        ///
+        /// Returns a `ConfigError` if the configuration file can't be read.
+        pub fn enrollment_path() -> Result<String, ConfigError> {
             let settings = Config::builder()
-                .add_source(config::File::with_name("/etc/sshmgmt_config.json"))
+                .add_source(config::File::with_name(consts::CONFIG_PATH))
+                .add_source(config::Environment::with_prefix(consts::ENV_PREFIX))
                 .build()?;
 
-            Ok(settings.try_deserialize::<ConfigFile>()?)
+            Ok(settings
+                .get_string("enrollment_path")
+                .unwrap_or(consts::ENROLLMENT_PATH.to_string()))
+        }
+
+        /// Returns the ports sshd listens on.
+        pub fn ssh_ports(&self) -> Vec<u16> {
+            self.ssh_ports
+                .clone()
+                .unwrap_or(vec![crate::consts::SSH_PORT])
+        }
+
+        /// Creates the account backend selected by the configuration.
+        pub fn account_backend(&self) -> Box<dyn AccountBackend> {
+            match &self.fake_root {
+                Some(root) => Box::new(FakeRootBackend::new(root)),
+                None => Box::new(ShadowUtilsBackend::new(
+                    self.shadow_path
+                        .as_deref()
+                        .unwrap_or(crate::consts::SHADOW_PATH),
+                )),
+            }
         }
     }
 }
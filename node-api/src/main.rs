@@ -1,23 +1,32 @@
-#![feature(proc_macro_hygiene, decl_macro)]
-
 #[macro_use]
 extern crate rocket;
 
+use api_types::v1;
+use api_types::v1::bulk::*;
+use api_types::v2;
+use lib::accounting::{TrafficAccounting, TrafficTotals, UserQuota};
+use lib::audit::{self, Audit, AuditEntry, AuditLog};
+use lib::backend::{AccountBackend, DryRun};
+use lib::bulk;
 use lib::config;
 use lib::consts;
+use lib::enrollment;
+use lib::metrics;
 use lib::models::*;
+use lib::passwords;
+use lib::sessions::{self, KickedSessions, LoginLimiter, LoginViolation, Session};
 use lib::stats::*;
 use lib::users::models::*;
 use lib::users::*;
+use rocket::http::ContentType;
+use rocket::serde::json::Json;
 use rocket::State;
-use rocket_contrib::json::Json;
-use serde_json::json;
-use serde_json::Value;
-use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
 macro_rules! api_err {
     ($msg:expr) => {
-        Json(json!({ "Err": $msg }))
+        Json(Refusal::new($msg))
     };
 }
 
@@ -26,39 +35,98 @@ fn ping() -> &'static str {
     "pong"
 }
 
+#[get("/metrics")]
+fn prometheus_metrics(
+    token: Token,
+    backend: &State<Box<dyn AccountBackend>>,
+    accounting: &State<Arc<TrafficAccounting>>,
+    node_config: &State<config::ConfigFile>,
+) -> Result<(ContentType, String), Json<Refusal>> {
+    token.validate_metrics()?;
+
+    let exposition = metrics::render(
+        &node_config.node_info,
+        &***backend,
+        accounting,
+        &node_config.ssh_ports(),
+    );
+
+    Ok((
+        ContentType::new("text", "plain").with_params(("version", "0.0.4")),
+        exposition,
+    ))
+}
+
 #[get("/node_info")]
-fn node_info(node_config: State<config::ConfigFile>) -> Json<config::NodeInfo> {
+fn node_info(node_config: &State<config::ConfigFile>) -> Json<config::NodeInfo> {
     Json(node_config.node_info.clone())
 }
 
 #[get("/net_stats")]
-fn net_stats() -> Json<Vec<NetworkUsage>> {
-    Json(NetworkUsage::get_usage())
+fn net_stats(token: Token) -> Result<Json<Vec<NetworkUsage>>, Json<Refusal>> {
+    token.validate(Scope::StatsRead)?;
+    Ok(Json(net_usage()))
 }
 
 #[get("/hw_stats")]
-fn hw_stats() -> Json<HwUsage> {
-    Json(HwUsage::new())
+fn hw_stats(token: Token) -> Result<Json<HwUsage>, Json<Refusal>> {
+    token.validate(Scope::StatsRead)?;
+    Ok(Json(hw_usage()))
+}
+
+#[get("/net_stats?<pretty>")]
+fn net_stats_v2(
+    token: Token,
+    sampler: &State<Arc<NetSampler>>,
+    pretty: Option<bool>,
+) -> Result<Json<v2::stats::NetStats>, Json<Refusal>> {
+    token.validate(Scope::StatsRead)?;
+    Ok(Json(sampler.net_stats(pretty.unwrap_or(false))))
+}
+
+#[get("/hw_stats?<pretty>")]
+fn hw_stats_v2(
+    token: Token,
+    pretty: Option<bool>,
+) -> Result<Json<Result<v2::stats::HwStats, UserErrors>>, Json<Refusal>> {
+    token.validate(Scope::StatsRead)?;
+    Ok(Json(lib::stats::hw_stats(pretty.unwrap_or(false))))
+}
+
+#[get("/system_stats")]
+fn system_stats_v2(
+    token: Token,
+    sampler: &State<Arc<CpuSampler>>,
+    backend: &State<Box<dyn AccountBackend>>,
+    node_config: &State<config::ConfigFile>,
+) -> Result<Json<Result<v2::stats::SystemStats, UserErrors>>, Json<Refusal>> {
+    token.validate(Scope::StatsRead)?;
+    Ok(Json(lib::stats::system_stats(
+        sampler,
+        node_config.node_info.capacity,
+        &***backend,
+    )))
 }
 
 #[post("/list_users", format = "json", data = "<lookup_params>")]
 fn list_users(
     token: Token,
+    backend: &State<Box<dyn AccountBackend>>,
     lookup_params: Json<UserLookupParams>,
-) -> Result<Json<Vec<String>>, Json<Value>> {
-    token.validate()?;
+) -> Result<Json<Vec<String>>, Json<Refusal>> {
+    token.validate(Scope::UsersRead)?;
 
     if lookup_params.prefix.is_none() && lookup_params.group.is_none() {
         return Err(api_err!("Please provide username's prefix or groupname"));
     }
 
     if let Some(prefix) = &lookup_params.prefix {
-        return Ok(Json(SSHUser::get_users_by_prefix(&prefix)));
+        return Ok(Json(SSHUser::get_users_by_prefix(&***backend, &prefix)));
     } else {
         if let Some(group) = &lookup_params.group {
-            return Ok(Json(SSHUser::get_users_by_group(&group)));
+            return Ok(Json(SSHUser::get_users_by_group(&***backend, &group)));
         } else {
-            return Ok(Json(SSHUser::get_users_by_prefix("")));
+            return Ok(Json(SSHUser::get_users_by_prefix(&***backend, "")));
         }
     }
 }
@@ -66,163 +134,366 @@ fn list_users(
 #[get("/user_expiry/<user>")]
 fn user_expiry(
     token: Token,
+    backend: &State<Box<dyn AccountBackend>>,
+    user: String,
+) -> Result<Json<Result<UserExp, UserErrors>>, Json<Refusal>> {
+    token.validate(Scope::UsersRead)?;
+    Ok(Json(SSHUser::get_chage_exp(&***backend, &user)))
+}
+
+#[get("/user_quota/<user>")]
+fn user_quota(
+    token: Token,
+    accounting: &State<Arc<TrafficAccounting>>,
     user: String,
-) -> Result<Json<Result<UserExp, UserErrors>>, Json<Value>> {
-    token.validate()?;
-    Ok(Json(SSHUser::get_chage_exp(&user)))
+) -> Result<Json<Result<UserQuota, UserErrors>>, Json<Refusal>> {
+    token.validate(Scope::UsersRead)?;
+    Ok(Json(accounting.quota(&user)))
+}
+
+#[get("/legacy_passwords")]
+fn legacy_passwords(
+    token: Token,
+    backend: &State<Box<dyn AccountBackend>>,
+    node_config: &State<config::ConfigFile>,
+) -> Result<Json<Vec<String>>, Json<Refusal>> {
+    token.validate(Scope::UsersRead)?;
+    Ok(Json(passwords::legacy_accounts(
+        &***backend,
+        &node_config.secrets.legacy_salts(),
+    )))
+}
+
+#[get("/login_violations?<user>")]
+fn login_violations(
+    token: Token,
+    limiter: &State<Arc<LoginLimiter>>,
+    user: Option<String>,
+) -> Result<Json<Result<Vec<LoginViolation>, UserErrors>>, Json<Refusal>> {
+    token.validate(Scope::UsersRead)?;
+    Ok(Json(limiter.violations(user.as_deref())))
+}
+
+#[get("/audit?<from>&<to>&<user>")]
+fn audit_log(
+    token: Token,
+    audit_log: &State<AuditLog>,
+    from: Option<u64>,
+    to: Option<u64>,
+    user: Option<String>,
+) -> Result<Json<Result<Vec<AuditEntry>, UserErrors>>, Json<Refusal>> {
+    token.validate(Scope::UsersRead)?;
+    Ok(Json(audit_log.entries(from, to, user.as_deref())))
+}
+
+#[get("/sessions?<user>")]
+fn list_sessions(
+    token: Token,
+    backend: &State<Box<dyn AccountBackend>>,
+    node_config: &State<config::ConfigFile>,
+    user: Option<String>,
+) -> Result<Json<Vec<Session>>, Json<Refusal>> {
+    token.validate(Scope::UsersRead)?;
+
+    let sessions = sessions::list_sessions(&***backend, &node_config.ssh_ports())
+        .into_iter()
+        .filter(|session| user.as_ref().map_or(true, |user| &session.username == user))
+        .collect();
+
+    Ok(Json(sessions))
+}
+
+#[post("/kick", format = "json", data = "<kick_params>")]
+fn kick(
+    token: Token,
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    node_config: &State<config::ConfigFile>,
+    kick_params: Json<Command<KickParams>>,
+) -> Result<Json<Result<KickedSessions, UserErrors>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &kick_params)?;
+    let backend = DryRun::new(&***backend, kick_params.dry_run);
+
+    let username = kick_params.params.username.as_deref();
+    if username.is_none() && kick_params.params.pid.is_none() {
+        return Err(audit.refuse(
+            &claims,
+            &kick_params,
+            "Please provide the username or the session's pid",
+        ));
+    }
+
+    let result = sessions::kick(
+        &backend,
+        &node_config.ssh_ports(),
+        username,
+        kick_params.params.pid,
+    );
+    audit.record(&claims, username, &kick_params, &result);
+
+    Ok(Json(result))
 }
 
 #[post("/userdel", format = "json", data = "<lookup_params>")]
 fn userdel(
     token: Token,
-    lookup_params: Json<UserLookupParams>,
-) -> Result<Json<Result<UserStatus, UserErrors>>, Json<Value>> {
-    match token.validate() {
-        Ok(role) => match role {
-            Role::Privileged => {
-                if let Some(username) = &lookup_params.username {
-                    Ok(Json(SSHUser::userdel(&username)))
-                } else {
-                    Err(api_err!("username field cannot be empty"))
-                }
-            }
-            _ => Err(api_err!("Authentication Failed")),
-        },
-        Err(e) => Err(api_err!(*e)),
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    lookup_params: Json<Command<UserLookupParams>>,
+) -> Result<Json<Result<UserStatus, UserErrors>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &lookup_params)?;
+    let backend = DryRun::new(&***backend, lookup_params.dry_run);
+
+    if let Some(username) = &lookup_params.params.username {
+        let result = SSHUser::userdel(&backend, username);
+        audit.record(&claims, Some(username), &lookup_params, &result);
+
+        Ok(Json(result))
+    } else {
+        Err(audit.refuse(&claims, &lookup_params, "username field cannot be empty"))
     }
 }
 
 #[post("/auto_useradd", format = "json", data = "<user_data>")]
 fn auto_useradd(
     token: Token,
-    user_data: Json<AutoSSHUser>,
-) -> Result<Json<Result<SSHUser, UserErrors>>, Json<Value>> {
-    match token.validate() {
-        Ok(role) => match role {
-            Role::Privileged => {
-                let user_data = user_data.0;
-                Ok(Json(SSHUser::auto_add(
-                    (&user_data.prefix, user_data.users_count),
-                    user_data.group,
-                    user_data.exp_date,
-                )))
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    node_config: &State<config::ConfigFile>,
+    accounting: &State<Arc<TrafficAccounting>>,
+    limiter: &State<Arc<LoginLimiter>>,
+    user_data: Json<Command<AutoSSHUser>>,
+) -> Result<Json<Result<v1::users::SSHUser, UserErrors>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &user_data)?;
+    let backend = DryRun::new(&***backend, user_data.dry_run);
+
+    let user = user_data.params.clone();
+    let result: Result<v1::users::SSHUser, UserErrors> = sessions::check_limit(user.max_logins)
+        .and_then(|_| {
+            SSHUser::auto_add(
+                &backend,
+                &node_config.passwords,
+                (&user.prefix, user.users_count),
+                user.group,
+                user.exp_date,
+            )
+        })
+        .and_then(|sshuser| {
+            if !user_data.dry_run {
+                let username = sshuser.user_credentials.get_username();
+                accounting.set_quota(username, user.traffic_quota)?;
+                limiter.set_limit(username, user.max_logins)?;
             }
-            _ => Err(api_err!("Authentication Failed")),
-        },
-        Err(e) => Err(api_err!(*e)),
-    }
+            Ok(sshuser)
+        })
+        .map(Into::into);
+
+    let username = result
+        .as_ref()
+        .ok()
+        .map(|sshuser| sshuser.username.as_str());
+    audit.record(&claims, username, &user_data, &result);
+
+    Ok(Json(result))
 }
 
 #[post("/useradd", format = "json", data = "<user_data>")]
 fn useradd(
     token: Token,
-    user_data: Json<InputSSHUser>,
-) -> Result<Json<Result<SSHUser, UserErrors>>, Json<Value>> {
-    match token.validate() {
-        Ok(role) => match role {
-            Role::Privileged => {
-                let user_data = user_data.0;
-                let shell = user_data.shell.unwrap_or(consts::DEFAULT_SHELL.to_string());
-                Ok(Json(SSHUser::add(
-                    user_data.username,
-                    shell,
-                    user_data.group,
-                    user_data.exp_date,
-                    user_data.password,
-                )))
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    node_config: &State<config::ConfigFile>,
+    accounting: &State<Arc<TrafficAccounting>>,
+    limiter: &State<Arc<LoginLimiter>>,
+    user_data: Json<Command<InputSSHUser>>,
+) -> Result<Json<Result<v1::users::SSHUser, UserErrors>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &user_data)?;
+    let backend = DryRun::new(&***backend, user_data.dry_run);
+
+    let user = user_data.params.clone();
+    let shell = user.shell.unwrap_or(consts::DEFAULT_SHELL.to_string());
+    let result = sessions::check_limit(user.max_logins)
+        .and_then(|_| {
+            SSHUser::add(
+                &backend,
+                &node_config.passwords,
+                user.username,
+                shell,
+                user.group,
+                user.exp_date,
+                user.password,
+            )
+        })
+        .and_then(|sshuser| {
+            if !user_data.dry_run {
+                let username = sshuser.user_credentials.get_username();
+                accounting.set_quota(username, user.traffic_quota)?;
+                limiter.set_limit(username, user.max_logins)?;
             }
-            _ => Err(api_err!("Authentication Failed")),
-        },
-        Err(e) => Err(api_err!(*e)),
-    }
+            Ok(sshuser)
+        })
+        .map(Into::into);
+    audit.record(
+        &claims,
+        Some(&user_data.params.username),
+        &user_data,
+        &result,
+    );
+
+    Ok(Json(result))
 }
 
 #[post("/passwd", format = "json", data = "<user>")]
 fn passwd(
     token: Token,
-    user: Json<UserPasswd>,
-) -> Result<Json<Result<UserRawCreds, UserErrors>>, Json<Value>> {
-    match token.validate() {
-        Ok(role) => match role {
-            Role::Privileged => Ok(Json(SSHUser::usermod_change_pass(
-                &user.username,
-                &user.password,
-            ))),
-            _ => Err(api_err!("Authentication Failed")),
-        },
-        Err(e) => Err(api_err!(*e)),
-    }
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    node_config: &State<config::ConfigFile>,
+    user: Json<Command<UserPasswd>>,
+) -> Result<Json<Result<UserPasswordHash, UserErrors>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &user)?;
+    let backend = DryRun::new(&***backend, user.dry_run);
+
+    let result = SSHUser::usermod_change_pass(
+        &backend,
+        &node_config.passwords,
+        &user.params.username,
+        &user.params.password,
+    );
+    audit.record(&claims, Some(&user.params.username), &user, &result);
+
+    Ok(Json(result))
+}
+
+#[post("/reset_password", format = "json", data = "<user>")]
+fn reset_password(
+    token: Token,
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    node_config: &State<config::ConfigFile>,
+    user: Json<Command<OnlyUser>>,
+) -> Result<Json<Result<UserRawCreds, UserErrors>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &user)?;
+    let backend = DryRun::new(&***backend, user.dry_run);
+
+    let result =
+        SSHUser::usermod_reset_pass(&backend, &node_config.passwords, &user.params.username);
+    audit.record(&claims, Some(&user.params.username), &user, &result);
+
+    Ok(Json(result))
 }
 
 #[post("/chgrp", format = "json", data = "<user>")]
 fn chgrp(
     token: Token,
-    user: Json<UserGrp>,
-) -> Result<Json<Result<ChGrpMsg, UserErrors>>, Json<Value>> {
-    match token.validate() {
-        Ok(role) => match role {
-            Role::Privileged => Ok(Json(SSHUser::usermod_change_grp(
-                &user.username,
-                &user.group,
-            ))),
-            _ => Err(api_err!("Authentication Failed")),
-        },
-        Err(e) => Err(api_err!(*e)),
-    }
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    user: Json<Command<UserGrp>>,
+) -> Result<Json<Result<ChGrpMsg, UserErrors>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &user)?;
+    let backend = DryRun::new(&***backend, user.dry_run);
+
+    let result = SSHUser::usermod_change_grp(&backend, &user.params.username, &user.params.group);
+    audit.record(&claims, Some(&user.params.username), &user, &result);
+
+    Ok(Json(result))
 }
 
 #[post("/chexp", format = "json", data = "<user>")]
 fn chexp(
     token: Token,
-    user: Json<UserExpDate>,
-) -> Result<Json<Result<ChExpMsg, UserErrors>>, Json<Value>> {
-    match token.validate() {
-        Ok(role) => match role {
-            Role::Privileged => Ok(Json(SSHUser::usermod_change_exp(
-                &user.username,
-                &user.exp_date,
-            ))),
-            _ => Err(api_err!("Authentication Failed")),
-        },
-        Err(e) => Err(api_err!(*e)),
-    }
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    user: Json<Command<UserExpDate>>,
+) -> Result<Json<Result<ChExpMsg, UserErrors>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &user)?;
+    let backend = DryRun::new(&***backend, user.dry_run);
+
+    let result =
+        SSHUser::usermod_change_exp(&backend, &user.params.username, &user.params.exp_date);
+    audit.record(&claims, Some(&user.params.username), &user, &result);
+
+    Ok(Json(result))
+}
+
+#[post("/chlimits", format = "json", data = "<user>")]
+fn chlimits(
+    token: Token,
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    accounting: &State<Arc<TrafficAccounting>>,
+    limiter: &State<Arc<LoginLimiter>>,
+    user: Json<Command<UserLimits>>,
+) -> Result<Json<Result<UserQuota, UserErrors>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &user)?;
+
+    let limits = &user.params;
+    let result = if backend.get_account(&limits.username).is_none() {
+        Err(UserErrors::InvalidUserOrGroup)
+    } else if let Err(err) = sessions::check_limit(limits.max_logins) {
+        Err(err)
+    } else if user.dry_run {
+        Ok(TrafficAccounting::fresh_quota(
+            &limits.username,
+            limits.traffic_quota,
+        ))
+    } else {
+        accounting
+            .set_quota(&limits.username, limits.traffic_quota)
+            .and_then(|_| limiter.set_limit(&limits.username, limits.max_logins))
+            .and_then(|_| accounting.quota(&limits.username))
+    };
+    audit.record(&claims, Some(&limits.username), &user, &result);
+
+    Ok(Json(result))
 }
 
 #[post("/userlock", format = "json", data = "<user>")]
 fn userlock(
     token: Token,
-    user: Json<OnlyUser>,
-) -> Result<Json<Result<UserStatus, UserErrors>>, Json<Value>> {
-    match token.validate() {
-        Ok(role) => match role {
-            Role::Privileged => Ok(Json(SSHUser::usermod_lock(&user.username))),
-            _ => Err(api_err!("Authentication Failed")),
-        },
-        Err(e) => Err(api_err!(*e)),
-    }
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    user: Json<Command<OnlyUser>>,
+) -> Result<Json<Result<UserStatus, UserErrors>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &user)?;
+    let backend = DryRun::new(&***backend, user.dry_run);
+
+    let result = SSHUser::usermod_lock(&backend, &user.params.username);
+    audit.record(&claims, Some(&user.params.username), &user, &result);
+
+    Ok(Json(result))
 }
 
 #[post("/userunlock", format = "json", data = "<user>")]
 fn userunlock(
     token: Token,
-    user: Json<OnlyUser>,
-) -> Result<Json<Result<UserStatus, UserErrors>>, Json<Value>> {
-    match token.validate() {
-        Ok(role) => match role {
-            Role::Privileged => Ok(Json(SSHUser::usermod_unlock(&user.username))),
-            _ => Err(api_err!("Authentication Failed")),
-        },
-        Err(e) => Err(api_err!(*e)),
-    }
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    accounting: &State<Arc<TrafficAccounting>>,
+    user: Json<Command<OnlyUser>>,
+) -> Result<Json<Result<UserStatus, UserErrors>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &user)?;
+    let backend = DryRun::new(&***backend, user.dry_run);
+
+    let result = SSHUser::usermod_unlock(&backend, &user.params.username).and_then(|status| {
+        if !user.dry_run {
+            accounting.rearm_quota(&user.params.username)?;
+        }
+        Ok(status)
+    });
+    audit.record(&claims, Some(&user.params.username), &user, &result);
+
+    Ok(Json(result))
 }
 
-//NOTE: needs working on
 #[post("/users_usage", format = "json", data = "<lookup_params>")]
 fn users_usage(
     token: Token,
+    backend: &State<Box<dyn AccountBackend>>,
+    accounting: &State<Arc<TrafficAccounting>>,
     lookup_params: Json<UserLookupParams>,
-) -> Result<Json<Result<HashMap<String, f64>, UserErrors>>, Json<Value>> {
-    token.validate()?;
+) -> Result<Json<Result<TrafficTotals, UserErrors>>, Json<Refusal>> {
+    token.validate(Scope::UsersRead)?;
 
     if lookup_params.prefix.is_none()
         && lookup_params.group.is_none()
@@ -234,24 +505,274 @@ fn users_usage(
     }
 
     if let Some(prefix) = &lookup_params.prefix {
-        return Ok(Json(SSHUser::get_usage_by_prefix(&prefix)));
+        return Ok(Json(SSHUser::get_usage_by_prefix(
+            &***backend,
+            accounting,
+            &prefix,
+        )));
     } else {
         if let Some(group) = &lookup_params.group {
-            return Ok(Json(SSHUser::get_usage_by_group(&group)));
+            return Ok(Json(SSHUser::get_usage_by_group(
+                &***backend,
+                accounting,
+                &group,
+            )));
         } else {
             if let Some(username) = &lookup_params.username {
-                return Ok(Json(SSHUser::get_usage_by_name(&username)));
+                return Ok(Json(SSHUser::get_usage_by_name(accounting, &username)));
             } else {
-                return Ok(Json(SSHUser::get_usage_by_prefix("")));
+                return Ok(Json(SSHUser::get_usage_by_prefix(
+                    &***backend,
+                    accounting,
+                    "",
+                )));
             }
         }
     }
 }
 
-fn main() {
-    rocket::ignite()
-        .manage(config::ConfigFile::load().unwrap_or_else(|_| panic!("Couldn't load config file!")))
-        .mount("/", routes![ping])
+#[post("/useradd", format = "json", data = "<bulk>")]
+fn bulk_useradd(
+    token: Token,
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    node_config: &State<config::ConfigFile>,
+    accounting: &State<Arc<TrafficAccounting>>,
+    limiter: &State<Arc<LoginLimiter>>,
+    bulk: Json<Command<BulkUseradd>>,
+) -> Result<Json<BulkResult<v1::users::SSHUser>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &bulk)?;
+    let backend = DryRun::new(&***backend, bulk.dry_run);
+
+    bulk::check_count(bulk.params.users.len() as u64)
+        .map_err(|msg| audit.refuse(&claims, &bulk, msg))?;
+    let result = bulk::useradd(
+        &backend,
+        &node_config.passwords,
+        accounting,
+        limiter,
+        bulk.params.users.clone(),
+        bulk.params.rollback,
+    );
+    audit.record_bulk(&claims, &bulk, &result);
+
+    Ok(Json(result))
+}
+
+#[post("/auto_useradd", format = "json", data = "<bulk>")]
+fn bulk_auto_useradd(
+    token: Token,
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    node_config: &State<config::ConfigFile>,
+    accounting: &State<Arc<TrafficAccounting>>,
+    limiter: &State<Arc<LoginLimiter>>,
+    bulk: Json<Command<BulkAutoUseradd>>,
+) -> Result<Json<BulkResult<v1::users::SSHUser>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &bulk)?;
+    let backend = DryRun::new(&***backend, bulk.dry_run);
+
+    bulk::check_count(bulk.params.count).map_err(|msg| audit.refuse(&claims, &bulk, msg))?;
+    let result = bulk::auto_useradd(
+        &backend,
+        &node_config.passwords,
+        accounting,
+        limiter,
+        bulk.params.clone(),
+    );
+    audit.record_bulk(&claims, &bulk, &result);
+
+    Ok(Json(result))
+}
+
+#[post("/userdel", format = "json", data = "<bulk>")]
+fn bulk_userdel(
+    token: Token,
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    bulk: Json<Command<BulkUsers>>,
+) -> Result<Json<BulkResult<UserStatus>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &bulk)?;
+    let backend = DryRun::new(&***backend, bulk.dry_run);
+
+    let usernames = bulk::select(&backend, &bulk.params.users)
+        .map_err(|msg| audit.refuse(&claims, &bulk, msg))?;
+    let result = bulk::userdel(&backend, usernames, bulk.params.rollback);
+    audit.record_bulk(&claims, &bulk, &result);
+
+    Ok(Json(result))
+}
+
+#[post("/userlock", format = "json", data = "<bulk>")]
+fn bulk_userlock(
+    token: Token,
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    bulk: Json<Command<BulkUsers>>,
+) -> Result<Json<BulkResult<UserStatus>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &bulk)?;
+    let backend = DryRun::new(&***backend, bulk.dry_run);
+
+    let usernames = bulk::select(&backend, &bulk.params.users)
+        .map_err(|msg| audit.refuse(&claims, &bulk, msg))?;
+    let result = bulk::userlock(&backend, usernames, bulk.params.rollback);
+    audit.record_bulk(&claims, &bulk, &result);
+
+    Ok(Json(result))
+}
+
+#[post("/userunlock", format = "json", data = "<bulk>")]
+fn bulk_userunlock(
+    token: Token,
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    accounting: &State<Arc<TrafficAccounting>>,
+    bulk: Json<Command<BulkUsers>>,
+) -> Result<Json<BulkResult<UserStatus>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &bulk)?;
+    let backend = DryRun::new(&***backend, bulk.dry_run);
+
+    let usernames = bulk::select(&backend, &bulk.params.users)
+        .map_err(|msg| audit.refuse(&claims, &bulk, msg))?;
+    let result = bulk::userunlock(&backend, usernames, bulk.params.rollback);
+    if !bulk.dry_run && !result.rolled_back {
+        for item in result.results.iter().filter(|item| item.result.is_ok()) {
+            let _ = accounting.rearm_quota(&item.username);
+        }
+    }
+    audit.record_bulk(&claims, &bulk, &result);
+
+    Ok(Json(result))
+}
+
+#[post("/chexp", format = "json", data = "<bulk>")]
+fn bulk_chexp(
+    token: Token,
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    bulk: Json<Command<BulkUserExpDate>>,
+) -> Result<Json<BulkResult<ChExpMsg>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &bulk)?;
+    let backend = DryRun::new(&***backend, bulk.dry_run);
+
+    let usernames = bulk::select(&backend, &bulk.params.users)
+        .map_err(|msg| audit.refuse(&claims, &bulk, msg))?;
+    let result = bulk::chexp(
+        &backend,
+        usernames,
+        &bulk.params.exp_date,
+        bulk.params.rollback,
+    );
+    audit.record_bulk(&claims, &bulk, &result);
+
+    Ok(Json(result))
+}
+
+#[post("/chgrp", format = "json", data = "<bulk>")]
+fn bulk_chgrp(
+    token: Token,
+    audit: Audit,
+    backend: &State<Box<dyn AccountBackend>>,
+    bulk: Json<Command<BulkUserGrp>>,
+) -> Result<Json<BulkResult<ChGrpMsg>>, Json<Refusal>> {
+    let claims = audit.validate(&token, Scope::UsersWrite, &bulk)?;
+    let backend = DryRun::new(&***backend, bulk.dry_run);
+
+    let usernames = bulk::select(&backend, &bulk.params.users)
+        .map_err(|msg| audit.refuse(&claims, &bulk, msg))?;
+    let result = bulk::chgrp(
+        &backend,
+        usernames,
+        &bulk.params.group,
+        bulk.params.rollback,
+    );
+    audit.record_bulk(&claims, &bulk, &result);
+
+    Ok(Json(result))
+}
+
+#[rocket::main]
+async fn main() {
+    if let Err(e) = enrollment::enroll_if_requested().await {
+        eprintln!("Couldn't enroll in centric-api: {e}");
+        std::process::exit(1);
+    }
+
+    let node_config =
+        config::ConfigFile::load().unwrap_or_else(|_| panic!("Couldn't load config file!"));
+
+    if let Err(e) = node_config.secrets.check() {
+        eprintln!("Refusing to start with insecure secrets: {e}");
+        std::process::exit(1);
+    }
+
+    if let Err(e) = node_config.passwords.check() {
+        eprintln!("Invalid password policy: {e}");
+        std::process::exit(1);
+    }
+
+    let mut figment = rocket::Config::figment();
+    match &node_config.tls {
+        Some(tls) => {
+            if let Err(e) = tls.check() {
+                eprintln!("Invalid TLS settings: {e}");
+                std::process::exit(1);
+            }
+            figment = figment.merge(("tls", tls.rocket_config()));
+        }
+        None => eprintln!("No TLS settings, serving the API over plain HTTP"),
+    }
+
+    let accounting = Arc::new(TrafficAccounting::new(
+        node_config
+            .traffic_store_path
+            .as_deref()
+            .unwrap_or(consts::TRAFFIC_STORE_PATH),
+        node_config.account_backend(),
+    ));
+    accounting
+        .clone()
+        .spawn(Duration::from_secs(consts::ACCOUNTING_INTERVAL_SECS));
+
+    let limiter = Arc::new(LoginLimiter::new(
+        node_config
+            .login_limits_path
+            .as_deref()
+            .unwrap_or(consts::LOGIN_LIMITS_PATH),
+        node_config.account_backend(),
+        node_config.ssh_ports(),
+    ));
+    limiter
+        .clone()
+        .spawn(Duration::from_secs(consts::LOGIN_LIMIT_INTERVAL_SECS));
+
+    let sampler = Arc::new(NetSampler::new());
+    sampler
+        .clone()
+        .spawn(Duration::from_secs(consts::NET_SAMPLE_INTERVAL_SECS));
+
+    let cpu_sampler = Arc::new(CpuSampler::new());
+    cpu_sampler
+        .clone()
+        .spawn(Duration::from_secs(consts::CPU_SAMPLE_INTERVAL_SECS));
+
+    let audit_log = AuditLog::new(
+        node_config
+            .audit_log_path
+            .as_deref()
+            .unwrap_or(consts::AUDIT_LOG_PATH),
+    );
+
+    let _ = rocket::custom(figment)
+        .manage(node_config.account_backend())
+        .manage(audit_log)
+        .attach(audit::fairing())
+        .manage(accounting)
+        .manage(limiter)
+        .manage(sampler)
+        .manage(cpu_sampler)
+        .manage(node_config)
+        .mount("/", routes![ping, prometheus_metrics])
         .mount("/api", routes![node_info])
         .mount(
             "/api/stats",
@@ -262,9 +783,19 @@ fn main() {
                 hw_stats,
                 list_users,
                 user_expiry,
+                user_quota,
                 users_usage,
+                legacy_passwords,
+                login_violations,
+                list_sessions,
+                audit_log,
             ],
         )
+        .mount(
+            "/api/v2/stats",
+            routes![net_stats_v2, hw_stats_v2, system_stats_v2],
+        )
+        .register("/api/cmd", catchers![audit::refused])
         .mount(
             "/api/cmd",
             routes![
@@ -272,12 +803,27 @@ fn main() {
                 useradd,
                 auto_useradd,
                 passwd,
+                reset_password,
                 chgrp,
                 chexp,
+                chlimits,
                 userlock,
-                userunlock
+                userunlock,
+                kick
+            ],
+        )
+        .mount(
+            "/api/cmd/bulk",
+            routes![
+                bulk_useradd,
+                bulk_auto_useradd,
+                bulk_userdel,
+                bulk_userlock,
+                bulk_userunlock,
+                bulk_chexp,
+                bulk_chgrp
             ],
         )
-        .launch();
+        .launch()
+        .await;
 }
-
@@ -0,0 +1,217 @@
+use crate::accounting::TrafficAccounting;
+use crate::backend::AccountBackend;
+use crate::consts;
+use crate::sessions;
+use crate::stats;
+use api_types::v1::node::NodeInfo;
+use std::fmt::{Display, Write};
+
+/// Prometheus metric families rendered in the text exposition format, every sample being
+/// labelled with the node's name and location.
+pub struct Exposition {
+    out: String,
+    labels: Vec<(&'static str, String)>,
+}
+
+impl Exposition {
+    /// Creates a new `Exposition` labelling samples after the given node.
+    pub fn new(node_info: &NodeInfo) -> Exposition {
+        Exposition {
+            out: String::new(),
+            labels: vec![
+                ("node", node_info.name.clone()),
+                ("location", node_info.location.clone()),
+            ],
+        }
+    }
+
+    /// Starts a metric family, `kind` being `gauge` or `counter`.
+    pub fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {name} {help}");
+        let _ = writeln!(self.out, "# TYPE {name} {kind}");
+    }
+
+    /// Adds a sample of the current family, with labels besides the node's ones.
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        let labels: Vec<String> = self
+            .labels
+            .iter()
+            .map(|(key, value)| (*key, value.as_str()))
+            .chain(labels.iter().copied())
+            .map(|(key, value)| format!("{key}=\"{}\"", escape(value)))
+            .collect();
+
+        let _ = writeln!(self.out, "{name}{{{}}} {value}", labels.join(","));
+    }
+
+    /// Returns the rendered families.
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+/// Escapes a label value as required by the text exposition format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Renders the node's statistics, accounts, sessions and per-user traffic.
+pub fn render(
+    node_info: &NodeInfo,
+    backend: &dyn AccountBackend,
+    accounting: &TrafficAccounting,
+    ssh_ports: &[u16],
+) -> String {
+    let mut exposition = Exposition::new(node_info);
+
+    let hw_usage = stats::hw_usage();
+
+    exposition.family("sshmgmt_load_average", "gauge", "Load average of the node.");
+    let (one, five, fifteen) = hw_usage.cpu_load;
+    for (period, load) in [("1m", one), ("5m", five), ("15m", fifteen)] {
+        exposition.sample("sshmgmt_load_average", &[("period", period)], load);
+    }
+
+    let sizes = [
+        (
+            "sshmgmt_memory_used_bytes",
+            "Memory in use.",
+            hw_usage.memory_usage.used,
+        ),
+        (
+            "sshmgmt_memory_total_bytes",
+            "Total memory.",
+            hw_usage.memory_usage.total,
+        ),
+        (
+            "sshmgmt_swap_used_bytes",
+            "Swap in use.",
+            hw_usage.swap_usage.used,
+        ),
+        (
+            "sshmgmt_swap_total_bytes",
+            "Total swap.",
+            hw_usage.swap_usage.total,
+        ),
+    ];
+    for (name, help, value) in sizes {
+        exposition.family(name, "gauge", help);
+        exposition.sample(name, &[], value);
+    }
+
+    let disk = &hw_usage.disk_info;
+    let disk_sizes = [
+        ("sshmgmt_disk_used_bytes", "Disk space in use.", disk.used),
+        ("sshmgmt_disk_total_bytes", "Total disk space.", disk.total),
+    ];
+    for (name, help, value) in disk_sizes {
+        exposition.family(name, "gauge", help);
+        exposition.sample(name, &[("mount_point", &disk.mount_point)], value);
+    }
+
+    exposition.family("sshmgmt_uptime_seconds", "gauge", "Uptime of the node.");
+    exposition.sample("sshmgmt_uptime_seconds", &[], hw_usage.uptime.seconds);
+
+    let net_usage = stats::net_usage();
+
+    exposition.family(
+        "sshmgmt_network_receive_bytes_total",
+        "counter",
+        "Bytes received by an interface.",
+    );
+    for usage in &net_usage {
+        exposition.sample(
+            "sshmgmt_network_receive_bytes_total",
+            &[("interface", &usage.interface)],
+            usage.rx_bytes,
+        );
+    }
+
+    exposition.family(
+        "sshmgmt_network_transmit_bytes_total",
+        "counter",
+        "Bytes sent by an interface.",
+    );
+    for usage in &net_usage {
+        exposition.sample(
+            "sshmgmt_network_transmit_bytes_total",
+            &[("interface", &usage.interface)],
+            usage.tx_bytes,
+        );
+    }
+
+    let usernames: Vec<String> = backend
+        .all_accounts()
+        .into_iter()
+        .filter(|account| account.uid >= consts::UID_MIN)
+        .map(|account| account.username)
+        .collect();
+    let locked = usernames
+        .iter()
+        .filter(|username| {
+            backend
+                .get_shadow(username)
+                .map_or(false, |shadow| shadow.locked)
+        })
+        .count();
+
+    exposition.family("sshmgmt_users", "gauge", "Accounts managed on the node.");
+    exposition.sample("sshmgmt_users", &[], usernames.len());
+
+    exposition.family(
+        "sshmgmt_users_locked",
+        "gauge",
+        "Managed accounts that are locked.",
+    );
+    exposition.sample("sshmgmt_users_locked", &[], locked);
+
+    exposition.family(
+        "sshmgmt_sessions",
+        "gauge",
+        "Live SSH sessions of managed accounts.",
+    );
+    exposition.sample(
+        "sshmgmt_sessions",
+        &[],
+        sessions::list_sessions(backend, ssh_ports).len(),
+    );
+
+    // Per-user traffic is only known once the accounting polled the counters
+    if let Ok(totals) = accounting.totals(&usernames) {
+        if totals.timestamp > 0 {
+            let mut users: Vec<_> = totals.users.into_iter().collect();
+            users.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            exposition.family(
+                "sshmgmt_user_receive_bytes_total",
+                "counter",
+                "Bytes received by an account.",
+            );
+            for (username, traffic) in &users {
+                exposition.sample(
+                    "sshmgmt_user_receive_bytes_total",
+                    &[("username", username)],
+                    traffic.rx,
+                );
+            }
+
+            exposition.family(
+                "sshmgmt_user_transmit_bytes_total",
+                "counter",
+                "Bytes sent by an account.",
+            );
+            for (username, traffic) in &users {
+                exposition.sample(
+                    "sshmgmt_user_transmit_bytes_total",
+                    &[("username", username)],
+                    traffic.tx,
+                );
+            }
+        }
+    }
+
+    exposition.finish()
+}
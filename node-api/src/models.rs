@@ -1,84 +1,30 @@
 use rocket::http::Status;
+use rocket::outcome::Outcome;
 use rocket::request::{self, FromRequest, Request};
-use rocket::Outcome;
-use serde::{Deserialize, Serialize};
 
 use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use sha2::{Digest, Sha256};
 
-use rocket_contrib::json::Json;
-use serde_json::{json, Value};
+use rocket::serde::json::Json;
 
-use crate::consts::JWT_SECRET;
+use crate::config::ConfigFile;
 
 // TODO: Generate and verify Unix passwords with structs
 
-// NOTE: Data models used in serializing request data should become a separated library in future
-// because they will be needed in the telegram-bot as well
+pub use api_types::v1::auth::{Claims, Scope};
+pub use api_types::v1::requests::*;
+pub use api_types::v1::Refusal;
 
-/// Struct representing parameters for user lookup.
-#[derive(Deserialize, Serialize)]
-pub struct UserLookupParams {
-    pub username: Option<String>,
-    pub prefix: Option<String>,
-    pub group: Option<String>,
-}
-
-/// Struct representing user expiration date.
-#[derive(Deserialize, Serialize)]
-pub struct UserExpDate {
-    pub username: String,
-    pub exp_date: String,
-}
-
-/// Struct representing user password.
-#[derive(Deserialize, Serialize)]
-pub struct UserPasswd {
-    pub username: String,
-    pub password: String,
-}
-
-/// Struct representing user group.
-#[derive(Deserialize, Serialize)]
-pub struct UserGrp {
-    pub username: String,
-    pub group: String,
-}
-
-/// Struct representing only username.
-#[derive(Deserialize, Serialize)]
-pub struct OnlyUser {
-    pub username: String,
-}
-
-/// Struct representing auto-generated SSH user.
-#[derive(Deserialize, Serialize)]
-pub struct AutoSSHUser {
-    pub prefix: String,
-    pub users_count: u64,
-    pub exp_date: String,
-    pub group: String,
-}
-
-/// Struct representing input for SSH user.
-#[derive(Deserialize, Serialize)]
-pub struct InputSSHUser {
-    pub username: String,
-    pub password: String,
-    pub exp_date: String,
-    pub group: String,
-    pub shell: Option<String>,
-}
-
-/// Struct representing JWT claims.
-#[derive(Deserialize, Serialize)]
-pub struct Claims {
-    pub role: String,
-    pub exp: u64,
-}
-
-/// Struct representing an API token.
+/// Struct representing an API token along with the keys it can be verified with.
 #[derive(Debug)]
-pub struct Token(String);
+pub struct Token {
+    token: String,
+    jwt_secrets: Vec<String>,
+    /// Audience the token must have been minted for, i.e. this node's ID.
+    audience: String,
+    /// Static token `/metrics` can be scraped with instead of an API token.
+    metrics_token: Option<String>,
+}
 
 /// Enum representing possible API token errors.
 #[derive(Debug)]
@@ -87,13 +33,28 @@ pub enum ApiTokenError {
     Invalid,
 }
 
-impl<'a, 'r> FromRequest<'a, 'r> for Token {
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Token {
     type Error = ApiTokenError;
 
-    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let (jwt_secrets, audience, metrics_token) = match request.rocket().state::<ConfigFile>() {
+            Some(node_config) => (
+                node_config.secrets.jwt_secrets(),
+                node_config.node_id.clone(),
+                node_config.secrets.metrics_token.clone(),
+            ),
+            None => return Outcome::Failure((Status::InternalServerError, ApiTokenError::Invalid)),
+        };
+
         let token = request.headers().get_one("Authorization");
         match token {
-            Some(token) => Outcome::Success(Token(token.replace("Bearer ", "").to_string())),
+            Some(token) => Outcome::Success(Token {
+                token: token.replace("Bearer ", "").to_string(),
+                jwt_secrets,
+                audience,
+                metrics_token,
+            }),
             None => Outcome::Failure((Status::Unauthorized, ApiTokenError::Missing)),
         }
     }
@@ -101,42 +62,51 @@ impl<'a, 'r> FromRequest<'a, 'r> for Token {
 
 impl Token {
     pub fn to_string(&self) -> String {
-        self.0.clone()
+        self.token.clone()
     }
 
-    pub fn validate(&self) -> Result<Role, Json<Value>> {
-        let decoded_token = match decode::<Claims>(
-            &self.0,
-            &DecodingKey::from_secret(JWT_SECRET),
-            &Validation::new(Algorithm::HS256),
-        ) {
-            Ok(token) => token,
-            Err(e) => return Err(Json(json!({"Err": e.to_string()}))),
-        };
+    /// Validates the token against the current key, then the previous one if any, and checks
+    /// that it was minted for this node, hasn't expired and grants the scope.
+    pub fn validate(&self, scope: Scope) -> Result<Claims, Json<Refusal>> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_audience(&[&self.audience]);
+        validation.set_required_spec_claims(&["exp", "aud"]);
+
+        let mut last_err = None;
+
+        for secret in &self.jwt_secrets {
+            match decode::<Claims>(
+                &self.token,
+                &DecodingKey::from_secret(secret.as_bytes()),
+                &validation,
+            ) {
+                Ok(token) if token.claims.has_scope(scope) => return Ok(token.claims),
+                Ok(_) => {
+                    return Err(Json(Refusal::new(format!(
+                        "Missing scope {}",
+                        scope.as_str()
+                    ))))
+                }
+                Err(e) => last_err = Some(e.to_string()),
+            }
+        }
 
-        Ok(Role::from_str(&decoded_token.claims.role))
+        Err(Json(Refusal::new(
+            last_err.unwrap_or_else(|| "Invalid token".to_string()),
+        )))
     }
-}
 
-/// Enum representing user roles.
-pub enum Role {
-    Privileged,
-    Normal,
-}
+    /// Validates the token of a metrics scrape: the static `metrics_token` if one is set, or
+    /// an API token granting `stats:read`.
+    pub fn validate_metrics(&self) -> Result<(), Json<Refusal>> {
+        if let Some(metrics_token) = &self.metrics_token {
+            let digest = |token: &str| Sha256::digest(token.as_bytes());
 
-impl Role {
-    pub fn from_str(role: &str) -> Self {
-        match role.to_lowercase().as_str() {
-            "privileged" => Role::Privileged,
-            _ => Role::Normal,
+            if digest(&self.token) == digest(metrics_token) {
+                return Ok(());
+            }
         }
-    }
 
-    pub fn to_string(&self) -> String {
-        match &self {
-            Role::Privileged => String::from("privileged"),
-            _ => String::from("normal"),
-        }
+        self.validate(Scope::StatsRead).map(|_| ())
     }
 }
-
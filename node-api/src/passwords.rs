@@ -0,0 +1,171 @@
+use crate::backend::AccountBackend;
+use crate::consts;
+use crate::users::models::UserErrors;
+use pwhash::sha512_crypt;
+use rand::rngs::OsRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Characters of crypt(3) salts.
+const SALT_ALPHABET: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+/// Length of generated SHA-512 crypt salts (the maximum the scheme uses).
+const SALT_LEN: usize = 16;
+
+/// Enum representing the algorithms account passwords can be hashed with.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HashMethod {
+    /// `$6$`, supported everywhere.
+    Sha512Crypt,
+    /// `$y$`, hashed with `mkpasswd` and only usable where libxcrypt supports it.
+    Yescrypt,
+}
+
+/// Struct representing how account passwords are generated and hashed.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    /// Length of generated passwords.
+    pub length: usize,
+    /// Characters generated passwords are drawn from.
+    pub alphabet: String,
+    pub hash_method: HashMethod,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            length: consts::PASSWD_LEN,
+            alphabet: consts::PASSWD_ALPHABET.to_string(),
+            hash_method: HashMethod::Sha512Crypt,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Generates a random password from the OS CSPRNG.
+    pub fn generate(&self) -> String {
+        let alphabet: Vec<char> = self.alphabet.chars().collect();
+
+        (0..self.length)
+            .map(|_| alphabet[OsRng.gen_range(0..alphabet.len())])
+            .collect()
+    }
+
+    /// Hashes a password with the configured method and a random salt.
+    pub fn hash(&self, password: &str) -> Result<String, UserErrors> {
+        match self.hash_method {
+            HashMethod::Sha512Crypt => {
+                let salt: String = (0..SALT_LEN)
+                    .map(|_| SALT_ALPHABET[OsRng.gen_range(0..SALT_ALPHABET.len())] as char)
+                    .collect();
+
+                sha512_crypt::hash_with(format!("$6${salt}").as_str(), password)
+                    .map_err(|_| UserErrors::InvalidPasswordHash)
+            }
+            HashMethod::Yescrypt => Self::mkpasswd("yescrypt", password),
+        }
+    }
+
+    /// Checks that passwords can be generated and hashed with this policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message describing the problem.
+    pub fn check(&self) -> Result<(), String> {
+        if self.length < consts::PASSWD_MIN_LEN {
+            return Err(format!(
+                "password length should be at least {}",
+                consts::PASSWD_MIN_LEN
+            ));
+        }
+        if self.alphabet.chars().count() < 10 {
+            return Err("password alphabet should have at least 10 characters".to_string());
+        }
+        if self.alphabet.contains(':') || self.alphabet.chars().any(char::is_whitespace) {
+            return Err("password alphabet can't contain ':' or whitespace".to_string());
+        }
+
+        self.hash(&self.generate())
+            .map(|_| ())
+            .map_err(|e| format!("couldn't hash with {:?}: {e:?}", self.hash_method))
+    }
+
+    /// Hashes a password with `mkpasswd`, passing it on stdin so it doesn't show up in `ps`.
+    fn mkpasswd(method: &str, password: &str) -> Result<String, UserErrors> {
+        let mut child = Command::new("mkpasswd")
+            .arg("--method")
+            .arg(method)
+            .arg("--stdin")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|_| UserErrors::CommandNotFound)?;
+
+        child
+            .stdin
+            .take()
+            .ok_or(UserErrors::UnexpectedError)?
+            .write_all(password.as_bytes())
+            .map_err(|_| UserErrors::UnexpectedError)?;
+
+        let output = child
+            .wait_with_output()
+            .map_err(|_| UserErrors::UnexpectedError)?;
+
+        let hash = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !output.status.success() || !hash.starts_with('$') {
+            return Err(UserErrors::InvalidPasswordHash);
+        }
+
+        Ok(hash)
+    }
+}
+
+/// Returns the name of the method a crypt(3) hash was created with.
+pub fn hash_method_name(hash: &str) -> Option<&'static str> {
+    let hash = hash.trim_start_matches('!');
+
+    match hash.split('$').nth(1)? {
+        "1" => Some("md5-crypt"),
+        "5" => Some("sha256-crypt"),
+        "6" => Some("sha512-crypt"),
+        "y" => Some("yescrypt"),
+        "gy" => Some("gost-yescrypt"),
+        "2a" | "2b" | "2y" => Some("bcrypt"),
+        _ => None,
+    }
+}
+
+/// Returns the salt of a crypt(3) hash.
+pub fn hash_salt(hash: &str) -> Option<&str> {
+    let fields: Vec<&str> = hash.trim_start_matches('!').split('$').collect();
+
+    // "$id$[params$]salt$hash" splits into ["", id, .., salt, hash]
+    match fields.len() {
+        len if len >= 4 => Some(fields[len - 2]),
+        _ => None,
+    }
+}
+
+/// Lists the accounts whose password hash still uses one of the fixed legacy salts, i.e. that
+/// were created or last changed with the old deterministic scheme and should be reset.
+pub fn legacy_accounts(backend: &dyn AccountBackend, legacy_salts: &[&str]) -> Vec<String> {
+    backend
+        .all_accounts()
+        .into_iter()
+        .filter(|account| account.uid >= consts::UID_MIN)
+        .filter(|account| {
+            backend
+                .get_shadow(&account.username)
+                .ok()
+                .and_then(|entry| entry.password_hash)
+                .and_then(|hash| hash_salt(&hash).map(|salt| legacy_salts.contains(&salt)))
+                .unwrap_or(false)
+        })
+        .map(|account| account.username)
+        .collect()
+}
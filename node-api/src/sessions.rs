@@ -0,0 +1,423 @@
+use crate::backend::AccountBackend;
+use crate::consts;
+use crate::users::models::UserErrors;
+pub use api_types::v1::sessions::{KickedSessions, LoginViolation, Session};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Clock ticks per second used by `/proc/<pid>/stat` (`CLK_TCK` is 100 on every Linux we run on).
+const CLOCK_TICKS: u64 = 100;
+/// Number of login limit violations kept in memory.
+const MAX_VIOLATIONS: usize = 500;
+
+/// Lists the live SSH sessions of managed users by scanning `/proc`.
+///
+/// Every session has an `sshd` (or `sshd-session` on OpenSSH >= 9.8) process running as the
+/// logged in user, its client address is taken from the socket connected to one of `ssh_ports`.
+pub fn list_sessions(backend: &dyn AccountBackend, ssh_ports: &[u16]) -> Vec<Session> {
+    let usernames: HashMap<u32, String> = backend
+        .all_accounts()
+        .into_iter()
+        .filter(|account| account.uid >= consts::UID_MIN)
+        .map(|account| (account.uid, account.username))
+        .collect();
+
+    let connections = read_tcp_connections();
+    let boot_time = read_boot_time();
+
+    let mut sessions: Vec<Session> = Vec::new();
+
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return sessions;
+    };
+
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|pid| pid.parse::<u32>().ok())
+        else {
+            continue;
+        };
+
+        let comm = fs::read_to_string(format!("/proc/{pid}/comm")).unwrap_or_default();
+        if !matches!(comm.trim(), "sshd" | "sshd-session") {
+            continue;
+        }
+
+        let Some(username) = read_uid(pid).and_then(|uid| usernames.get(&uid)) else {
+            continue;
+        };
+
+        let source_ip = socket_inodes(pid).iter().find_map(|inode| {
+            connections
+                .get(inode)
+                .filter(|(local_port, _)| ssh_ports.contains(local_port))
+                .map(|(_, remote)| remote.to_string())
+        });
+
+        let started_at = boot_time.and_then(|boot_time| {
+            read_start_ticks(pid).map(|ticks| boot_time + ticks / CLOCK_TICKS)
+        });
+
+        let (io_read, io_written) = read_io(pid);
+
+        sessions.push(Session {
+            username: username.to_string(),
+            pid,
+            source_ip,
+            started_at,
+            io_read,
+            io_written,
+        });
+    }
+
+    sessions
+}
+
+/// Reads the real uid of a process from `/proc/<pid>/status`.
+fn read_uid(pid: u32) -> Option<u32> {
+    fs::read_to_string(format!("/proc/{pid}/status"))
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Reads the start time of a process, in clock ticks after boot, from `/proc/<pid>/stat`.
+fn read_start_ticks(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+
+    // Fields after the parenthesised command name, starttime is field 22 of the whole line.
+    stat.rsplit_once(')')?
+        .1
+        .split_whitespace()
+        .nth(19)?
+        .parse()
+        .ok()
+}
+
+/// Reads the `rchar`/`wchar` counters of a process from `/proc/<pid>/io`.
+fn read_io(pid: u32) -> (Option<u64>, Option<u64>) {
+    let io = fs::read_to_string(format!("/proc/{pid}/io")).unwrap_or_default();
+    let counter = |name: &str| {
+        io.lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.trim().parse().ok())
+    };
+
+    (counter("rchar:"), counter("wchar:"))
+}
+
+/// Reads the boot time (unix timestamp) from `/proc/stat`.
+fn read_boot_time() -> Option<u64> {
+    fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("btime"))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Lists the socket inodes held open by a process.
+fn socket_inodes(pid: u32) -> Vec<u64> {
+    let Ok(fds) = fs::read_dir(format!("/proc/{pid}/fd")) else {
+        return Vec::new();
+    };
+
+    fds.flatten()
+        .filter_map(|fd| fs::read_link(fd.path()).ok())
+        .filter_map(|link| {
+            link.to_str()?
+                .strip_prefix("socket:[")?
+                .strip_suffix(']')?
+                .parse()
+                .ok()
+        })
+        .collect()
+}
+
+/// Reads established TCP connections from `/proc/net/tcp{,6}`, keyed by socket inode,
+/// as `(local port, remote address)`.
+fn read_tcp_connections() -> HashMap<u64, (u16, String)> {
+    let mut connections: HashMap<u64, (u16, String)> = HashMap::new();
+
+    for path in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        let content = fs::read_to_string(path).unwrap_or_default();
+
+        for line in content.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // State 01 is TCP_ESTABLISHED
+            if fields.len() < 10 || fields[3] != "01" {
+                continue;
+            }
+
+            let local_port = fields[1]
+                .split(':')
+                .nth(1)
+                .and_then(|port| u16::from_str_radix(port, 16).ok());
+            let remote_ip = fields[2].split(':').next().and_then(parse_hex_ip);
+            let inode = fields[9].parse::<u64>().ok();
+
+            if let (Some(local_port), Some(remote_ip), Some(inode)) = (local_port, remote_ip, inode)
+            {
+                connections.insert(inode, (local_port, remote_ip));
+            }
+        }
+    }
+
+    connections
+}
+
+/// Parses an address as printed in `/proc/net/tcp{,6}` (32-bit words in host byte order).
+fn parse_hex_ip(hex: &str) -> Option<String> {
+    let mut octets: Vec<u8> = Vec::new();
+    for i in 0..hex.len() / 8 {
+        let word = u32::from_str_radix(hex.get(i * 8..i * 8 + 8)?, 16).ok()?;
+        octets.extend_from_slice(&word.to_ne_bytes());
+    }
+
+    match octets.len() {
+        4 => Some(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]).to_string()),
+        16 => {
+            let octets: [u8; 16] = octets.try_into().ok()?;
+            let ip = Ipv6Addr::from(octets);
+            Some(
+                ip.to_ipv4_mapped()
+                    .map_or(ip.to_string(), |ip| ip.to_string()),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Terminates a session process.
+pub fn kill_session(pid: u32) -> Result<(), UserErrors> {
+    let status = Command::new("kill")
+        .arg("-TERM")
+        .arg(pid.to_string())
+        .status()
+        .map_err(|_| UserErrors::CommandNotFound)?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(UserErrors::PermissionDenied)
+    }
+}
+
+/// Terminates every session of `username`, or the single session `pid`.
+///
+/// Only pids belonging to listed sessions are killed, so the endpoint can't be used to
+/// signal arbitrary processes. On a dry run backend, the sessions are listed as killed
+/// without being signalled, on a fake root backend nothing is killed.
+pub fn kick(
+    backend: &dyn AccountBackend,
+    ssh_ports: &[u16],
+    username: Option<&str>,
+    pid: Option<u32>,
+) -> Result<KickedSessions, UserErrors> {
+    // A fake root's accounts share their uids with the live system's, their sessions can't be
+    // told apart from real users' ones.
+    if backend.fake_root() && !backend.dry_run() {
+        return Err(UserErrors::PermissionDenied);
+    }
+
+    let sessions: Vec<Session> = list_sessions(backend, ssh_ports)
+        .into_iter()
+        .filter(|session| username.map_or(true, |username| session.username == username))
+        .filter(|session| pid.map_or(true, |pid| session.pid == pid))
+        .collect();
+
+    if sessions.is_empty() {
+        return Err(UserErrors::SessionNotFound);
+    }
+
+    let mut kicked = KickedSessions {
+        killed_pids: Vec::new(),
+        failed_pids: Vec::new(),
+    };
+
+    for session in sessions {
+        if backend.dry_run() {
+            kicked.killed_pids.push(session.pid);
+            continue;
+        }
+
+        match kill_session(session.pid) {
+            Ok(_) => kicked.killed_pids.push(session.pid),
+            Err(_) => kicked.failed_pids.push(session.pid),
+        }
+    }
+
+    Ok(kicked)
+}
+
+/// Checks that a login limit allows at least one session.
+pub fn check_limit(max_logins: Option<u32>) -> Result<(), UserErrors> {
+    match max_logins {
+        Some(0) => Err(UserErrors::InvalidLoginLimit),
+        _ => Ok(()),
+    }
+}
+
+/// Enforces per-user concurrent login limits by killing the newest sessions over the limit.
+///
+/// Nothing is enforced on a fake root backend, whose uids would match the live system's sessions.
+/// Limits are persisted to a small JSON store, violations are kept in memory for reporting.
+pub struct LoginLimiter {
+    store_path: PathBuf,
+    backend: Box<dyn AccountBackend>,
+    ssh_ports: Vec<u16>,
+    limits: Mutex<HashMap<String, u32>>,
+    violations: Mutex<VecDeque<LoginViolation>>,
+}
+
+impl LoginLimiter {
+    /// Creates a new `LoginLimiter`, loading previous limits from `store_path` if present.
+    pub fn new<P: Into<PathBuf>>(
+        store_path: P,
+        backend: Box<dyn AccountBackend>,
+        ssh_ports: Vec<u16>,
+    ) -> LoginLimiter {
+        let store_path = store_path.into();
+        let mut limits: HashMap<String, u32> = fs::read_to_string(&store_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        limits.retain(|_, max_logins| check_limit(Some(*max_logins)).is_ok());
+
+        LoginLimiter {
+            store_path,
+            backend,
+            ssh_ports,
+            limits: Mutex::new(limits),
+            violations: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Spawns the background thread checking the sessions every `interval`.
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> thread::JoinHandle<()> {
+        thread::spawn(move || loop {
+            if let Err(e) = self.enforce() {
+                eprintln!("login limit enforcement failed: {e:?}");
+            }
+            thread::sleep(interval);
+        })
+    }
+
+    /// Kills the newest sessions of every user having more sessions than their limit.
+    pub fn enforce(&self) -> Result<(), UserErrors> {
+        if self.backend.fake_root() {
+            return Ok(());
+        }
+
+        let limits = self
+            .limits
+            .lock()
+            .map_err(|_| UserErrors::UnexpectedError)?
+            .clone();
+
+        let mut sessions_by_user: HashMap<String, Vec<Session>> = HashMap::new();
+        for session in list_sessions(&*self.backend, &self.ssh_ports) {
+            sessions_by_user
+                .entry(session.username.to_string())
+                .or_default()
+                .push(session);
+        }
+
+        for (username, mut sessions) in sessions_by_user {
+            let Some(max_logins) = limits.get(&username).copied() else {
+                continue;
+            };
+            if sessions.len() <= max_logins as usize {
+                continue;
+            }
+
+            // Oldest sessions are kept, the ones that pushed the user over the limit go.
+            sessions.sort_by_key(|session| (session.started_at, session.pid));
+
+            let killed_pids = sessions[max_logins as usize..]
+                .iter()
+                .filter(|session| kill_session(session.pid).is_ok())
+                .map(|session| session.pid)
+                .collect();
+
+            self.record(LoginViolation {
+                username,
+                max_logins,
+                sessions: sessions.len(),
+                killed_pids,
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |now| now.as_secs()),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets (or removes) the concurrent login limit of a user, refusing a limit of zero.
+    pub fn set_limit(&self, username: &str, max_logins: Option<u32>) -> Result<(), UserErrors> {
+        check_limit(max_logins)?;
+
+        let mut limits = self
+            .limits
+            .lock()
+            .map_err(|_| UserErrors::UnexpectedError)?;
+
+        match max_logins {
+            Some(max_logins) => limits.insert(username.to_string(), max_logins),
+            None => limits.remove(username),
+        };
+
+        if let Some(parent) = self.store_path.parent() {
+            fs::create_dir_all(parent).map_err(|_| UserErrors::PermissionDenied)?;
+        }
+
+        let content = serde_json::to_string(&*limits).map_err(|_| UserErrors::UnexpectedError)?;
+        let tmp_path = self.store_path.with_extension("tmp");
+
+        fs::write(&tmp_path, content).map_err(|_| UserErrors::PermissionDenied)?;
+        fs::rename(&tmp_path, &self.store_path).map_err(|_| UserErrors::PermissionDenied)
+    }
+
+    /// Returns the recorded violations, newest last, optionally only those of one user.
+    pub fn violations(&self, username: Option<&str>) -> Result<Vec<LoginViolation>, UserErrors> {
+        let violations = self
+            .violations
+            .lock()
+            .map_err(|_| UserErrors::UnexpectedError)?;
+
+        Ok(violations
+            .iter()
+            .filter(|violation| username.map_or(true, |username| violation.username == username))
+            .cloned()
+            .collect())
+    }
+
+    /// Records a violation, dropping the oldest ones past `MAX_VIOLATIONS`.
+    fn record(&self, violation: LoginViolation) -> Result<(), UserErrors> {
+        let mut violations = self
+            .violations
+            .lock()
+            .map_err(|_| UserErrors::UnexpectedError)?;
+
+        violations.push_back(violation);
+        while violations.len() > MAX_VIOLATIONS {
+            violations.pop_front();
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use crate::users::models::UserErrors;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use time::{macros::format_description, Date, Duration, OffsetDateTime};
+
+/// Struct representing one record of a `shadow(5)` file.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ShadowEntry {
+    pub username: String,
+    /// Password hash, never serialized.
+    #[serde(skip)]
+    pub password_hash: Option<String>,
+    /// Day of the last password change, in days since the unix epoch.
+    pub last_change: Option<i64>,
+    pub min_age: Option<i64>,
+    pub max_age: Option<i64>,
+    pub warn_period: Option<i64>,
+    pub inactivity: Option<i64>,
+    /// Day the account expires, in days since the unix epoch.
+    pub expiry: Option<i64>,
+    pub locked: bool,
+}
+
+impl ShadowEntry {
+    /// Parses a single `shadow` line, returns `None` for malformed lines.
+    pub fn parse(line: &str) -> Option<ShadowEntry> {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() < 8 || fields[0].is_empty() {
+            return None;
+        }
+
+        let number = |field: &str| field.trim().parse::<i64>().ok();
+
+        Some(ShadowEntry {
+            username: fields[0].to_string(),
+            password_hash: Some(fields[1].to_string()).filter(|hash| hash.contains('$')),
+            last_change: number(fields[2]),
+            min_age: number(fields[3]),
+            max_age: number(fields[4]),
+            warn_period: number(fields[5]),
+            inactivity: number(fields[6]),
+            expiry: number(fields[7]),
+            locked: fields[1].starts_with('!'),
+        })
+    }
+
+    /// Returns the expiry date as `YYYY-MM-DD` or `never`, expiries out of the date range
+    /// being returned as the raw number of days.
+    pub fn exp_date(&self) -> String {
+        match self.expiry {
+            Some(days) => days_to_date(days).unwrap_or_else(|| days.to_string()),
+            None => "never".to_string(),
+        }
+    }
+
+    /// Returns the date of the last password change as `YYYY-MM-DD`.
+    pub fn last_change_date(&self) -> Option<String> {
+        self.last_change
+            .filter(|days| *days > 0)
+            .and_then(days_to_date)
+    }
+}
+
+/// Reads every record of the shadow file at `path`.
+///
+/// # Errors
+///
+/// Returns `ShadowNotFound` if the file doesn't exist and `PermissionDenied` if it can't be
+/// read.
+pub fn read_shadow<P: AsRef<Path>>(path: P) -> Result<Vec<ShadowEntry>, UserErrors> {
+    let content = fs::read_to_string(path).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => UserErrors::ShadowNotFound,
+        _ => UserErrors::PermissionDenied,
+    })?;
+
+    Ok(content.lines().filter_map(ShadowEntry::parse).collect())
+}
+
+/// Reads the shadow record of a single user from the shadow file at `path`.
+pub fn get_entry<P: AsRef<Path>>(path: P, username: &str) -> Result<ShadowEntry, UserErrors> {
+    read_shadow(path)?
+        .into_iter()
+        .find(|entry| entry.username == username)
+        .ok_or(UserErrors::InvalidUserOrGroup)
+}
+
+/// Converts a `YYYY-MM-DD` date into days since the unix epoch.
+pub fn date_to_days(date: &str) -> Result<i64, UserErrors> {
+    let format = format_description!("[year]-[month]-[day]");
+    let date = Date::parse(date, &format).map_err(|_| UserErrors::InvalidExpDate)?;
+
+    Ok((date - OffsetDateTime::UNIX_EPOCH.date()).whole_days())
+}
+
+/// Parses an expiry date the way shadow-utils does: `YYYY-MM-DD` or days since the unix epoch,
+/// an empty value or `-1` meaning no expiry.
+pub fn parse_exp_date(exp_date: &str) -> Result<Option<i64>, UserErrors> {
+    match exp_date {
+        "" | "-1" => Ok(None),
+        _ => match exp_date.parse::<i64>() {
+            Ok(days) if days >= 0 => Ok(Some(days)),
+            Ok(_) => Err(UserErrors::InvalidExpDate),
+            Err(_) => date_to_days(exp_date).map(Some),
+        },
+    }
+}
+
+/// Converts days since the unix epoch into a `YYYY-MM-DD` date, returns `None` for days out
+/// of the supported date range.
+pub fn days_to_date(days: i64) -> Option<String> {
+    let format = format_description!("[year]-[month]-[day]");
+    let date = OffsetDateTime::UNIX_EPOCH
+        .date()
+        .checked_add(Duration::seconds(days.checked_mul(86_400)?))?;
+
+    date.format(&format).ok()
+}
+
+/// Returns today's date in days since the unix epoch.
+pub fn today() -> i64 {
+    (OffsetDateTime::now_utc().date() - OffsetDateTime::UNIX_EPOCH.date()).whole_days()
+}
@@ -1,218 +1,477 @@
-use serde::{Deserialize, Serialize};
+use crate::backend::AccountBackend;
+use crate::consts;
+use crate::users::models::UserErrors;
+pub use api_types::v1::stats::*;
+use api_types::v2::stats as v2;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use systemstat::{ByteSize, Platform};
-
-/// Struct representing network usage information for a specific interface.
-#[derive(Deserialize, Serialize)]
-pub struct NetworkUsage {
-    interface: String,
-    tx: String,
-    rx: String,
-    total: String,
-}
-
-impl NetworkUsage {
-    /// Retrieves a list of available network interfaces.
-    pub fn get_net_if() -> Vec<String> {
-        let paths = fs::read_dir("/sys/class/net").unwrap();
-
-        let mut interfaces: Vec<String> = Vec::new();
-
-        for path in paths {
-            interfaces.push(
-                path.unwrap()
-                    .path()
-                    .display()
-                    .to_string()
-                    .replace("/sys/class/net/", ""),
-            );
-        }
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use systemstat::Platform;
+
+/// Retrieves a list of available network interfaces.
+pub fn get_net_if() -> Vec<String> {
+    let paths = fs::read_dir("/sys/class/net").unwrap();
+
+    let mut interfaces: Vec<String> = Vec::new();
 
-        interfaces
+    for path in paths {
+        interfaces.push(
+            path.unwrap()
+                .path()
+                .display()
+                .to_string()
+                .replace("/sys/class/net/", ""),
+        );
     }
 
-    /// Retrieves network usage details for all available interfaces.
-    pub fn get_usage() -> Vec<NetworkUsage> {
-        let mut network_usage_list: Vec<NetworkUsage> = Vec::new();
-
-        for interface in NetworkUsage::get_net_if() {
-            let interface_rx: u64 =
-                fs::read_to_string(format!("/sys/class/net/{interface}/statistics/rx_bytes"))
-                    .unwrap()
-                    .trim()
-                    .parse()
-                    .unwrap(); /* It can be unwrapped, we won't get errors here */
-
-            let interface_tx: u64 =
-                fs::read_to_string(format!("/sys/class/net/{interface}/statistics/tx_bytes"))
-                    .unwrap()
-                    .trim()
-                    .parse()
-                    .unwrap();
-
-            network_usage_list.push(NetworkUsage::new(interface, interface_tx, interface_rx));
-        }
+    interfaces
+}
+
+/// Retrieves network usage details for all available interfaces.
+pub fn net_usage() -> Vec<NetworkUsage> {
+    let mut network_usage_list: Vec<NetworkUsage> = Vec::new();
+
+    for interface in get_net_if() {
+        let interface_rx: u64 =
+            fs::read_to_string(format!("/sys/class/net/{interface}/statistics/rx_bytes"))
+                .unwrap()
+                .trim()
+                .parse()
+                .unwrap(); /* It can be unwrapped, we won't get errors here */
 
-        network_usage_list
+        let interface_tx: u64 =
+            fs::read_to_string(format!("/sys/class/net/{interface}/statistics/tx_bytes"))
+                .unwrap()
+                .trim()
+                .parse()
+                .unwrap();
+
+        network_usage_list.push(NetworkUsage::new(interface, interface_tx, interface_rx));
     }
 
-    /// Creates a new NetworkUsage instance with calculated total data.
-    pub fn new(interface: String, tx: u64, rx: u64) -> NetworkUsage {
-        let total: u64 = tx + rx;
+    network_usage_list
+}
+
+/// Retrieves information about CPU load, memory usage, swap usage, disk info, and uptime.
+pub fn hw_usage() -> HwUsage {
+    let sys = systemstat::platform::linux::PlatformImpl::new();
 
-        NetworkUsage {
-            interface,
-            tx: ByteSize(tx).to_string(),
-            rx: ByteSize(rx).to_string(),
-            total: ByteSize(total).to_string(),
-        }
+    // TODO: needs some error handling
+    let cpu_load = sys.load_average().unwrap();
+    let memory_usage = sys.memory().unwrap();
+    let swap_usage = sys.swap().unwrap();
+    let uptime = sys.uptime().unwrap();
+    let disk = sys.mount_at("/").unwrap(); // probably would need root previlage
+
+    HwUsage {
+        cpu_load: (cpu_load.one, cpu_load.five, cpu_load.fifteen),
+        memory_usage: MemInfo::new(memory_usage.total.as_u64(), memory_usage.free.as_u64()),
+        swap_usage: MemInfo::new(swap_usage.total.as_u64(), swap_usage.free.as_u64()),
+        uptime: UptimeInfo::new(uptime.as_secs()),
+        disk_info: DiskInfo::new(disk.fs_mounted_on, disk.total.as_u64(), disk.free.as_u64()),
     }
 }
 
-/// Represents information about system uptime.
-#[derive(Deserialize, Serialize)]
-pub struct UptimeInfo {
-    seconds: u64,
-    pretty: String,
+/// Reads a counter of `/sys/class/net/<interface>/statistics`, missing ones being zero.
+fn read_counter(interface: &str, counter: &str) -> u64 {
+    fs::read_to_string(format!("/sys/class/net/{interface}/statistics/{counter}"))
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(0)
 }
 
-impl UptimeInfo {
-    /// Creates a new `UptimeInfo` instance based on the given number of seconds.
-    pub fn new(seconds: u64) -> UptimeInfo {
-        UptimeInfo {
-            seconds,
-            pretty: UptimeInfo::seconds_to_pretty(seconds),
-        }
-    }
+/// Retrieves the counters of every network interface, without rates.
+pub fn interface_counters() -> Vec<v2::InterfaceStats> {
+    let Ok(paths) = fs::read_dir("/sys/class/net") else {
+        return Vec::new();
+    };
 
-    /// Converts a duration in seconds to a human-readable string format.
-    fn seconds_to_pretty(seconds: u64) -> String {
-        let mut time_string = String::new();
-        let mut _remaining = seconds;
+    let mut interfaces: Vec<v2::InterfaceStats> = paths
+        .filter_map(|path| path.ok())
+        .map(|path| {
+            let interface = path.file_name().to_string_lossy().to_string();
+            let counter = |counter: &str| read_counter(&interface, counter);
 
-        //up 4 days, 15 hours, 22 minutes
-        let months: u64 = _remaining / 2592000;
-        _remaining -= months * 2592000;
+            v2::InterfaceStats {
+                rx_bytes: counter("rx_bytes"),
+                tx_bytes: counter("tx_bytes"),
+                rx_packets: counter("rx_packets"),
+                tx_packets: counter("tx_packets"),
+                rx_errors: counter("rx_errors"),
+                tx_errors: counter("tx_errors"),
+                rx_dropped: counter("rx_dropped"),
+                tx_dropped: counter("tx_dropped"),
+                interface,
+                ..Default::default()
+            }
+        })
+        .collect();
 
-        let weeks: u64 = _remaining / 604800;
-        _remaining -= weeks / 604800;
+    interfaces.sort_by(|a, b| a.interface.cmp(&b.interface));
+    interfaces
+}
 
-        let days: u64 = _remaining / 86400;
-        _remaining -= days * 86400;
+/// Counters of every interface at one point in time.
+struct NetSample {
+    taken: Instant,
+    interfaces: HashMap<String, (u64, u64)>,
+}
 
-        let hours: u64 = _remaining / 3600;
-        _remaining -= hours * 3600;
+/// Keeps the last two samples of the interface counters in memory, rates being computed
+/// between them.
+#[derive(Default)]
+pub struct NetSampler {
+    samples: Mutex<Vec<NetSample>>,
+}
 
-        let minutes: u64 = _remaining / 60;
-        _remaining -= minutes * 60;
+impl NetSampler {
+    /// Creates a new `NetSampler` without any sample.
+    pub fn new() -> NetSampler {
+        NetSampler::default()
+    }
 
-        if months > 0 {
-            time_string.push_str(&format!("{months} months "));
-        }
-        if weeks > 0 {
-            time_string.push_str(&format!("{weeks} weeks "));
+    /// Spawns the background thread sampling the counters every `interval`.
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> thread::JoinHandle<()> {
+        thread::spawn(move || loop {
+            self.sample();
+            thread::sleep(interval);
+        })
+    }
+
+    /// Samples the counters, replacing the oldest sample.
+    pub fn sample(&self) {
+        let sample = NetSample {
+            taken: Instant::now(),
+            interfaces: interface_counters()
+                .into_iter()
+                .map(|stats| (stats.interface, (stats.rx_bytes, stats.tx_bytes)))
+                .collect(),
+        };
+
+        if let Ok(mut samples) = self.samples.lock() {
+            samples.push(sample);
+            if samples.len() > 2 {
+                samples.remove(0);
+            }
         }
-        if days > 0 {
-            time_string.push_str(&format!("{days} days "));
+    }
+
+    /// Retrieves the current counters of every interface, along with their rates over the
+    /// last two samples.
+    pub fn net_stats(&self, pretty: bool) -> v2::NetStats {
+        let mut interfaces = interface_counters();
+        let mut rate_window = None;
+
+        if let Ok(samples) = self.samples.lock() {
+            if let [previous, latest] = samples.as_slice() {
+                let window = latest.taken.duration_since(previous.taken).as_secs_f64();
+                rate_window = Some(window).filter(|window| *window > 0.0);
+
+                for stats in &mut interfaces {
+                    let (Some(previous), Some(latest), Some(window)) = (
+                        previous.interfaces.get(&stats.interface),
+                        latest.interfaces.get(&stats.interface),
+                        rate_window,
+                    ) else {
+                        continue;
+                    };
+
+                    let rate = |previous: u64, latest: u64| {
+                        (latest >= previous).then(|| (latest - previous) as f64 / window)
+                    };
+                    stats.rx_rate = rate(previous.0, latest.0);
+                    stats.tx_rate = rate(previous.1, latest.1);
+                }
+            }
         }
-        if hours > 0 {
-            time_string.push_str(&format!("{hours} hours "));
+
+        if pretty {
+            interfaces = interfaces
+                .into_iter()
+                .map(v2::InterfaceStats::with_pretty)
+                .collect();
         }
-        if minutes > 0 {
-            time_string.push_str(&format!("{minutes} minutes"));
+
+        v2::NetStats {
+            rate_window,
+            interfaces,
         }
+    }
+}
+
+/// Retrieves the load, memory, swap, uptime and every mounted filesystem of the node.
+///
+/// # Errors
+///
+/// Returns `UnexpectedError` if the statistics can't be read.
+pub fn hw_stats(pretty: bool) -> Result<v2::HwStats, UserErrors> {
+    let sys = systemstat::platform::linux::PlatformImpl::new();
+
+    let load = sys
+        .load_average()
+        .map_err(|_| UserErrors::UnexpectedError)?;
+    let memory = sys.memory().map_err(|_| UserErrors::UnexpectedError)?;
+    let swap = sys.swap().map_err(|_| UserErrors::UnexpectedError)?;
+    let uptime = sys.uptime().map_err(|_| UserErrors::UnexpectedError)?;
+    let mounts = sys.mounts().map_err(|_| UserErrors::UnexpectedError)?;
+
+    // The last mount on a mount point hides the others
+    let mut filesystems: Vec<v2::FilesystemStats> = Vec::new();
+    for mount in mounts.into_iter().filter(|mount| mount.total.as_u64() > 0) {
+        filesystems.retain(|filesystem| filesystem.mount_point != mount.fs_mounted_on);
 
-        time_string
+        filesystems.push(v2::FilesystemStats {
+            mount_point: mount.fs_mounted_on,
+            device: mount.fs_mounted_from,
+            fs_type: mount.fs_type,
+            total: mount.total.as_u64(),
+            free: mount.free.as_u64(),
+            avail: mount.avail.as_u64(),
+            used: mount.total.as_u64().saturating_sub(mount.free.as_u64()),
+            files_total: mount.files_total as u64,
+            files_free: mount.files_avail as u64,
+            pretty: None,
+        });
     }
+
+    let stats = v2::HwStats {
+        load: v2::LoadAverage {
+            one: load.one,
+            five: load.five,
+            fifteen: load.fifteen,
+        },
+        memory: v2::MemoryStats::new(memory.total.as_u64(), memory.free.as_u64()),
+        swap: v2::MemoryStats::new(swap.total.as_u64(), swap.free.as_u64()),
+        filesystems,
+        uptime: uptime.as_secs(),
+        uptime_pretty: None,
+    };
+
+    Ok(if pretty { stats.with_pretty() } else { stats })
 }
 
-/// Represents information about memory usage.
-#[derive(Deserialize, Serialize)]
-pub struct MemInfo {
-    used: u64,
-    free: u64,
+/// Busy and total time of a CPU in clock ticks, `core` being `None` for all cores together.
+#[derive(Clone, Copy)]
+struct CpuTimes {
+    core: Option<usize>,
+    busy: u64,
     total: u64,
-    pretty: String,
-}
-
-impl MemInfo {
-    /// Creates a new `MemInfo` instance based on total and free memory values.
-    pub fn new(total: u64, free: u64) -> MemInfo {
-        let used = total - free;
-        let pretty = format!(
-            "{}/{} ({})",
-            ByteSize(total - free),
-            ByteSize(total),
-            ByteSize(free)
-        );
-        MemInfo {
-            used,
-            free,
-            total,
-            pretty,
+}
+
+/// Reads the times of every CPU from `/proc/stat`, all cores together first.
+fn cpu_times() -> Vec<CpuTimes> {
+    let content = fs::read_to_string("/proc/stat").unwrap_or_default();
+
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let core = match fields.next()?.strip_prefix("cpu")? {
+                "" => None,
+                core => Some(core.parse().ok()?),
+            };
+
+            // user, nice, system, idle, iowait, irq, softirq and steal, guest time being
+            // already counted in user time
+            let times: Vec<u64> = fields
+                .take(8)
+                .filter_map(|time| time.parse().ok())
+                .collect();
+            let idle = times.get(3).copied().unwrap_or(0) + times.get(4).copied().unwrap_or(0);
+            let total: u64 = times.iter().sum();
+
+            Some(CpuTimes {
+                core,
+                busy: total.saturating_sub(idle),
+                total,
+            })
+        })
+        .collect()
+}
+
+/// Keeps the last two samples of the CPU times in memory, utilisation being computed
+/// between them.
+#[derive(Default)]
+pub struct CpuSampler {
+    samples: Mutex<Vec<Vec<CpuTimes>>>,
+}
+
+impl CpuSampler {
+    /// Creates a new `CpuSampler` without any sample.
+    pub fn new() -> CpuSampler {
+        CpuSampler::default()
+    }
+
+    /// Spawns the background thread sampling the CPU times every `interval`.
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> thread::JoinHandle<()> {
+        thread::spawn(move || loop {
+            self.sample();
+            thread::sleep(interval);
+        })
+    }
+
+    /// Samples the CPU times, replacing the oldest sample.
+    pub fn sample(&self) {
+        let sample = cpu_times();
+
+        if let Ok(mut samples) = self.samples.lock() {
+            samples.push(sample);
+            if samples.len() > 2 {
+                samples.remove(0);
+            }
         }
     }
+
+    /// Retrieves the utilisation of all cores together and of every core over the last two
+    /// samples, as percentages.
+    pub fn cpu_stats(&self) -> (Option<f32>, Vec<v2::CpuStats>) {
+        let usage = |core: Option<usize>| {
+            let samples = self.samples.lock().ok()?;
+            let [previous, latest] = samples.as_slice() else {
+                return None;
+            };
+            let find = |times: &[CpuTimes]| times.iter().copied().find(|cpu| cpu.core == core);
+            let (previous, latest) = (find(previous)?, find(latest)?);
+
+            let total = latest
+                .total
+                .checked_sub(previous.total)
+                .filter(|total| *total > 0)?;
+            let busy = latest.busy.checked_sub(previous.busy)?;
+            Some((busy as f64 / total as f64 * 100.0) as f32)
+        };
+
+        let cpus = cpu_times()
+            .into_iter()
+            .filter_map(|cpu| cpu.core)
+            .map(|core| v2::CpuStats {
+                core,
+                usage: usage(Some(core)),
+            })
+            .collect();
+
+        (usage(None), cpus)
+    }
 }
 
-/// Represents information about disk usage.
-#[derive(Deserialize, Serialize)]
-pub struct DiskInfo {
-    mount_point: String,
-    used: u64,
-    free: u64,
-    total: u64,
-    pretty: String,
-}
-
-impl DiskInfo {
-    /// Creates a new `DiskInfo` instance based on mount point, total, and free disk space values.
-    pub fn new(mount_point: String, total: u64, free: u64) -> DiskInfo {
-        let used = total - free;
-        let pretty = format!(
-            "{}: {}/{} ({})",
-            mount_point,
-            ByteSize(total - free),
-            ByteSize(total),
-            ByteSize(free)
-        );
-        DiskInfo {
-            mount_point,
-            used,
-            free,
-            total,
-            pretty,
+/// Names of the socket states of `/proc/net/tcp{,6}`, indexed by their number.
+const TCP_STATES: [&str; 12] = [
+    "UNKNOWN",
+    "ESTABLISHED",
+    "SYN_SENT",
+    "SYN_RECV",
+    "FIN_WAIT1",
+    "FIN_WAIT2",
+    "TIME_WAIT",
+    "CLOSE",
+    "CLOSE_WAIT",
+    "LAST_ACK",
+    "LISTEN",
+    "CLOSING",
+];
+
+/// Counts the IPv4 and IPv6 TCP sockets by state.
+fn tcp_connections() -> BTreeMap<String, u64> {
+    let mut connections: BTreeMap<String, u64> = BTreeMap::new();
+
+    for path in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        let content = fs::read_to_string(path).unwrap_or_default();
+
+        for line in content.lines().skip(1) {
+            let Some(state) = line
+                .split_whitespace()
+                .nth(3)
+                .and_then(|state| usize::from_str_radix(state, 16).ok())
+            else {
+                continue;
+            };
+
+            let state = TCP_STATES.get(state).copied().unwrap_or("UNKNOWN");
+            *connections.entry(state.to_string()).or_default() += 1;
         }
     }
+
+    connections
 }
 
-/// Represents hardware usage information including CPU load, memory usage, swap usage, disk info, and uptime.
-#[derive(Deserialize, Serialize)]
-pub struct HwUsage {
-    cpu_load: (f32, f32, f32),
-    memory_usage: MemInfo,
-    swap_usage: MemInfo,
-    disk_info: DiskInfo,
-    uptime: UptimeInfo,
-}
-
-impl HwUsage {
-    /// Creates a new `HwUsage` instance with information about CPU load, memory usage, swap usage, disk info, and uptime.
-    pub fn new() -> HwUsage {
-        let sys = systemstat::platform::linux::PlatformImpl::new();
-
-        // TODO: needs some error handling
-        let cpu_load = sys.load_average().unwrap();
-        let memory_usage = sys.memory().unwrap();
-        let swap_usage = sys.swap().unwrap();
-        let uptime = sys.uptime().unwrap();
-        let disk = sys.mount_at("/").unwrap(); // probably would need root previlage
-
-        HwUsage {
-            cpu_load: (cpu_load.one, cpu_load.five, cpu_load.fifteen),
-            memory_usage: MemInfo::new(memory_usage.total.as_u64(), memory_usage.free.as_u64()),
-            swap_usage: MemInfo::new(swap_usage.total.as_u64(), swap_usage.free.as_u64()),
-            uptime: UptimeInfo::new(uptime.as_secs()),
-            disk_info: DiskInfo::new(disk.fs_mounted_on, disk.total.as_u64(), disk.free.as_u64()),
+/// Counts every process and the `sshd` ones by scanning `/proc`.
+fn count_processes() -> (u64, u64) {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return (0, 0);
+    };
+
+    let mut processes = 0;
+    let mut sshd_processes = 0;
+
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|pid| pid.parse::<u32>().ok())
+        else {
+            continue;
+        };
+
+        processes += 1;
+
+        let comm = fs::read_to_string(format!("/proc/{pid}/comm")).unwrap_or_default();
+        if matches!(comm.trim(), "sshd" | "sshd-session") {
+            sshd_processes += 1;
         }
     }
+
+    (processes, sshd_processes)
+}
+
+/// Reads the `PRETTY_NAME` of the operating system from `os-release`.
+fn os_name() -> Option<String> {
+    ["/etc/os-release", "/usr/lib/os-release"]
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())?
+        .lines()
+        .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+        .map(|name| name.trim().trim_matches('"').to_string())
+}
+
+/// Retrieves the CPU utilisation and temperature, processes, TCP connections, kernel and
+/// operating system of the node, along with its capacity and managed accounts.
+///
+/// # Errors
+///
+/// Returns `UnexpectedError` if the kernel release can't be read.
+pub fn system_stats(
+    sampler: &CpuSampler,
+    capacity: Option<u64>,
+    backend: &dyn AccountBackend,
+) -> Result<v2::SystemStats, UserErrors> {
+    let sys = systemstat::platform::linux::PlatformImpl::new();
+
+    let kernel = fs::read_to_string("/proc/sys/kernel/osrelease")
+        .map_err(|_| UserErrors::UnexpectedError)?
+        .trim()
+        .to_string();
+
+    let (cpu_usage, cpus) = sampler.cpu_stats();
+    let (processes, sshd_processes) = count_processes();
+
+    let accounts = backend
+        .all_accounts()
+        .into_iter()
+        .filter(|account| account.uid >= consts::UID_MIN)
+        .count() as u64;
+
+    Ok(v2::SystemStats {
+        cpu_usage,
+        cpus,
+        cpu_temperature: sys.cpu_temp().ok(),
+        processes,
+        sshd_processes,
+        tcp_connections: tcp_connections(),
+        kernel,
+        os: os_name(),
+        capacity,
+        accounts,
+    })
 }
@@ -1,72 +1,18 @@
 use crate::consts;
-use pwhash::sha512_crypt;
-use regex::Regex;
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
-use std::collections::HashMap;
-use std::process::Command;
 use time::{macros::format_description, Date};
 
+use crate::accounting::{TrafficAccounting, TrafficTotals};
+use crate::backend::AccountBackend;
+use crate::passwords::{self, PasswordPolicy};
+
 use self::models::*;
 
 pub mod models {
-    use serde::{Deserialize, Serialize};
-
-    #[derive(Deserialize, Serialize)]
-    pub struct SSHUserInfo {
-        pub username: String,
-        pub userid: u32,
-        pub usergroup: Option<(u32, String)>,
-        pub exp_date: String,
-    }
-
-    #[derive(Deserialize, Serialize)]
-    pub struct UserRawCreds {
-        pub username: String,
-        pub password: String,
-        pub password_hash: String,
-    }
-
-    #[derive(Deserialize, Serialize)]
-    pub struct UserExp {
-        pub username: String,
-        pub exp_date: String,
-    }
-
-    #[derive(Deserialize, Serialize)]
-    pub struct UserStatus {
-        pub username: String,
-        pub status: String,
-    }
-
-    #[derive(Deserialize, Serialize)]
-    pub struct ChExpMsg {
-        pub username: String,
-        pub exp_date: String,
-        pub message: String,
-    }
-
-    #[derive(Deserialize, Serialize)]
-    pub struct ChGrpMsg {
-        pub username: String,
-        pub group: String,
-        pub message: String,
-    }
-
-    #[derive(Deserialize, Serialize)]
-    pub enum UserErrors {
-        UserAlreadyExists,
-        InvalidUserOrGroup,
-        InvalidShell,
-        InvalidExpDate,
-        InvalidPasswordHash,
-        PermissionDenied,
-        UnexpectedError,
-        ProcessTerminated,
-        CannotDeleteYourSelf,
-        InvalidTraceFile,
-        CommandNotFound,
-    }
+    pub use api_types::v1::users::{
+        ChExpMsg, ChGrpMsg, SSHUserInfo, UserErrors, UserExp, UserPasswordHash, UserRawCreds,
+        UserStatus,
+    };
 }
 
 //NOTE: Deprecated should be removed in newer versions
@@ -78,40 +24,31 @@ pub struct UserCredentials {
 }
 
 impl UserCredentials {
-    /// Creates a new `UserCredentials` instance with a hashed password.
-    pub fn new(username: String) -> UserCredentials {
-        UserCredentials {
-            username: username.clone(),
-            password_hash: Self::hash_password(&Self::gen_password(username)),
-        }
+    /// Creates a new `UserCredentials` instance with a random password.
+    pub fn new(username: String, policy: &PasswordPolicy) -> Result<UserCredentials, UserErrors> {
+        Self::new_raw(username, policy.generate(), policy)
     }
 
     /// Creates a new `UserCredentials` instance with provided username and hashed password.
-    pub fn new_raw(username: String, password: String) -> UserCredentials {
-        // Verify password hash formatting
-        UserCredentials {
+    pub fn new_raw(
+        username: String,
+        password: String,
+        policy: &PasswordPolicy,
+    ) -> Result<UserCredentials, UserErrors> {
+        Ok(UserCredentials {
             username,
-            password_hash: Self::hash_password(&password),
-        }
+            password_hash: Self::hash_password(&password, policy)?,
+        })
     }
 
-    /// Hashes a password using SHA-512 crypt.
-    pub fn hash_password(password: &str) -> String {
-        sha512_crypt::hash_with(consts::PASSWD_PARAMS, password).unwrap()
+    /// Hashes a password with the policy's method and a random salt.
+    pub fn hash_password(password: &str, policy: &PasswordPolicy) -> Result<String, UserErrors> {
+        policy.hash(password)
     }
 
-    /// Generates a psudo-random password based on the given username.
-    pub fn gen_password(username: String) -> String {
-        //TODO: just randomly generate it
-        let mut hasher = Sha256::new();
-
-        hasher.update(consts::PASSWD_IV);
-        hasher.update(username.as_bytes());
-
-        let hash = hasher.finalize();
-
-        let hex_hash = format!("{hash:0x}");
-        format!("{}{}", consts::PASSWD_PREFIX, &hex_hash[10..16])
+    /// Generates a random password following the policy.
+    pub fn gen_password(policy: &PasswordPolicy) -> String {
+        policy.generate()
     }
 
     /// Gets the username from the credentials.
@@ -138,15 +75,19 @@ pub struct SSHUser {
 impl SSHUser {
     /// Automatically adds an SSH user with incremented username, default shell, usergroup, and expiration date.
     pub fn auto_add(
+        backend: &dyn AccountBackend,
+        policy: &PasswordPolicy,
         users_info: (&str, u64),
         usergroup: String,
         exp_date: String,
     ) -> Result<SSHUser, UserErrors> {
         //NOTE: users_info contains username's prefix and users count
         let username = format!("{}{}", users_info.0, users_info.1 + 1);
-        let password = UserCredentials::gen_password(username.clone());
+        let password = UserCredentials::gen_password(policy);
 
         Self::add(
+            backend,
+            policy,
             username,
             consts::DEFAULT_SHELL.to_string(),
             usergroup,
@@ -157,6 +98,8 @@ impl SSHUser {
 
     /// Adds an SSH user with the provided username, shell, usergroup, expiration date, and password.
     pub fn add(
+        backend: &dyn AccountBackend,
+        policy: &PasswordPolicy,
         username: String,
         shell: String,
         usergroup: String,
@@ -164,272 +107,200 @@ impl SSHUser {
         password: String,
     ) -> Result<SSHUser, UserErrors> {
         let exp_date = Self::format_exp_date(&exp_date)?;
-        let user_credentials = UserCredentials::new_raw(username, password);
-
-        let process_status = Command::new("useradd")
-            .arg("-p")
-            .arg(user_credentials.get_password_hash())
-            .arg("-s")
-            .arg(&shell)
-            .arg("-g")
-            .arg(&usergroup)
-            .arg("-e")
-            .arg(&exp_date)
-            .arg(user_credentials.get_username())
-            .status();
-
-        match process_status {
-            Ok(status) => {
-                if let Some(error) = Self::unixuser_code_to_err(status.code()) {
-                    Err(error)
-                } else {
-                    Ok(SSHUser {
-                        user_credentials,
-                        shell,
-                        usergroup,
-                        exp_date,
-                    })
-                }
-            }
-            Err(_) => Err(UserErrors::CommandNotFound),
-        }
-    }
-
-    /// Restores password from the username (will be removed due to security issues).
-    pub fn restore_password(username: String) -> UserRawCreds {
-        let password = UserCredentials::gen_password(username.clone());
-        let password_hash = UserCredentials::hash_password(&password);
-
-        UserRawCreds {
-            username,
-            password,
-            password_hash,
-        }
+        let user_credentials = UserCredentials::new_raw(username, password, policy)?;
+
+        backend.useradd(
+            user_credentials.get_username(),
+            user_credentials.get_password_hash(),
+            &shell,
+            &usergroup,
+            &exp_date,
+        )?;
+
+        Ok(SSHUser {
+            user_credentials,
+            shell,
+            usergroup,
+            exp_date,
+        })
     }
 
     /// Deletes the current SSH user.
-    pub fn del(&self) -> Result<UserStatus, UserErrors> {
-        Self::userdel(self.user_credentials.get_username())
+    pub fn del(&self, backend: &dyn AccountBackend) -> Result<UserStatus, UserErrors> {
+        Self::userdel(backend, self.user_credentials.get_username())
     }
 
     /// Deletes an SSH user by username.
-    pub fn userdel(username: &str) -> Result<UserStatus, UserErrors> {
-        let process_status = Command::new("userdel").arg(username).status();
+    pub fn userdel(backend: &dyn AccountBackend, username: &str) -> Result<UserStatus, UserErrors> {
+        backend.userdel(username)?;
 
-        match process_status {
-            Ok(status) => {
-                if let Some(error) = Self::unixuser_code_to_err(status.code()) {
-                    Err(error)
-                } else {
-                    Ok(UserStatus {
-                        username: username.to_string(),
-                        status: format!("user {username} sucessfully deleted"),
-                    })
-                }
-            }
-            Err(_) => Err(UserErrors::CommandNotFound),
-        }
+        Ok(UserStatus {
+            username: username.to_string(),
+            status: format!("user {username} {} deleted", Self::outcome(backend)),
+        })
     }
 
     /// Changes the password of the current SSH user.
-    pub fn usermod_change_pass(username: &str, password: &str) -> Result<UserRawCreds, UserErrors> {
-        let password_hash = UserCredentials::hash_password(password);
-
-        let process_status = Command::new("usermod")
-            .arg("-p")
-            .arg(&password_hash)
-            .arg(username)
-            .status();
-
-        match process_status {
-            Ok(status) => {
-                if let Some(error) = Self::unixuser_code_to_err(status.code()) {
-                    Err(error)
-                } else {
-                    Ok(UserRawCreds {
-                        username: username.to_string(),
-                        password: password.to_string(),
-                        password_hash,
-                    })
-                }
-            }
-            Err(_) => Err(UserErrors::CommandNotFound),
-        }
+    pub fn usermod_change_pass(
+        backend: &dyn AccountBackend,
+        policy: &PasswordPolicy,
+        username: &str,
+        password: &str,
+    ) -> Result<UserPasswordHash, UserErrors> {
+        let password_hash = UserCredentials::hash_password(password, policy)?;
+
+        backend.set_password_hash(username, &password_hash)?;
+
+        Ok(UserPasswordHash {
+            username: username.to_string(),
+            password_hash,
+        })
+    }
+
+    /// Replaces the password of an SSH user with a freshly generated one.
+    ///
+    /// This is the only place the new plaintext password is exposed, it isn't stored anywhere.
+    pub fn usermod_reset_pass(
+        backend: &dyn AccountBackend,
+        policy: &PasswordPolicy,
+        username: &str,
+    ) -> Result<UserRawCreds, UserErrors> {
+        let password = UserCredentials::gen_password(policy);
+        let password_hash =
+            Self::usermod_change_pass(backend, policy, username, &password)?.password_hash;
+
+        Ok(UserRawCreds {
+            username: username.to_string(),
+            password,
+            password_hash,
+        })
     }
 
     /// Changes the expiration date of the current SSH user.
-    pub fn chexp(&self, exp_date: &str) -> Result<ChExpMsg, UserErrors> {
-        Self::usermod_change_exp(self.user_credentials.get_username(), exp_date)
+    pub fn chexp(
+        &self,
+        backend: &dyn AccountBackend,
+        exp_date: &str,
+    ) -> Result<ChExpMsg, UserErrors> {
+        Self::usermod_change_exp(backend, self.user_credentials.get_username(), exp_date)
     }
 
     /// Changes the expiration date of an SSH user by username.
-    pub fn usermod_change_exp(username: &str, exp_date: &str) -> Result<ChExpMsg, UserErrors> {
+    pub fn usermod_change_exp(
+        backend: &dyn AccountBackend,
+        username: &str,
+        exp_date: &str,
+    ) -> Result<ChExpMsg, UserErrors> {
         let exp_date = Self::format_exp_date(exp_date)?;
 
-        let process_status = Command::new("chage")
-            .arg("-E")
-            .arg(&exp_date)
-            .arg(username)
-            .status();
+        backend.set_exp_date(username, &exp_date)?;
 
-        match process_status {
-            Ok(status) => {
-                if let Some(error) = Self::unixuser_code_to_err(status.code()) {
-                    Err(error)
-                } else {
-                    Ok(ChExpMsg {
-                        username: username.to_string(),
-                        exp_date: exp_date.to_string(),
-                        message: format!(
-                            "user {username}'s expiry date sucessfully changed to {exp_date}"
-                        ),
-                    })
-                }
-            }
-            Err(_) => Err(UserErrors::CommandNotFound),
-        }
+        Ok(ChExpMsg {
+            username: username.to_string(),
+            exp_date: exp_date.to_string(),
+            message: format!(
+                "user {username}'s expiry date {} changed to {exp_date}",
+                Self::outcome(backend)
+            ),
+        })
     }
 
-    pub fn chgrp(&self, group: &str) -> Result<ChGrpMsg, UserErrors> {
-        Self::usermod_change_grp(self.user_credentials.get_username(), group)
+    pub fn chgrp(&self, backend: &dyn AccountBackend, group: &str) -> Result<ChGrpMsg, UserErrors> {
+        Self::usermod_change_grp(backend, self.user_credentials.get_username(), group)
     }
 
-    pub fn usermod_change_grp(username: &str, group: &str) -> Result<ChGrpMsg, UserErrors> {
-        let process_status = Command::new("usermod")
-            .arg("-g")
-            .arg(group)
-            .arg(username)
-            .status();
+    pub fn usermod_change_grp(
+        backend: &dyn AccountBackend,
+        username: &str,
+        group: &str,
+    ) -> Result<ChGrpMsg, UserErrors> {
+        backend.set_group(username, group)?;
 
-        match process_status {
-            Ok(status) => {
-                if let Some(error) = Self::unixuser_code_to_err(status.code()) {
-                    Err(error)
-                } else {
-                    Ok(ChGrpMsg {
-                        username: username.to_string(),
-                        group: group.to_string(),
-                        message: format!("user {username}'s group sucessfully changed to {group}"),
-                    })
-                }
-            }
-            Err(_) => Err(UserErrors::CommandNotFound),
-        }
+        Ok(ChGrpMsg {
+            username: username.to_string(),
+            group: group.to_string(),
+            message: format!(
+                "user {username}'s group {} changed to {group}",
+                Self::outcome(backend)
+            ),
+        })
     }
 
-    pub fn lock(&self) -> Result<UserStatus, UserErrors> {
-        Self::usermod_lock(self.user_credentials.get_username())
+    pub fn lock(&self, backend: &dyn AccountBackend) -> Result<UserStatus, UserErrors> {
+        Self::usermod_lock(backend, self.user_credentials.get_username())
     }
 
-    pub fn usermod_lock(username: &str) -> Result<UserStatus, UserErrors> {
-        let process_status = Command::new("usermod").arg("-L").arg(username).status();
+    pub fn usermod_lock(
+        backend: &dyn AccountBackend,
+        username: &str,
+    ) -> Result<UserStatus, UserErrors> {
+        backend.lock(username)?;
 
-        match process_status {
-            Ok(status) => {
-                if let Some(error) = Self::unixuser_code_to_err(status.code()) {
-                    Err(error)
-                } else {
-                    Ok(UserStatus {
-                        username: username.to_string(),
-                        status: format!("user {username} sucessfully locked"),
-                    })
-                }
-            }
-            Err(_) => Err(UserErrors::CommandNotFound),
-        }
+        Ok(UserStatus {
+            username: username.to_string(),
+            status: format!("user {username} {} locked", Self::outcome(backend)),
+        })
     }
 
-    pub fn unlock(&self) -> Result<UserStatus, UserErrors> {
-        Self::usermod_unlock(self.user_credentials.get_username())
+    pub fn unlock(&self, backend: &dyn AccountBackend) -> Result<UserStatus, UserErrors> {
+        Self::usermod_unlock(backend, self.user_credentials.get_username())
     }
 
-    pub fn usermod_unlock(username: &str) -> Result<UserStatus, UserErrors> {
-        let process_status = Command::new("usermod").arg("-U").arg(username).status();
+    pub fn usermod_unlock(
+        backend: &dyn AccountBackend,
+        username: &str,
+    ) -> Result<UserStatus, UserErrors> {
+        backend.unlock(username)?;
 
-        match process_status {
-            Ok(status) => {
-                if let Some(error) = Self::unixuser_code_to_err(status.code()) {
-                    Err(error)
-                } else {
-                    Ok(UserStatus {
-                        username: username.to_string(),
-                        status: format!("user {username} sucessfully unlocked"),
-                    })
-                }
-            }
-            Err(_) => Err(UserErrors::CommandNotFound),
-        }
+        Ok(UserStatus {
+            username: username.to_string(),
+            status: format!("user {username} {} unlocked", Self::outcome(backend)),
+        })
     }
 
-    pub fn exp(&self) -> Result<UserExp, UserErrors> {
-        Self::get_chage_exp(self.user_credentials.get_username())
+    pub fn exp(&self, backend: &dyn AccountBackend) -> Result<UserExp, UserErrors> {
+        Self::get_chage_exp(backend, self.user_credentials.get_username())
     }
 
-    // TODO: clean it up
-    pub fn get_chage_exp(username: &str) -> Result<UserExp, UserErrors> {
-        let process_output = Command::new("chage").arg("-l").arg(username).output();
-        match process_output {
-            Ok(output) => {
-                if output.status.code() != Some(0) {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    if stderr.contains("does not exist in /etc/passwd") {
-                        Err(UserErrors::InvalidUserOrGroup)
-                    } else {
-                        Err(Self::unixuser_code_to_err(output.status.code()).unwrap())
-                    }
-                } else {
-                    let user_info = String::from_utf8_lossy(&output.stdout);
-
-                    let re = Regex::new("Account expires\t+: (.*)\n").unwrap();
-
-                    let caps = re.captures(&user_info);
-
-                    if let Some(caps) = caps {
-                        let exp_date = caps.get(1).map_or("", |m| m.as_str()).to_string();
-                        if exp_date == "never" {
-                            return Ok(UserExp {
-                                username: username.to_string(),
-                                exp_date: "never".to_string(),
-                            });
-                        }
-
-                        let inp_format = format_description!("[month repr:short] [day], [year]");
-
-                        let out_format = format_description!("[year]-[month]-[day]");
-                        match Date::parse(&exp_date, &inp_format) {
-                            Ok(date) => Ok(UserExp {
-                                username: username.to_string(),
-                                exp_date: date.format(&out_format).unwrap(),
-                            }),
-                            Err(_) => Err(UserErrors::InvalidExpDate),
-                        }
-                    } else {
-                        Err(UserErrors::UnexpectedError)
-                    }
-                }
-            }
-            Err(_e) => Err(UserErrors::CommandNotFound),
-        }
+    pub fn get_chage_exp(
+        backend: &dyn AccountBackend,
+        username: &str,
+    ) -> Result<UserExp, UserErrors> {
+        Ok(UserExp {
+            username: username.to_string(),
+            exp_date: backend.get_shadow(username)?.exp_date(),
+        })
     }
 
     /// Retrieves SSH user information by username.
-    pub fn get_user(username: &str) -> Option<SSHUserInfo> {
-        match users::get_user_by_name(username) {
+    pub fn get_user(backend: &dyn AccountBackend, username: &str) -> Option<SSHUserInfo> {
+        match backend.get_account(username) {
             Some(user) => {
-                let group_id = user.primary_group_id();
-                let usergroup: Option<(u32, String)> = users::get_group_by_gid(group_id)
-                    .map(|group| (group.gid(), group.name().to_string_lossy().to_string()));
+                let usergroup: Option<(u32, String)> = backend
+                    .get_group_name(user.gid)
+                    .map(|group| (user.gid, group));
 
-                let exp_info = Self::get_chage_exp(username).unwrap_or(UserExp {
-                    username: username.to_string(),
-                    exp_date: "N/A".to_string(),
-                });
+                let shadow_entry = backend.get_shadow(username).ok();
 
                 Some(SSHUserInfo {
                     username: username.to_string(),
-                    userid: user.uid(),
+                    userid: user.uid,
                     usergroup,
-                    exp_date: exp_info.exp_date,
+                    exp_date: shadow_entry
+                        .as_ref()
+                        .map_or("N/A".to_string(), |entry| entry.exp_date()),
+                    locked: shadow_entry.as_ref().map_or(false, |entry| entry.locked),
+                    last_change: shadow_entry
+                        .as_ref()
+                        .and_then(|entry| entry.last_change_date()),
+                    min_age: shadow_entry.as_ref().and_then(|entry| entry.min_age),
+                    max_age: shadow_entry.as_ref().and_then(|entry| entry.max_age),
+                    inactivity: shadow_entry.as_ref().and_then(|entry| entry.inactivity),
+                    hash_method: shadow_entry
+                        .as_ref()
+                        .and_then(|entry| entry.password_hash.as_deref())
+                        .and_then(passwords::hash_method_name)
+                        .map(str::to_string),
                 })
             }
 
@@ -438,32 +309,29 @@ impl SSHUser {
     }
 
     /// Retrieves a list of usernames matching the provided prefix.
-    pub fn get_users_by_prefix(prefix: &str) -> Vec<String> {
-        Self::get_users_core(prefix, None)
+    pub fn get_users_by_prefix(backend: &dyn AccountBackend, prefix: &str) -> Vec<String> {
+        Self::get_users_core(backend, prefix, None)
     }
 
-    pub fn get_users_by_group(usergroup: &str) -> Vec<String> {
-        Self::get_users_core("", Some(usergroup))
+    pub fn get_users_by_group(backend: &dyn AccountBackend, usergroup: &str) -> Vec<String> {
+        Self::get_users_core(backend, "", Some(usergroup))
     }
 
-    pub fn get_users_core(prefix: &str, usergroup: Option<&str>) -> Vec<String> {
-        let iter = unsafe { users::all_users() };
+    pub fn get_users_core(
+        backend: &dyn AccountBackend,
+        prefix: &str,
+        usergroup: Option<&str>,
+    ) -> Vec<String> {
         let mut users_list: Vec<String> = Vec::new();
 
-        for user in iter {
-            let username = user.name().to_string_lossy();
-            let groups = user.groups();
-
-            if username.starts_with(prefix) {
+        for user in backend.all_accounts() {
+            if user.username.starts_with(prefix) {
                 if let Some(usergroup) = usergroup {
-                    if let Some(groups) = groups {
-                        let group = groups.iter().find(|g| g.name() == usergroup);
-                        if group.is_some() {
-                            users_list.push(username.to_string());
-                        }
+                    if user.groups.iter().any(|g| g == usergroup) {
+                        users_list.push(user.username);
                     }
                 } else {
-                    users_list.push(username.to_string());
+                    users_list.push(user.username);
                 }
             }
         }
@@ -471,52 +339,49 @@ impl SSHUser {
         users_list
     }
 
-    /// Retrieves resource usage for a specific user group.
-    pub fn get_usage_by_group(usergroup: &str) -> Result<HashMap<String, f64>, UserErrors> {
-        let users = Self::get_users_by_group(usergroup);
-        Self::get_usage_core(&users)
+    /// Retrieves traffic usage for a specific user group.
+    pub fn get_usage_by_group(
+        backend: &dyn AccountBackend,
+        accounting: &TrafficAccounting,
+        usergroup: &str,
+    ) -> Result<TrafficTotals, UserErrors> {
+        let users = Self::get_users_by_group(backend, usergroup);
+        Self::get_usage_core(accounting, &users)
     }
 
-    /// Retrieves resource usage for a specific username.
-    pub fn get_usage_by_name(username: &str) -> Result<HashMap<String, f64>, UserErrors> {
-        Self::get_usage_core(&vec![username.to_string()])
+    /// Retrieves traffic usage for a specific username.
+    pub fn get_usage_by_name(
+        accounting: &TrafficAccounting,
+        username: &str,
+    ) -> Result<TrafficTotals, UserErrors> {
+        Self::get_usage_core(accounting, &[username.to_string()])
     }
 
-    /// Retrieves resource usage for usernames matching the provided prefix.
-    pub fn get_usage_by_prefix(prefix: &str) -> Result<HashMap<String, f64>, UserErrors> {
-        let users = Self::get_users_by_prefix(prefix);
-        Self::get_usage_core(&users)
+    /// Retrieves traffic usage for usernames matching the provided prefix.
+    pub fn get_usage_by_prefix(
+        backend: &dyn AccountBackend,
+        accounting: &TrafficAccounting,
+        prefix: &str,
+    ) -> Result<TrafficTotals, UserErrors> {
+        let users = Self::get_users_by_prefix(backend, prefix);
+        Self::get_usage_core(accounting, &users)
     }
 
-    // NOTE: Needs Cleaning up and optimization and error handling
-    /// Core method for retrieving resource usage for a list of usernames.
-    pub fn get_usage_core(users: &Vec<String>) -> Result<HashMap<String, f64>, UserErrors> {
-        let binding = match std::fs::read_to_string(consts::NETHOGS_TRACE_PATH) {
-            Ok(binding) => binding,
-            Err(_e) => return Err(UserErrors::InvalidTraceFile),
-        };
-        let log_content = binding.split('\n');
-
-        let re = Regex::new(r".+/.+/\w+[\s|\t]+(.+)").unwrap();
-
-        let mut usage_list: HashMap<String, f64> = HashMap::new();
+    /// Core method for retrieving cumulative rx/tx traffic for a list of usernames.
+    pub fn get_usage_core(
+        accounting: &TrafficAccounting,
+        users: &[String],
+    ) -> Result<TrafficTotals, UserErrors> {
+        accounting.totals(users)
+    }
 
-        for user in users.iter() {
-            let mut total_usage = 0f64;
-            for log_line in log_content.clone() {
-                if log_line.contains(user) {
-                    let caps = re
-                        .captures(&log_line)
-                        .unwrap_or(re.captures("a: n/0/0  0  0").unwrap());
-                    let usage_part = caps.get(1).map_or("", |m| m.as_str()).to_string();
-                    for usage in usage_part.split('\t') {
-                        total_usage += usage.parse::<f64>().unwrap_or(0f64);
-                    }
-                }
-            }
-            usage_list.insert(user.to_string(), total_usage);
+    /// Describes the outcome of a command in its status message.
+    fn outcome(backend: &dyn AccountBackend) -> &'static str {
+        if backend.dry_run() {
+            "would be"
+        } else {
+            "sucessfully"
         }
-        Ok(usage_list)
     }
 
     /// Formats an expiration date string into the required format.
@@ -527,20 +392,16 @@ impl SSHUser {
             Err(_) => Err(UserErrors::InvalidExpDate),
         }
     }
+}
 
-    /// Converts Unix user command exit codes to corresponding errors.
-    fn unixuser_code_to_err(code: Option<i32>) -> Option<UserErrors> {
-        if let Some(code) = code {
-            match code {
-                0 => None,
-                1 => Some(UserErrors::PermissionDenied),
-                3 => Some(UserErrors::InvalidShell),
-                6 => Some(UserErrors::InvalidUserOrGroup),
-                9 => Some(UserErrors::UserAlreadyExists),
-                _ => Some(UserErrors::UnexpectedError),
-            }
-        } else {
-            Some(UserErrors::ProcessTerminated)
+impl From<SSHUser> for api_types::v1::users::SSHUser {
+    fn from(sshuser: SSHUser) -> Self {
+        api_types::v1::users::SSHUser {
+            username: sshuser.user_credentials.username,
+            password_hash: sshuser.user_credentials.password_hash,
+            shell: sshuser.shell,
+            usergroup: sshuser.usergroup,
+            exp_date: sshuser.exp_date,
         }
     }
 }
@@ -0,0 +1,24 @@
+use lib::config::ConfigFile;
+use std::fs;
+use std::path::PathBuf;
+
+fn sample() -> ConfigFile {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("config_sample.json");
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn sample_secrets_are_refused() {
+    let err = sample().secrets.check().unwrap_err();
+    assert!(err.starts_with("jwt_secret"), "{err}");
+}
+
+#[test]
+fn generated_secrets_are_accepted() {
+    let mut config = sample();
+    config.secrets.jwt_secret = "3f9a".repeat(16);
+    config.secrets.check().unwrap();
+
+    config.secrets.metrics_token = Some("<METRICS_TOKEN_PLACEHOLDER_LONGER_THAN_32>".to_string());
+    assert!(config.secrets.check().is_err());
+}
@@ -0,0 +1,339 @@
+use api_types::v1::bulk::UserSelection;
+use api_types::v1::requests::UserLookupParams;
+use lib::backend::{AccountBackend, DryRun, FakeRootBackend};
+use lib::bulk;
+use lib::passwords::PasswordPolicy;
+use lib::sessions::{self, LoginLimiter};
+use lib::users::models::UserErrors;
+use lib::users::SSHUser;
+use std::fs;
+use std::path::PathBuf;
+
+const PASSWD: &str = "root:x:0:0:root:/root:/bin/bash
+alice:x:1000:1001::/home/alice:/bin/rbash
+";
+const SHADOW: &str = "root:*:19000:0:99999:7:::
+alice:$6$salt$hash:19000:0:99999:7::21915:
+";
+const GROUP: &str = "root:x:0:
+grp1:x:1001:
+grp2:x:1002:alice
+";
+
+/// Creates a fake root holding the fixture `passwd`, `shadow` and `group` files.
+fn fake_root(name: &str) -> (PathBuf, FakeRootBackend) {
+    let root = std::env::temp_dir().join(format!("sshmgmt-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("etc")).unwrap();
+
+    fs::write(root.join("etc/passwd"), PASSWD).unwrap();
+    fs::write(root.join("etc/shadow"), SHADOW).unwrap();
+    fs::write(root.join("etc/group"), GROUP).unwrap();
+
+    let backend = FakeRootBackend::new(&root);
+    (root, backend)
+}
+
+fn add_user(backend: &dyn AccountBackend, username: &str) -> Result<SSHUser, UserErrors> {
+    SSHUser::add(
+        backend,
+        &PasswordPolicy::default(),
+        username.to_string(),
+        "/bin/rbash".to_string(),
+        "grp1".to_string(),
+        "2030-01-01".to_string(),
+        "password123".to_string(),
+    )
+}
+
+#[test]
+fn useradd_creates_managed_account() {
+    let (root, backend) = fake_root("useradd");
+
+    add_user(&backend, "bob").unwrap();
+
+    let user = SSHUser::get_user(&backend, "bob").unwrap();
+    assert_eq!(user.userid, 1001);
+    assert_eq!(user.usergroup, Some((1001, "grp1".to_string())));
+    assert_eq!(user.exp_date, "2030-01-01");
+    assert!(!user.locked);
+
+    let shadow = backend.get_shadow("bob").unwrap();
+    assert!(shadow.password_hash.unwrap().starts_with("$6$"));
+
+    assert!(matches!(
+        add_user(&backend, "bob"),
+        Err(UserErrors::UserAlreadyExists)
+    ));
+    assert!(matches!(
+        SSHUser::add(
+            &backend,
+            &PasswordPolicy::default(),
+            "carol".to_string(),
+            "/bin/rbash".to_string(),
+            "missing".to_string(),
+            "2030-01-01".to_string(),
+            "password123".to_string(),
+        ),
+        Err(UserErrors::InvalidUserOrGroup)
+    ));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn usermod_edits_rows() {
+    let (root, backend) = fake_root("usermod");
+
+    SSHUser::usermod_lock(&backend, "alice").unwrap();
+    assert!(backend.get_shadow("alice").unwrap().locked);
+    SSHUser::usermod_unlock(&backend, "alice").unwrap();
+    assert!(!backend.get_shadow("alice").unwrap().locked);
+
+    SSHUser::usermod_change_exp(&backend, "alice", "2031-06-15").unwrap();
+    assert_eq!(
+        SSHUser::get_chage_exp(&backend, "alice").unwrap().exp_date,
+        "2031-06-15"
+    );
+
+    SSHUser::usermod_change_grp(&backend, "alice", "grp1").unwrap();
+    assert_eq!(backend.get_account("alice").unwrap().gid, 1001);
+
+    let hash =
+        SSHUser::usermod_change_pass(&backend, &PasswordPolicy::default(), "alice", "password456")
+            .unwrap()
+            .password_hash;
+    assert_eq!(
+        backend.get_shadow("alice").unwrap().password_hash,
+        Some(hash)
+    );
+
+    assert!(matches!(
+        SSHUser::usermod_lock(&backend, "nobody"),
+        Err(UserErrors::InvalidUserOrGroup)
+    ));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn userdel_removes_rows_and_memberships() {
+    let (root, backend) = fake_root("userdel");
+
+    SSHUser::userdel(&backend, "alice").unwrap();
+
+    assert!(backend.get_account("alice").is_none());
+    assert!(backend.get_shadow("alice").is_err());
+    assert_eq!(
+        fs::read_to_string(root.join("etc/group")).unwrap(),
+        "root:x:0:\ngrp1:x:1001:\ngrp2:x:1002:\n"
+    );
+    assert!(matches!(
+        SSHUser::userdel(&backend, "alice"),
+        Err(UserErrors::InvalidUserOrGroup)
+    ));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn malformed_rows_are_refused() {
+    let (root, backend) = fake_root("malformed");
+    fs::write(root.join("etc/shadow"), "alice\n").unwrap();
+    fs::write(root.join("etc/passwd"), "alice:x:1000\n").unwrap();
+
+    assert!(backend.lock("alice").is_err());
+    assert!(backend.unlock("alice").is_err());
+    assert!(backend.set_password_hash("alice", "$6$salt$hash").is_err());
+    assert!(backend.set_group("alice", "grp1").is_err());
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn backend_useradd_writes_rows() {
+    let (root, backend) = fake_root("backend-useradd");
+
+    backend
+        .useradd("bob", "$6$salt$hash", "/bin/rbash", "grp1", "")
+        .unwrap();
+
+    let passwd = fs::read_to_string(root.join("etc/passwd")).unwrap();
+    assert_eq!(
+        passwd.lines().last(),
+        Some("bob:x:1001:1001::/home/bob:/bin/rbash")
+    );
+    let shadow = fs::read_to_string(root.join("etc/shadow")).unwrap();
+    let row: Vec<&str> = shadow.lines().last().unwrap().split(':').collect();
+    assert_eq!(row.len(), 9);
+    assert_eq!(row[..2], ["bob", "$6$salt$hash"]);
+    assert_eq!(row[3..], ["0", "99999", "7", "", "", ""]);
+
+    assert!(matches!(
+        backend.useradd("bob", "$6$salt$hash", "/bin/rbash", "grp1", ""),
+        Err(UserErrors::UserAlreadyExists)
+    ));
+    assert!(matches!(
+        backend.useradd("carol", "$6$salt$hash", "/bin/rbash", "nogroup", ""),
+        Err(UserErrors::InvalidUserOrGroup)
+    ));
+    assert!(matches!(
+        backend.useradd("carol", "$6$salt$hash", "", "grp1", ""),
+        Err(UserErrors::InvalidShell)
+    ));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn backend_edits_only_the_users_row() {
+    let (root, backend) = fake_root("backend-edit");
+
+    backend.set_exp_date("alice", "-1").unwrap();
+    backend.lock("alice").unwrap();
+    assert_eq!(
+        fs::read_to_string(root.join("etc/shadow")).unwrap(),
+        "root:*:19000:0:99999:7:::\nalice:!$6$salt$hash:19000:0:99999:7:::\n"
+    );
+
+    backend.set_group("alice", "grp1").unwrap();
+    assert_eq!(
+        fs::read_to_string(root.join("etc/passwd")).unwrap(),
+        "root:x:0:0:root:/root:/bin/bash\nalice:x:1000:1001::/home/alice:/bin/rbash\n"
+    );
+
+    assert!(matches!(
+        backend.set_exp_date("nobody", "-1"),
+        Err(UserErrors::InvalidUserOrGroup)
+    ));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn backend_refuses_separators_in_fields() {
+    let (root, backend) = fake_root("backend-separators");
+
+    assert!(matches!(
+        backend.useradd("eve:x:0:0", "$6$salt$hash", "/bin/rbash", "grp1", ""),
+        Err(UserErrors::InvalidUserOrGroup)
+    ));
+    assert!(matches!(
+        backend.useradd("eve\nroot", "$6$salt$hash", "/bin/rbash", "grp1", ""),
+        Err(UserErrors::InvalidUserOrGroup)
+    ));
+    assert!(matches!(
+        backend.useradd("eve", "$6$salt$hash", "/bin/bash:x", "grp1", ""),
+        Err(UserErrors::InvalidShell)
+    ));
+    assert!(matches!(
+        backend.useradd("eve", "hash\nroot::0:0", "/bin/rbash", "grp1", ""),
+        Err(UserErrors::InvalidPasswordHash)
+    ));
+    assert!(matches!(
+        backend.set_password_hash("alice", "::19000"),
+        Err(UserErrors::InvalidPasswordHash)
+    ));
+
+    assert_eq!(fs::read_to_string(root.join("etc/passwd")).unwrap(), PASSWD);
+    assert_eq!(fs::read_to_string(root.join("etc/shadow")).unwrap(), SHADOW);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn dry_run_leaves_files_untouched() {
+    let (root, backend) = fake_root("dry-run");
+    let dry_run = DryRun::new(&backend, true);
+
+    add_user(&dry_run, "bob").unwrap();
+    SSHUser::usermod_lock(&dry_run, "alice").unwrap();
+    SSHUser::userdel(&dry_run, "alice").unwrap();
+
+    assert!(matches!(
+        add_user(&dry_run, "alice"),
+        Err(UserErrors::UserAlreadyExists)
+    ));
+    assert_eq!(fs::read_to_string(root.join("etc/passwd")).unwrap(), PASSWD);
+    assert_eq!(fs::read_to_string(root.join("etc/shadow")).unwrap(), SHADOW);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn bulk_selection_skips_system_accounts() {
+    let (root, backend) = fake_root("bulk");
+    let lookup = |prefix: &str, group: Option<&str>| {
+        UserSelection::Lookup(UserLookupParams {
+            username: None,
+            prefix: Some(prefix.to_string()),
+            group: group.map(str::to_string),
+        })
+    };
+
+    assert!(bulk::select(&backend, &lookup("", None)).is_err());
+    assert_eq!(
+        bulk::select(&backend, &lookup("r", None)).unwrap(),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        bulk::select(&backend, &lookup("a", Some("grp2"))).unwrap(),
+        vec!["alice".to_string()]
+    );
+    assert!(bulk::select(
+        &backend,
+        &UserSelection::Usernames(vec!["alice".to_string(), "root".to_string()])
+    )
+    .is_err());
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn sessions_are_left_alone() {
+    let (root, backend) = fake_root("sessions");
+    let limiter = LoginLimiter::new(
+        root.join("login_limits.json"),
+        Box::new(FakeRootBackend::new(&root)),
+        vec![22],
+    );
+
+    assert_eq!(
+        limiter.set_limit("alice", Some(0)),
+        Err(UserErrors::InvalidLoginLimit)
+    );
+    limiter.set_limit("alice", Some(1)).unwrap();
+    limiter.enforce().unwrap();
+    assert!(limiter.violations(None).unwrap().is_empty());
+
+    assert!(matches!(
+        sessions::kick(&backend, &[22], Some("alice"), None),
+        Err(UserErrors::PermissionDenied)
+    ));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn rollback_restores_accounts_without_expiry() {
+    let (root, backend) = fake_root("rollback");
+    backend.set_exp_date("alice", "-1").unwrap();
+    let usernames = vec!["alice".to_string(), "nobody".to_string()];
+
+    let result = bulk::chexp(&backend, usernames.clone(), "2031-06-15", true);
+    assert!(result.rolled_back);
+    assert!(result.rollback_failed.is_empty());
+    assert_eq!(backend.get_shadow("alice").unwrap().expiry, None);
+
+    let result = bulk::userdel(&backend, usernames, true);
+    assert!(result.rolled_back);
+    assert!(result.rollback_failed.is_empty());
+    assert_eq!(result.partially_restored, vec!["alice".to_string()]);
+
+    let shadow = backend.get_shadow("alice").unwrap();
+    assert_eq!(shadow.expiry, None);
+    assert_eq!(shadow.password_hash.as_deref(), Some("$6$salt$hash"));
+    assert_eq!(backend.get_account("alice").unwrap().gid, 1001);
+
+    fs::remove_dir_all(root).unwrap();
+}
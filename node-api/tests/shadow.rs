@@ -0,0 +1,87 @@
+use lib::shadow::{self, ShadowEntry};
+use lib::users::models::UserErrors;
+use std::path::PathBuf;
+
+fn fixture() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/shadow")
+}
+
+#[test]
+fn parses_fixture() {
+    let entries = shadow::read_shadow(fixture()).unwrap();
+    let usernames: Vec<&str> = entries
+        .iter()
+        .map(|entry| entry.username.as_str())
+        .collect();
+    assert_eq!(usernames, ["root", "alice", "bob", "carol"]);
+
+    let alice = &entries[1];
+    assert_eq!(alice.password_hash.as_deref(), Some("$6$salt$hash"));
+    assert!(!alice.locked);
+    assert_eq!(alice.exp_date(), "2030-01-01");
+    assert_eq!(alice.last_change_date().as_deref(), Some("2022-01-08"));
+
+    let bob = &entries[2];
+    assert!(bob.locked);
+    assert_eq!(bob.min_age, Some(1));
+    assert_eq!(bob.max_age, Some(90));
+    assert_eq!(bob.warn_period, Some(14));
+    assert_eq!(bob.inactivity, Some(30));
+    assert_eq!(bob.exp_date(), "never");
+}
+
+#[test]
+fn out_of_range_expiry_does_not_panic() {
+    let carol = shadow::get_entry(fixture(), "carol").unwrap();
+    assert_eq!(carol.exp_date(), "9999999999");
+    assert_eq!(carol.last_change_date(), None);
+
+    assert_eq!(shadow::days_to_date(i64::MAX), None);
+    assert_eq!(shadow::days_to_date(i64::MIN), None);
+    assert_eq!(shadow::days_to_date(0).as_deref(), Some("1970-01-01"));
+}
+
+#[test]
+fn skips_malformed_lines() {
+    assert!(ShadowEntry::parse("malformed:x:19000").is_none());
+    assert!(ShadowEntry::parse(":x:0:0:99999:7:::").is_none());
+    assert!(matches!(
+        shadow::get_entry(fixture(), "malformed"),
+        Err(UserErrors::InvalidUserOrGroup)
+    ));
+}
+
+#[test]
+fn missing_file_is_reported() {
+    assert!(matches!(
+        shadow::read_shadow(fixture().with_file_name("missing")),
+        Err(UserErrors::ShadowNotFound)
+    ));
+}
+
+#[test]
+fn dates_round_trip() {
+    let days = shadow::date_to_days("2030-01-01").unwrap();
+    assert_eq!(days, 21915);
+    assert_eq!(shadow::days_to_date(days).as_deref(), Some("2030-01-01"));
+    assert!(matches!(
+        shadow::date_to_days("2030-13-01"),
+        Err(UserErrors::InvalidExpDate)
+    ));
+}
+
+#[test]
+fn exp_dates_parse_like_shadow_utils() {
+    assert_eq!(shadow::parse_exp_date("2030-01-01").unwrap(), Some(21915));
+    assert_eq!(shadow::parse_exp_date("21915").unwrap(), Some(21915));
+    assert_eq!(shadow::parse_exp_date("-1").unwrap(), None);
+    assert_eq!(shadow::parse_exp_date("").unwrap(), None);
+    assert!(matches!(
+        shadow::parse_exp_date("never"),
+        Err(UserErrors::InvalidExpDate)
+    ));
+    assert!(matches!(
+        shadow::parse_exp_date("-2"),
+        Err(UserErrors::InvalidExpDate)
+    ));
+}
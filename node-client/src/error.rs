@@ -0,0 +1,68 @@
+use api_types::v1::users::UserErrors;
+use api_types::v1::Refusal;
+use std::fmt;
+
+/// Enum representing the ways a request to node-api can fail.
+#[derive(Debug)]
+pub enum NodeClientError {
+    /// The node couldn't be reached or didn't answer in time.
+    Request(reqwest::Error),
+    /// The node answered with something else than the route's response.
+    Decode(reqwest::Error),
+    /// The node refused the request, e.g. for an invalid token or missing parameters.
+    Refused(Refusal),
+    /// The node accepted the command but it failed.
+    User(UserErrors),
+    /// The node answered a dry run without echoing it, so the command may have been applied.
+    DryRunIgnored,
+}
+
+impl NodeClientError {
+    /// Checks whether the request may succeed if it's sent again.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            NodeClientError::Request(err) => err.is_connect() || err.is_timeout(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for NodeClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeClientError::Request(err) => write!(f, "couldn't reach the node: {err}"),
+            NodeClientError::Decode(err) => write!(f, "unexpected response from the node: {err}"),
+            NodeClientError::Refused(refusal) => {
+                write!(f, "request refused by the node: {}", refusal.msg())
+            }
+            NodeClientError::User(err) => write!(f, "command failed on the node: {err:?}"),
+            NodeClientError::DryRunIgnored => {
+                write!(
+                    f,
+                    "the node ignored the dry run, the command may have been applied"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for NodeClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NodeClientError::Request(err) | NodeClientError::Decode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<Refusal> for NodeClientError {
+    fn from(refusal: Refusal) -> Self {
+        NodeClientError::Refused(refusal)
+    }
+}
+
+impl From<UserErrors> for NodeClientError {
+    fn from(err: UserErrors) -> Self {
+        NodeClientError::User(err)
+    }
+}
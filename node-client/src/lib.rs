@@ -0,0 +1,657 @@
+//! Async client of node-api, with one method per route.
+//!
+//! Requests are signed with short-lived tokens minted from the node's key, every call has a
+//! timeout and idempotent calls are retried with exponential backoff when the node can't be
+//! reached. Clients are cheap to create: they share the connection pool of the
+//! `reqwest::Client` they're given.
+
+use api_types::v1::accounting::{TrafficTotals, UserQuota};
+use api_types::v1::audit::AuditEntry;
+use api_types::v1::auth::{Claims, Scope};
+use api_types::v1::bulk::*;
+use api_types::v1::node::NodeInfo;
+use api_types::v1::requests::*;
+use api_types::v1::sessions::{KickedSessions, LoginViolation, Session};
+use api_types::v1::stats::{HwUsage, NetworkUsage};
+use api_types::v1::users::*;
+use api_types::v1::{Reply, UserResult};
+use api_types::v2::stats::{HwStats, NetStats, SystemStats};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use reqwest::RequestBuilder;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+
+mod error;
+
+pub use error::NodeClientError;
+
+pub mod consts {
+    use std::time::Duration;
+
+    /// Lifetime of the token minted for each request, in seconds.
+    pub const TOKEN_TTL: u64 = 60;
+    pub const TIMEOUT: Duration = Duration::from_secs(10);
+    pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+    pub const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+    pub const RETRIES: u32 = 2;
+    pub const BACKOFF: Duration = Duration::from_millis(200);
+    pub const MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+    pub const PING_PATH: &str = "/ping";
+    pub const NODE_INFO_PATH: &str = "/api/stats/node_info";
+    pub const NET_STATS_PATH: &str = "/api/stats/net_stats";
+    pub const HW_STATS_PATH: &str = "/api/stats/hw_stats";
+    pub const NET_STATS_V2_PATH: &str = "/api/v2/stats/net_stats";
+    pub const HW_STATS_V2_PATH: &str = "/api/v2/stats/hw_stats";
+    pub const SYSTEM_STATS_V2_PATH: &str = "/api/v2/stats/system_stats";
+    pub const LIST_USERS_PATH: &str = "/api/stats/list_users";
+    pub const USER_EXPIRY_PATH: &str = "/api/stats/user_expiry";
+    pub const USER_QUOTA_PATH: &str = "/api/stats/user_quota";
+    pub const USERS_USAGE_PATH: &str = "/api/stats/users_usage";
+    pub const LEGACY_PASSWORDS_PATH: &str = "/api/stats/legacy_passwords";
+    pub const LOGIN_VIOLATIONS_PATH: &str = "/api/stats/login_violations";
+    pub const SESSIONS_PATH: &str = "/api/stats/sessions";
+    pub const AUDIT_PATH: &str = "/api/stats/audit";
+    pub const KICK_PATH: &str = "/api/cmd/kick";
+    pub const USERDEL_PATH: &str = "/api/cmd/userdel";
+    pub const USERADD_PATH: &str = "/api/cmd/useradd";
+    pub const AUTO_USERADD_PATH: &str = "/api/cmd/auto_useradd";
+    pub const PASSWD_PATH: &str = "/api/cmd/passwd";
+    pub const RESET_PASSWORD_PATH: &str = "/api/cmd/reset_password";
+    pub const CHGRP_PATH: &str = "/api/cmd/chgrp";
+    pub const CHEXP_PATH: &str = "/api/cmd/chexp";
+    pub const CHLIMITS_PATH: &str = "/api/cmd/chlimits";
+    pub const USERLOCK_PATH: &str = "/api/cmd/userlock";
+    pub const USERUNLOCK_PATH: &str = "/api/cmd/userunlock";
+    pub const BULK_USERADD_PATH: &str = "/api/cmd/bulk/useradd";
+    pub const BULK_AUTO_USERADD_PATH: &str = "/api/cmd/bulk/auto_useradd";
+    pub const BULK_USERDEL_PATH: &str = "/api/cmd/bulk/userdel";
+    pub const BULK_USERLOCK_PATH: &str = "/api/cmd/bulk/userlock";
+    pub const BULK_USERUNLOCK_PATH: &str = "/api/cmd/bulk/userunlock";
+    pub const BULK_CHEXP_PATH: &str = "/api/cmd/bulk/chexp";
+    pub const BULK_CHGRP_PATH: &str = "/api/cmd/bulk/chgrp";
+}
+
+/// Returns a builder of HTTP clients with the connection settings node clients expect, TLS
+/// settings are left to the caller.
+pub fn client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .connect_timeout(consts::CONNECT_TIMEOUT)
+        .pool_idle_timeout(consts::POOL_IDLE_TIMEOUT)
+}
+
+/// Struct representing how idempotent calls are retried when the node can't be reached.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Attempts after the first one.
+    pub retries: u32,
+    /// Delay before the first retry, doubled on every following one.
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: consts::RETRIES,
+            backoff: consts::BACKOFF,
+            max_backoff: consts::MAX_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retries.
+    pub fn none() -> Self {
+        RetryPolicy {
+            retries: 0,
+            ..Default::default()
+        }
+    }
+}
+
+/// Struct representing a client of a single node.
+///
+/// Reads, `chexp`, `chgrp`, `chlimits`, `userlock` and `userunlock` are retried, commands
+/// that can't be safely applied twice (adding or deleting accounts, resetting passwords,
+/// kicking sessions) are sent once.
+#[derive(Clone)]
+pub struct NodeClient {
+    http: reqwest::Client,
+    base_url: String,
+    node_id: i32,
+    jwt_secret: String,
+    timeout: Duration,
+    retry: RetryPolicy,
+    subject: Option<String>,
+    dry_run: bool,
+}
+
+impl NodeClient {
+    /// Creates a new client of the node at `base_url` (e.g. `https://node01.example.com:8010`),
+    /// registered as `node_id` and verifying tokens with `jwt_secret`.
+    pub fn new(http: reqwest::Client, base_url: &str, node_id: i32, jwt_secret: &str) -> Self {
+        NodeClient {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            node_id,
+            jwt_secret: jwt_secret.to_string(),
+            timeout: consts::TIMEOUT,
+            retry: RetryPolicy::default(),
+            subject: None,
+            dry_run: false,
+        }
+    }
+
+    /// Sets how long a single attempt may take.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how idempotent calls are retried.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Sets the subject of the tokens, recorded in the audit log of the node.
+    pub fn with_subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    /// Sends commands as dry runs: the node only validates them and reports what would change.
+    ///
+    /// Replies that don't echo the flag are refused with `DryRunIgnored`, nodes predating dry
+    /// runs having applied the command.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Checks that the node is up.
+    pub async fn ping(&self) -> Result<(), NodeClientError> {
+        self.http
+            .get(self.url(consts::PING_PATH))
+            .timeout(self.timeout)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(NodeClientError::Request)
+    }
+
+    /// Fetches the name, location and capacity of the node.
+    pub async fn node_info(&self) -> Result<NodeInfo, NodeClientError> {
+        self.send(|| self.http.get(self.url(consts::NODE_INFO_PATH)), true)
+            .await
+    }
+
+    /// Fetches the traffic of every network interface.
+    pub async fn net_stats(&self) -> Result<Vec<NetworkUsage>, NodeClientError> {
+        self.get(consts::NET_STATS_PATH, Scope::StatsRead).await
+    }
+
+    /// Fetches the load, memory, disk usage and uptime of the node.
+    pub async fn hw_stats(&self) -> Result<HwUsage, NodeClientError> {
+        self.get(consts::HW_STATS_PATH, Scope::StatsRead).await
+    }
+
+    /// Fetches the raw counters, errors, drops and rates of every network interface, along
+    /// with human-readable summaries if `pretty`.
+    pub async fn net_stats_v2(&self, pretty: bool) -> Result<NetStats, NodeClientError> {
+        let token = self.token(Scope::StatsRead);
+        self.send(
+            || {
+                self.http
+                    .get(self.url(consts::NET_STATS_V2_PATH))
+                    .query(&[("pretty", pretty)])
+                    .bearer_auth(&token)
+            },
+            true,
+        )
+        .await
+    }
+
+    /// Fetches the load, memory, every mounted filesystem and uptime of the node in bytes and
+    /// seconds, along with human-readable summaries if `pretty`.
+    pub async fn hw_stats_v2(&self, pretty: bool) -> Result<HwStats, NodeClientError> {
+        let token = self.token(Scope::StatsRead);
+        let reply: UserResult<HwStats> = self
+            .send(
+                || {
+                    self.http
+                        .get(self.url(consts::HW_STATS_V2_PATH))
+                        .query(&[("pretty", pretty)])
+                        .bearer_auth(&token)
+                },
+                true,
+            )
+            .await?;
+
+        Ok(reply?)
+    }
+
+    /// Fetches the per-core CPU utilisation, temperature, processes, TCP connections, kernel
+    /// and operating system of the node, along with its capacity and managed accounts.
+    pub async fn system_stats(&self) -> Result<SystemStats, NodeClientError> {
+        let reply: UserResult<SystemStats> = self
+            .get(consts::SYSTEM_STATS_V2_PATH, Scope::StatsRead)
+            .await?;
+
+        Ok(reply?)
+    }
+
+    /// Lists the accounts matching a prefix or group.
+    pub async fn list_users(
+        &self,
+        lookup_params: &UserLookupParams,
+    ) -> Result<Vec<String>, NodeClientError> {
+        self.post(
+            consts::LIST_USERS_PATH,
+            Scope::UsersRead,
+            lookup_params,
+            true,
+        )
+        .await
+    }
+
+    /// Fetches the expiry date of an account.
+    pub async fn user_expiry(&self, username: &str) -> Result<UserExp, NodeClientError> {
+        let path = format!("{}/{username}", consts::USER_EXPIRY_PATH);
+        let reply: UserResult<UserExp> = self.get(&path, Scope::UsersRead).await?;
+
+        Ok(reply?)
+    }
+
+    /// Fetches the traffic quota of an account and how much of it is left.
+    pub async fn user_quota(&self, username: &str) -> Result<UserQuota, NodeClientError> {
+        let path = format!("{}/{username}", consts::USER_QUOTA_PATH);
+        let reply: UserResult<UserQuota> = self.get(&path, Scope::UsersRead).await?;
+
+        Ok(reply?)
+    }
+
+    /// Fetches the cumulative traffic of the accounts matching a username, prefix or group.
+    pub async fn users_usage(
+        &self,
+        lookup_params: &UserLookupParams,
+    ) -> Result<TrafficTotals, NodeClientError> {
+        let reply: UserResult<TrafficTotals> = self
+            .post(
+                consts::USERS_USAGE_PATH,
+                Scope::UsersRead,
+                lookup_params,
+                true,
+            )
+            .await?;
+
+        Ok(reply?)
+    }
+
+    /// Lists the accounts whose password hash still uses a legacy salt.
+    pub async fn legacy_passwords(&self) -> Result<Vec<String>, NodeClientError> {
+        self.get(consts::LEGACY_PASSWORDS_PATH, Scope::UsersRead)
+            .await
+    }
+
+    /// Lists the recent concurrent login limit violations, of a single account if given.
+    pub async fn login_violations(
+        &self,
+        username: Option<&str>,
+    ) -> Result<Vec<LoginViolation>, NodeClientError> {
+        let token = self.token(Scope::UsersRead);
+        let reply: UserResult<Vec<LoginViolation>> = self
+            .send(
+                || {
+                    self.http
+                        .get(self.url(consts::LOGIN_VIOLATIONS_PATH))
+                        .query(&[("user", username)])
+                        .bearer_auth(&token)
+                },
+                true,
+            )
+            .await?;
+
+        Ok(reply?)
+    }
+
+    /// Lists the live SSH sessions, of a single account if given.
+    pub async fn sessions(&self, username: Option<&str>) -> Result<Vec<Session>, NodeClientError> {
+        let token = self.token(Scope::UsersRead);
+        self.send(
+            || {
+                self.http
+                    .get(self.url(consts::SESSIONS_PATH))
+                    .query(&[("user", username)])
+                    .bearer_auth(&token)
+            },
+            true,
+        )
+        .await
+    }
+
+    /// Lists the commands recorded in the audit log between `from` and `to` (unix timestamps),
+    /// of a single account if given.
+    pub async fn audit(
+        &self,
+        from: Option<u64>,
+        to: Option<u64>,
+        username: Option<&str>,
+    ) -> Result<Vec<AuditEntry>, NodeClientError> {
+        let token = self.token(Scope::UsersRead);
+        let reply: UserResult<Vec<AuditEntry>> = self
+            .send(
+                || {
+                    self.http
+                        .get(self.url(consts::AUDIT_PATH))
+                        .query(&[("from", from), ("to", to)])
+                        .query(&[("user", username)])
+                        .bearer_auth(&token)
+                },
+                true,
+            )
+            .await?;
+
+        Ok(reply?)
+    }
+
+    /// Kills all sessions of an account, or a single one by PID.
+    pub async fn kick(&self, kick_params: &KickParams) -> Result<KickedSessions, NodeClientError> {
+        self.cmd(consts::KICK_PATH, kick_params, false).await
+    }
+
+    /// Deletes an account.
+    pub async fn userdel(&self, username: &str) -> Result<UserStatus, NodeClientError> {
+        let lookup_params = UserLookupParams {
+            username: Some(username.to_string()),
+            ..Default::default()
+        };
+
+        self.cmd(consts::USERDEL_PATH, &lookup_params, false).await
+    }
+
+    /// Creates an account.
+    pub async fn useradd(&self, user: &InputSSHUser) -> Result<SSHUser, NodeClientError> {
+        self.cmd(consts::USERADD_PATH, user, false).await
+    }
+
+    /// Creates an account named after the prefix and the number of accounts already using it.
+    pub async fn auto_useradd(&self, user: &AutoSSHUser) -> Result<SSHUser, NodeClientError> {
+        self.cmd(consts::AUTO_USERADD_PATH, user, false).await
+    }
+
+    /// Changes the password of an account.
+    pub async fn passwd(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<UserPasswordHash, NodeClientError> {
+        let user = UserPasswd {
+            username: username.to_string(),
+            password: password.to_string(),
+        };
+
+        self.cmd(consts::PASSWD_PATH, &user, false).await
+    }
+
+    /// Resets the password of an account to a random one, returned along with its hash.
+    pub async fn reset_password(&self, username: &str) -> Result<UserRawCreds, NodeClientError> {
+        self.cmd(consts::RESET_PASSWORD_PATH, &only_user(username), false)
+            .await
+    }
+
+    /// Moves an account to another group.
+    pub async fn chgrp(&self, username: &str, group: &str) -> Result<ChGrpMsg, NodeClientError> {
+        let user = UserGrp {
+            username: username.to_string(),
+            group: group.to_string(),
+        };
+
+        self.cmd(consts::CHGRP_PATH, &user, true).await
+    }
+
+    /// Changes the expiry date (`YYYY-MM-DD`) of an account.
+    pub async fn chexp(&self, username: &str, exp_date: &str) -> Result<ChExpMsg, NodeClientError> {
+        let user = UserExpDate {
+            username: username.to_string(),
+            exp_date: exp_date.to_string(),
+        };
+
+        self.cmd(consts::CHEXP_PATH, &user, true).await
+    }
+
+    /// Sets the traffic quota and concurrent login limit of an account.
+    pub async fn chlimits(&self, limits: &UserLimits) -> Result<UserQuota, NodeClientError> {
+        self.cmd(consts::CHLIMITS_PATH, limits, true).await
+    }
+
+    /// Locks an account.
+    pub async fn userlock(&self, username: &str) -> Result<UserStatus, NodeClientError> {
+        self.cmd(consts::USERLOCK_PATH, &only_user(username), true)
+            .await
+    }
+
+    /// Unlocks an account.
+    pub async fn userunlock(&self, username: &str) -> Result<UserStatus, NodeClientError> {
+        self.cmd(consts::USERUNLOCK_PATH, &only_user(username), true)
+            .await
+    }
+
+    /// Creates several accounts.
+    pub async fn bulk_useradd(
+        &self,
+        bulk: &BulkUseradd,
+    ) -> Result<BulkResult<SSHUser>, NodeClientError> {
+        self.command(consts::BULK_USERADD_PATH, bulk, false).await
+    }
+
+    /// Creates `count` accounts named after the prefix.
+    pub async fn bulk_auto_useradd(
+        &self,
+        bulk: &BulkAutoUseradd,
+    ) -> Result<BulkResult<SSHUser>, NodeClientError> {
+        self.command(consts::BULK_AUTO_USERADD_PATH, bulk, false)
+            .await
+    }
+
+    /// Deletes several accounts.
+    pub async fn bulk_userdel(
+        &self,
+        bulk: &BulkUsers,
+    ) -> Result<BulkResult<UserStatus>, NodeClientError> {
+        self.command(consts::BULK_USERDEL_PATH, bulk, false).await
+    }
+
+    /// Locks several accounts.
+    pub async fn bulk_userlock(
+        &self,
+        bulk: &BulkUsers,
+    ) -> Result<BulkResult<UserStatus>, NodeClientError> {
+        self.command(consts::BULK_USERLOCK_PATH, bulk, true).await
+    }
+
+    /// Unlocks several accounts.
+    pub async fn bulk_userunlock(
+        &self,
+        bulk: &BulkUsers,
+    ) -> Result<BulkResult<UserStatus>, NodeClientError> {
+        self.command(consts::BULK_USERUNLOCK_PATH, bulk, true).await
+    }
+
+    /// Changes the expiry date of several accounts.
+    pub async fn bulk_chexp(
+        &self,
+        bulk: &BulkUserExpDate,
+    ) -> Result<BulkResult<ChExpMsg>, NodeClientError> {
+        self.command(consts::BULK_CHEXP_PATH, bulk, true).await
+    }
+
+    /// Changes the group of several accounts.
+    pub async fn bulk_chgrp(
+        &self,
+        bulk: &BulkUserGrp,
+    ) -> Result<BulkResult<ChGrpMsg>, NodeClientError> {
+        self.command(consts::BULK_CHGRP_PATH, bulk, true).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// Mints a token for a single call, bound to this node and carrying the given scope.
+    fn token(&self, scope: Scope) -> String {
+        let unix_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let mut claims = Claims::new(self.node_id, unix_time + consts::TOKEN_TTL, &[scope]);
+        claims.sub = self.subject.clone();
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.jwt_secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        scope: Scope,
+    ) -> Result<T, NodeClientError> {
+        let token = self.token(scope);
+
+        self.send(|| self.http.get(self.url(path)).bearer_auth(&token), true)
+            .await
+    }
+
+    async fn post<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        scope: Scope,
+        body: &B,
+        retry: bool,
+    ) -> Result<T, NodeClientError> {
+        let token = self.token(scope);
+
+        self.send(
+            || {
+                self.http
+                    .post(self.url(path))
+                    .bearer_auth(&token)
+                    .json(body)
+            },
+            retry,
+        )
+        .await
+    }
+
+    /// Sends a command, flattening its result.
+    async fn cmd<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+        retry: bool,
+    ) -> Result<T, NodeClientError> {
+        let reply: UserResult<T> = self.command(path, body, retry).await?;
+
+        Ok(reply?)
+    }
+
+    /// Sends the body of a command, as a dry run if set, in which case the node has to echo it.
+    async fn command<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+        retry: bool,
+    ) -> Result<T, NodeClientError> {
+        let command = Command {
+            params: body,
+            dry_run: self.dry_run,
+        };
+
+        let token = self.token(Scope::UsersWrite);
+
+        self.send_checked(
+            || {
+                self.http
+                    .post(self.url(path))
+                    .bearer_auth(&token)
+                    .json(&command)
+            },
+            retry,
+            self.dry_run,
+        )
+        .await
+    }
+
+    /// Sends the request built by `request`, again after a backoff as long as the node can't
+    /// be reached (or answers with a server error) and `retry` is set.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: impl Fn() -> RequestBuilder,
+        retry: bool,
+    ) -> Result<T, NodeClientError> {
+        self.send_checked(request, retry, false).await
+    }
+
+    /// Sends the request like `send`, successful replies having to echo `dry_run` in the
+    /// `DRY_RUN_HEADER` when it's set.
+    async fn send_checked<T: DeserializeOwned>(
+        &self,
+        request: impl Fn() -> RequestBuilder,
+        retry: bool,
+        dry_run: bool,
+    ) -> Result<T, NodeClientError> {
+        let retries = if retry { self.retry.retries } else { 0 };
+        let mut backoff = self.retry.backoff;
+        let mut attempt = 0;
+
+        loop {
+            let last_attempt = attempt >= retries;
+
+            match request().timeout(self.timeout).send().await {
+                Ok(response) if !last_attempt && response.status().is_server_error() => {}
+                Ok(response) => {
+                    let echoed = response
+                        .headers()
+                        .get(DRY_RUN_HEADER)
+                        .map_or(false, |value| value == "true");
+
+                    let reply = response
+                        .json::<Reply<T>>()
+                        .await
+                        .map_err(NodeClientError::Decode)?
+                        .into_result()
+                        .map_err(NodeClientError::Refused)?;
+
+                    if dry_run && !echoed {
+                        return Err(NodeClientError::DryRunIgnored);
+                    }
+                    return Ok(reply);
+                }
+                Err(err) => {
+                    let err = NodeClientError::Request(err);
+                    if last_attempt || !err.is_transient() {
+                        return Err(err);
+                    }
+                }
+            }
+
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(self.retry.max_backoff);
+            attempt += 1;
+        }
+    }
+}
+
+fn only_user(username: &str) -> OnlyUser {
+    OnlyUser {
+        username: username.to_string(),
+    }
+}
@@ -0,0 +1,38 @@
+#!/bin/sh
+# Generates a local CA along with a node-api server certificate and a centric-api client
+# certificate signed by it, for testing mutual TLS between the two.
+#
+# Usage: gen_test_certs.sh [OUT_DIR] [NODE_HOST]
+set -eu
+
+OUT_DIR="${1:-./test-certs}"
+NODE_HOST="${2:-localhost}"
+DAYS=30
+
+mkdir -p "$OUT_DIR"
+cd "$OUT_DIR"
+
+# Local CA
+openssl genpkey -algorithm RSA -pkeyopt rsa_keygen_bits:2048 -out ca.key 2>/dev/null
+openssl req -x509 -new -key ca.key -sha256 -days "$DAYS" \
+    -subj "/CN=ssh-mgmt-toolkit test CA" -out ca.pem
+
+# node-api server certificate
+openssl genpkey -algorithm RSA -pkeyopt rsa_keygen_bits:2048 -out node.key 2>/dev/null
+openssl req -new -key node.key -subj "/CN=$NODE_HOST" -out node.csr
+printf "subjectAltName=DNS:%s,DNS:localhost,IP:127.0.0.1\nextendedKeyUsage=serverAuth\n" \
+    "$NODE_HOST" > node.ext
+openssl x509 -req -in node.csr -CA ca.pem -CAkey ca.key -CAcreateserial -sha256 \
+    -days "$DAYS" -extfile node.ext -out node.pem 2>/dev/null
+
+# centric-api client certificate
+openssl genpkey -algorithm RSA -pkeyopt rsa_keygen_bits:2048 -out client.key 2>/dev/null
+openssl req -new -key client.key -subj "/CN=centric-api" -out client.csr
+printf "extendedKeyUsage=clientAuth\n" > client.ext
+openssl x509 -req -in client.csr -CA ca.pem -CAkey ca.key -CAcreateserial -sha256 \
+    -days "$DAYS" -extfile client.ext -out client.pem 2>/dev/null
+
+rm -f node.csr node.ext client.csr client.ext ca.srl
+
+echo "Certificates written to $(pwd)"
+echo "node tls_fingerprint: $(openssl x509 -in node.pem -noout -fingerprint -sha256 | cut -d= -f2)"
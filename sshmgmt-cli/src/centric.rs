@@ -0,0 +1,99 @@
+use reqwest::RequestBuilder;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Struct representing a client of centric-api.
+pub struct Centric {
+    http: reqwest::Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl Centric {
+    /// Creates a new client of the centric-api at `url`, authenticating with `token` if given.
+    pub fn new(url: &str, token: Option<String>) -> Result<Self, String> {
+        let http = reqwest::Client::builder()
+            .use_rustls_tls()
+            .timeout(node_client::consts::TIMEOUT * 3)
+            .build()
+            .map_err(|err| format!("couldn't build the HTTP client: {err}"))?;
+
+        Ok(Centric {
+            http,
+            url: url.trim_end_matches('/').to_string(),
+            token,
+        })
+    }
+
+    /// Logs in and returns the issued token.
+    pub async fn login(&self, username: &str, password: &str) -> Result<String, String> {
+        let token = self
+            .post(
+                "/auth/login",
+                &json!({ "username": username, "password": password }),
+            )
+            .await?;
+
+        token
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| "unexpected response from centric-api".to_string())
+    }
+
+    pub async fn get(&self, path: &str) -> Result<Value, String> {
+        self.send(self.http.get(self.url(path))).await
+    }
+
+    pub async fn post<B: Serialize>(&self, path: &str, body: &B) -> Result<Value, String> {
+        self.send(self.http.post(self.url(path)).json(body)).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.url, path)
+    }
+
+    /// Sends a request and unwraps the `{"Ok": ...}` of the response.
+    async fn send(&self, request: RequestBuilder) -> Result<Value, String> {
+        let request = match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+
+        let response = request
+            .send()
+            .await
+            .map_err(|err| format!("couldn't reach centric-api: {err}"))?;
+
+        let status = response.status();
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|_| format!("unexpected response from centric-api ({status})"))?;
+
+        match body {
+            Value::Object(mut body) if body.contains_key("Ok") => Ok(body.remove("Ok").unwrap()),
+            Value::Object(body) if body.contains_key("Err") => Err(describe_err(&body["Err"])),
+            _ => Err(format!("unexpected response from centric-api ({status})")),
+        }
+    }
+}
+
+/// Formats `{"type": ..., "code": ..., "msg": ..., "raw_msg": ...}` errors of centric-api.
+fn describe_err(err: &Value) -> String {
+    let mut description = format!(
+        "{} error {}: {}",
+        err["type"].as_str().unwrap_or("unknown"),
+        err["code"],
+        err["msg"].as_str().unwrap_or_default()
+    );
+
+    if let Some(raw_msg) = err["raw_msg"].as_str() {
+        description.push_str(&format!(" ({raw_msg})"));
+    }
+
+    if err["type"] == "auth" && err["code"] == 401 {
+        description.push_str(", run `sshmgmt login`");
+    }
+
+    description
+}
@@ -0,0 +1,142 @@
+use node_client::NodeClient;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+/// Struct representing `config.json` in the config directory.
+#[derive(Deserialize, Serialize, Default, Debug)]
+pub struct Config {
+    /// Base URL of centric-api, e.g. `https://centric.example.com`.
+    pub centric_url: Option<String>,
+    /// Nodes reachable directly, by name.
+    #[serde(default)]
+    pub nodes: HashMap<String, NodeProfile>,
+}
+
+/// Struct representing how to reach a node-api directly.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct NodeProfile {
+    /// Address of the node, e.g. `https://node01.example.com:8010`.
+    pub address: String,
+    pub node_id: i32,
+    pub jwt_secret: String,
+    /// PEM CA certificate the node's certificate is signed with, the system roots otherwise.
+    pub ca_cert: Option<String>,
+    /// PEM client certificate and key presented to nodes requiring client certificates.
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+}
+
+impl NodeProfile {
+    /// Builds a client of the node, its commands being audited as `sshmgmt:<local user>`.
+    pub fn client(&self) -> Result<NodeClient, String> {
+        let mut builder = node_client::client_builder().use_rustls_tls();
+
+        if let Some(ca_cert) = &self.ca_cert {
+            let cert = reqwest::Certificate::from_pem(&read(ca_cert)?)
+                .map_err(|err| format!("invalid CA certificate in {ca_cert}: {err}"))?;
+            builder = builder.add_root_certificate(cert);
+        }
+
+        match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => {
+                let mut pem = read(cert)?;
+                pem.extend(read(key)?);
+
+                let identity = reqwest::Identity::from_pem(&pem)
+                    .map_err(|err| format!("invalid client certificate or key: {err}"))?;
+                builder = builder.identity(identity);
+            }
+            (None, None) => {}
+            _ => return Err("client_cert and client_key should be set together".to_string()),
+        }
+
+        let http = builder
+            .build()
+            .map_err(|err| format!("couldn't build the HTTP client: {err}"))?;
+
+        let subject = match std::env::var("USER") {
+            Ok(user) => format!("sshmgmt:{user}"),
+            Err(_) => "sshmgmt".to_string(),
+        };
+
+        Ok(
+            NodeClient::new(http, &self.address, self.node_id, &self.jwt_secret)
+                .with_subject(subject),
+        )
+    }
+}
+
+/// Struct representing the cached token of a centric-api login.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CachedToken {
+    /// centric-api the token was issued by.
+    pub url: String,
+    pub token: String,
+}
+
+/// Returns the default config directory, `~/.config/sshmgmt` on Linux.
+pub fn default_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("sshmgmt")
+}
+
+/// Loads `config.json` from the config directory, an empty config if it doesn't exist.
+pub fn load(dir: &Path) -> Result<Config, String> {
+    let path = dir.join("config.json");
+
+    match fs::read_to_string(&path) {
+        Ok(config) => serde_json::from_str(&config)
+            .map_err(|err| format!("invalid config in {}: {err}", path.display())),
+        Err(_) => Ok(Config::default()),
+    }
+}
+
+/// Loads the cached token, as long as it was issued by the given centric-api.
+pub fn load_token(dir: &Path, url: &str) -> Option<String> {
+    let cached = fs::read_to_string(dir.join("token.json")).ok()?;
+    let cached: CachedToken = serde_json::from_str(&cached).ok()?;
+
+    (cached.url == url).then_some(cached.token)
+}
+
+/// Caches the token of a login, readable only by the current user.
+pub fn save_token(dir: &Path, url: &str, token: &str) -> Result<(), String> {
+    let cached = CachedToken {
+        url: url.to_string(),
+        token: token.to_string(),
+    };
+
+    fs::create_dir_all(dir).map_err(|err| format!("couldn't create {}: {err}", dir.display()))?;
+
+    let path = dir.join("token.json");
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&path)
+        .map_err(|err| format!("couldn't write {}: {err}", path.display()))?;
+
+    file.write_all(serde_json::to_string(&cached).unwrap().as_bytes())
+        .map_err(|err| format!("couldn't write {}: {err}", path.display()))
+}
+
+/// Removes the cached token.
+pub fn remove_token(dir: &Path) -> Result<(), String> {
+    let path = dir.join("token.json");
+
+    match fs::remove_file(&path) {
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(format!("couldn't remove {}: {err}", path.display())),
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|err| format!("couldn't open {path}: {err}"))
+}
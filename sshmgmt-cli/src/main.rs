@@ -0,0 +1,751 @@
+use api_types::v1::requests::{InputSSHUser, UserLookupParams};
+use clap::{Args, Parser, Subcommand};
+use node_client::NodeClient;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+mod centric;
+mod config;
+mod output;
+
+use centric::Centric;
+use output::{print_record, print_rows, Format};
+
+const DEFAULT_CENTRIC_URL: &str = "http://127.0.0.1:8000";
+
+const NODE_COLUMNS: &[&str] = &[
+    "id",
+    "name",
+    "location",
+    "address",
+    "status",
+    "latency_ms",
+    "last_seen",
+    "approved",
+    "capacity",
+];
+const SERVICE_COLUMNS: &[&str] = &["id", "max_logins", "max_traffic", "price", "available"];
+const CUSTOMER_COLUMNS: &[&str] = &["id", "ref_id", "register_date"];
+const SELL_COLUMNS: &[&str] = &[
+    "id",
+    "user_id",
+    "ref_id",
+    "service_id",
+    "node_id",
+    "username",
+    "status",
+    "invoice_date",
+];
+const RENEWAL_COLUMNS: &[&str] = &[
+    "id",
+    "sell_id",
+    "service_id",
+    "days",
+    "price",
+    "invoice_date",
+];
+const ENROLLMENT_CODE_COLUMNS: &[&str] = &["id", "created_at", "expires_at", "used_at", "node_id"];
+const NET_STATS_COLUMNS: &[&str] = &["interface", "tx", "rx", "total"];
+const METRICS_COLUMNS: &[&str] = &[
+    "timestamp",
+    "load_one",
+    "load_five",
+    "load_fifteen",
+    "memory_used",
+    "memory_total",
+    "swap_used",
+    "disk_used",
+    "disk_total",
+];
+const NET_METRICS_COLUMNS: &[&str] = &["interface", "timestamp", "rx", "tx", "rx_rate", "tx_rate"];
+const SESSION_COLUMNS: &[&str] = &["username", "pid", "source_ip", "started_at"];
+const AUDIT_COLUMNS: &[&str] = &[
+    "timestamp",
+    "subject",
+    "route",
+    "username",
+    "dry_run",
+    "result",
+];
+
+/// Manages the nodes, services, customers and sells of ssh-mgmt-toolkit through centric-api,
+/// and the SSH accounts of nodes directly through their node-api.
+#[derive(Parser)]
+#[command(name = "sshmgmt", version)]
+struct Cli {
+    /// Base URL of centric-api, `centric_url` of the config otherwise
+    #[arg(long, env = "SSHMGMT_URL", global = true)]
+    url: Option<String>,
+
+    /// Directory of `config.json` and the cached token
+    #[arg(long, env = "SSHMGMT_CONFIG_DIR", global = true)]
+    config_dir: Option<PathBuf>,
+
+    /// Prints responses as JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Logs in to centric-api and caches the token
+    Login {
+        #[arg(long, short)]
+        username: String,
+        /// Prompted for when not given
+        #[arg(long, env = "SSHMGMT_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+    },
+    /// Removes the cached token
+    Logout,
+    /// Nodes registered in centric-api
+    #[command(subcommand)]
+    Node(NodeCommand),
+    /// Services sold by centric-api
+    #[command(subcommand)]
+    Service(ServiceCommand),
+    /// Customers (`/users`) of centric-api
+    #[command(subcommand)]
+    Customer(CustomerCommand),
+    /// Sells of centric-api
+    #[command(subcommand)]
+    Sell(SellCommand),
+    /// SSH accounts of a node, managed directly through its node-api
+    User(UserArgs),
+}
+
+#[derive(Subcommand)]
+enum NodeCommand {
+    /// Lists the nodes
+    List,
+    /// Shows a node
+    Show { node_id: i32 },
+    /// Adds a node, approved right away
+    Add {
+        /// Address of the node, e.g. `https://node01.example.com:8010`
+        #[arg(long)]
+        address: String,
+        /// Key the node verifies tokens with, at least 32 characters
+        #[arg(long, env = "SSHMGMT_NODE_SECRET", hide_env_values = true)]
+        jwt_secret: String,
+        /// SHA-256 fingerprint of the node's TLS certificate
+        #[arg(long)]
+        tls_fingerprint: Option<String>,
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        location: Option<String>,
+        /// Number of accounts the node is meant to hold
+        #[arg(long)]
+        capacity: Option<i64>,
+    },
+    /// Approves an enrolled node
+    Approve { node_id: i32 },
+    /// Deletes a node
+    Delete { node_id: i32 },
+    /// Fetches the name, location and capacity reported by a node
+    Info { node_id: i32 },
+    /// Fetches the hardware statistics of a node
+    Stats {
+        node_id: i32,
+        /// Fetches the network statistics instead
+        #[arg(long)]
+        net: bool,
+    },
+    /// Fetches the recorded statistics of a node, averaged over steps
+    Metrics {
+        node_id: i32,
+        /// Unix timestamp of the first step, a day ago by default
+        #[arg(long)]
+        from: Option<u64>,
+        /// Unix timestamp of the end, now by default
+        #[arg(long)]
+        to: Option<u64>,
+        /// Seconds per step, the collection interval by default
+        #[arg(long)]
+        step: Option<u64>,
+        /// Fetches the interface counters instead
+        #[arg(long)]
+        net: bool,
+    },
+    /// Creates a one-time enrollment code
+    EnrollmentCode {
+        /// Seconds the code stays valid, a day by default
+        #[arg(long)]
+        ttl: Option<u64>,
+    },
+    /// Lists the enrollment codes
+    EnrollmentCodes,
+}
+
+#[derive(Subcommand)]
+enum ServiceCommand {
+    /// Lists the services
+    List,
+    /// Shows a service
+    Show { service_id: i32 },
+    /// Adds a service
+    Add {
+        #[arg(long)]
+        max_logins: i32,
+        /// Traffic quota in GiB, unlimited when not given
+        #[arg(long)]
+        max_traffic: Option<i32>,
+        #[arg(long)]
+        price: i32,
+    },
+}
+
+#[derive(Subcommand)]
+enum CustomerCommand {
+    /// Lists the customers
+    List,
+    /// Adds a customer
+    Add {
+        customer_id: i64,
+        /// Customer who referred this one
+        #[arg(long = "ref")]
+        ref_id: Option<i64>,
+    },
+    /// Lists the customers referred by a customer
+    Refs { customer_id: i64 },
+}
+
+#[derive(Subcommand)]
+enum SellCommand {
+    /// Lists the sells, of a single referrer or customer if given
+    List {
+        /// Lists the sells referred by this customer
+        #[arg(long = "ref", conflicts_with = "customer")]
+        ref_id: Option<i64>,
+        /// Lists the sells of this customer
+        #[arg(long)]
+        customer: Option<i64>,
+    },
+    /// Shows a sell
+    Show { sell_id: i32 },
+    /// Creates an unverified sell
+    New {
+        #[arg(long)]
+        customer: i64,
+        #[arg(long)]
+        service: i32,
+        #[arg(long)]
+        node: i32,
+        #[arg(long = "ref")]
+        ref_id: Option<i64>,
+    },
+    /// Verifies a sell, creating its account on the node
+    Verify {
+        sell_id: i32,
+        /// 30 by default
+        #[arg(long)]
+        days: Option<i64>,
+    },
+    /// Renews a verified sell
+    Renew {
+        sell_id: i32,
+        /// 30 by default
+        #[arg(long)]
+        days: Option<i64>,
+        /// Moves the sell to another service
+        #[arg(long)]
+        service: Option<i32>,
+    },
+    /// Resets the password of a sell's account
+    ResetPassword { sell_id: i32 },
+    /// Lists the renewals of a sell
+    Renewals { sell_id: i32 },
+}
+
+#[derive(Args)]
+struct UserArgs {
+    /// Name of the node in the `nodes` of the config
+    #[arg(long, short)]
+    node: String,
+
+    /// Only validates commands, showing what they would change
+    #[arg(long)]
+    dry_run: bool,
+
+    #[command(subcommand)]
+    command: UserCommand,
+}
+
+#[derive(Subcommand)]
+enum UserCommand {
+    /// Lists the accounts matching a prefix or group
+    List {
+        #[arg(long, required_unless_present = "group")]
+        prefix: Option<String>,
+        #[arg(long)]
+        group: Option<String>,
+    },
+    /// Creates an account
+    Add {
+        username: String,
+        /// Expiry date, `YYYY-MM-DD`
+        #[arg(long)]
+        exp_date: String,
+        #[arg(long)]
+        group: String,
+        /// Prompted for when not given
+        #[arg(long, env = "SSHMGMT_USER_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+        #[arg(long)]
+        shell: Option<String>,
+        /// Traffic quota in bytes
+        #[arg(long)]
+        traffic_quota: Option<u64>,
+        /// Maximum concurrent SSH sessions
+        #[arg(long)]
+        max_logins: Option<u32>,
+    },
+    /// Deletes an account
+    Del { username: String },
+    /// Locks an account
+    Lock { username: String },
+    /// Unlocks an account
+    Unlock { username: String },
+    /// Changes the expiry date of an account
+    Chexp {
+        username: String,
+        /// `YYYY-MM-DD`
+        exp_date: String,
+    },
+    /// Shows the traffic quota of an account
+    Quota { username: String },
+    /// Lists the live SSH sessions, of a single account if given
+    Sessions { username: Option<String> },
+    /// Lists the commands recorded in the audit log of the node, of a single account if given
+    Audit {
+        username: Option<String>,
+        /// Unix timestamp of the oldest entry
+        #[arg(long)]
+        from: Option<u64>,
+        /// Unix timestamp of the newest entry
+        #[arg(long)]
+        to: Option<u64>,
+    },
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), String> {
+    let config_dir = cli.config_dir.unwrap_or_else(config::default_dir);
+    let config = config::load(&config_dir)?;
+
+    let format = if cli.json {
+        Format::Json
+    } else {
+        Format::Table
+    };
+    let url = cli
+        .url
+        .or(config.centric_url.clone())
+        .unwrap_or_else(|| DEFAULT_CENTRIC_URL.to_string());
+
+    let centric = || -> Result<Centric, String> {
+        let token = config::load_token(&config_dir, &url)
+            .ok_or("not logged in, run `sshmgmt login`".to_string())?;
+
+        Centric::new(&url, Some(token))
+    };
+
+    match cli.command {
+        Command::Login { username, password } => {
+            let password = match password {
+                Some(password) => password,
+                None => rpassword::prompt_password("Password: ")
+                    .map_err(|err| format!("couldn't read the password: {err}"))?,
+            };
+
+            let token = Centric::new(&url, None)?
+                .login(&username, &password)
+                .await?;
+            config::save_token(&config_dir, &url, &token)?;
+
+            eprintln!("Logged in to {url} as {username}");
+        }
+        Command::Logout => config::remove_token(&config_dir)?,
+        Command::Node(command) => node(&centric()?, command, format).await?,
+        Command::Service(command) => service(&centric()?, command, format).await?,
+        Command::Customer(command) => customer(&centric()?, command, format).await?,
+        Command::Sell(command) => sell(&centric()?, command, format).await?,
+        Command::User(args) => {
+            let profile = config.nodes.get(&args.node).ok_or(format!(
+                "no node named {} in {}",
+                args.node,
+                config_dir.join("config.json").display()
+            ))?;
+
+            let node = profile.client()?.with_dry_run(args.dry_run);
+            user(node, args.command, format).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn node(centric: &Centric, command: NodeCommand, format: Format) -> Result<(), String> {
+    match command {
+        NodeCommand::List => print_rows(
+            &centric.get("/nodes/nodes_list").await?,
+            NODE_COLUMNS,
+            format,
+        ),
+        NodeCommand::Show { node_id } => print_record(
+            &centric.get(&format!("/nodes/get_node/{node_id}")).await?,
+            format,
+        ),
+        NodeCommand::Add {
+            address,
+            jwt_secret,
+            tls_fingerprint,
+            name,
+            location,
+            capacity,
+        } => {
+            let node_info = json!({
+                "address": address,
+                "jwt_secret": jwt_secret,
+                "tls_fingerprint": tls_fingerprint,
+                "name": name,
+                "location": location,
+                "capacity": capacity,
+            });
+
+            print_rows(
+                &centric.post("/nodes/new_node", &node_info).await?,
+                NODE_COLUMNS,
+                format,
+            )
+        }
+        NodeCommand::Approve { node_id } => print_rows(
+            &centric
+                .post(&format!("/nodes/approve_node/{node_id}"), &())
+                .await?,
+            NODE_COLUMNS,
+            format,
+        ),
+        NodeCommand::Delete { node_id } => print_record(
+            &centric
+                .post(&format!("/nodes/delete_node/{node_id}"), &())
+                .await?,
+            format,
+        ),
+        NodeCommand::Info { node_id } => print_record(
+            &centric.get(&format!("/nodes/node_info/{node_id}")).await?,
+            format,
+        ),
+        NodeCommand::Stats { node_id, net: true } => print_rows(
+            &centric.get(&format!("/nodes/net_stats/{node_id}")).await?,
+            NET_STATS_COLUMNS,
+            format,
+        ),
+        NodeCommand::Stats {
+            node_id,
+            net: false,
+        } => print_record(
+            &centric.get(&format!("/nodes/hw_stats/{node_id}")).await?,
+            format,
+        ),
+        NodeCommand::Metrics {
+            node_id,
+            from,
+            to,
+            step,
+            net,
+        } => {
+            let query: Vec<String> = [("from", from), ("to", to), ("step", step)]
+                .into_iter()
+                .filter_map(|(key, value)| value.map(|value| format!("{key}={value}")))
+                .collect();
+            let metrics = centric
+                .get(&format!("/nodes/metrics/{node_id}?{}", query.join("&")))
+                .await?;
+
+            if net {
+                let points: Vec<Value> = metrics["interfaces"]
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|(interface, points)| {
+                        points.as_array().into_iter().flatten().map(move |point| {
+                            let mut point = point.clone();
+                            point["interface"] = json!(interface);
+                            point
+                        })
+                    })
+                    .collect();
+
+                print_rows(&Value::Array(points), NET_METRICS_COLUMNS, format)
+            } else {
+                print_rows(&metrics["samples"], METRICS_COLUMNS, format)
+            }
+        }
+        NodeCommand::EnrollmentCode { ttl } => {
+            let path = match ttl {
+                Some(ttl) => format!("/nodes/new_enrollment_code?ttl={ttl}"),
+                None => "/nodes/new_enrollment_code".to_string(),
+            };
+
+            print_record(&centric.post(&path, &()).await?, format)
+        }
+        NodeCommand::EnrollmentCodes => print_rows(
+            &centric.get("/nodes/enrollment_codes").await?,
+            ENROLLMENT_CODE_COLUMNS,
+            format,
+        ),
+    }
+
+    Ok(())
+}
+
+async fn service(centric: &Centric, command: ServiceCommand, format: Format) -> Result<(), String> {
+    match command {
+        ServiceCommand::List => print_rows(
+            &centric.get("/services/services_list").await?,
+            SERVICE_COLUMNS,
+            format,
+        ),
+        ServiceCommand::Show { service_id } => print_rows(
+            &centric
+                .get(&format!("/services/get_service/{service_id}"))
+                .await?,
+            SERVICE_COLUMNS,
+            format,
+        ),
+        ServiceCommand::Add {
+            max_logins,
+            max_traffic,
+            price,
+        } => {
+            let service_info = json!({
+                "max_logins": max_logins,
+                "max_traffic": max_traffic,
+                "price": price,
+            });
+
+            print_rows(
+                &centric.post("/services/new_service", &service_info).await?,
+                SERVICE_COLUMNS,
+                format,
+            )
+        }
+    }
+
+    Ok(())
+}
+
+async fn customer(
+    centric: &Centric,
+    command: CustomerCommand,
+    format: Format,
+) -> Result<(), String> {
+    match command {
+        CustomerCommand::List => print_rows(
+            &centric.get("/users/users_list").await?,
+            CUSTOMER_COLUMNS,
+            format,
+        ),
+        CustomerCommand::Add {
+            customer_id,
+            ref_id,
+        } => print_rows(
+            &centric
+                .post(
+                    "/users/new_user",
+                    &json!({ "id": customer_id, "ref_id": ref_id }),
+                )
+                .await?,
+            CUSTOMER_COLUMNS,
+            format,
+        ),
+        CustomerCommand::Refs { customer_id } => print_rows(
+            &centric
+                .get(&format!("/users/user_refs/{customer_id}"))
+                .await?,
+            CUSTOMER_COLUMNS,
+            format,
+        ),
+    }
+
+    Ok(())
+}
+
+async fn sell(centric: &Centric, command: SellCommand, format: Format) -> Result<(), String> {
+    match command {
+        SellCommand::List { ref_id, customer } => {
+            let path = match (ref_id, customer) {
+                (Some(ref_id), _) => format!("/sells/sells_list_by_ref/{ref_id}"),
+                (None, Some(customer)) => format!("/sells/sells_list_by_user/{customer}"),
+                (None, None) => "/sells/sells_list".to_string(),
+            };
+
+            print_rows(&centric.get(&path).await?, SELL_COLUMNS, format)
+        }
+        SellCommand::Show { sell_id } => print_record(
+            &centric.get(&format!("/sells/sell_info/{sell_id}")).await?,
+            format,
+        ),
+        SellCommand::New {
+            customer,
+            service,
+            node,
+            ref_id,
+        } => {
+            let sell_info = json!({
+                "user_id": customer,
+                "ref_id": ref_id,
+                "service_id": service,
+                "node_id": node,
+            });
+
+            print_rows(
+                &centric.post("/sells/new_sell", &sell_info).await?,
+                SELL_COLUMNS,
+                format,
+            )
+        }
+        SellCommand::Verify { sell_id, days } => print_record(
+            &centric
+                .post(
+                    &format!("/sells/verify_sell/{sell_id}"),
+                    &json!({ "days": days }),
+                )
+                .await?,
+            format,
+        ),
+        SellCommand::Renew {
+            sell_id,
+            days,
+            service,
+        } => print_rows(
+            &centric
+                .post(
+                    &format!("/sells/renew/{sell_id}"),
+                    &json!({ "days": days, "service_id": service }),
+                )
+                .await?,
+            SELL_COLUMNS,
+            format,
+        ),
+        SellCommand::ResetPassword { sell_id } => {
+            let reset = centric
+                .post(&format!("/sells/reset_password/{sell_id}"), &())
+                .await?;
+
+            match format {
+                Format::Json => print_record(&reset, format),
+                Format::Table => {
+                    print_rows(&reset["sell"], SELL_COLUMNS, format);
+                    println!("\nPassword: {}", reset["password"].as_str().unwrap_or("-"));
+                }
+            }
+        }
+        SellCommand::Renewals { sell_id } => print_rows(
+            &centric
+                .get(&format!("/sells/renewals_list_by_sell/{sell_id}"))
+                .await?,
+            RENEWAL_COLUMNS,
+            format,
+        ),
+    }
+
+    Ok(())
+}
+
+async fn user(node: NodeClient, command: UserCommand, format: Format) -> Result<(), String> {
+    let reply = match command {
+        UserCommand::List { prefix, group } => {
+            let lookup_params = UserLookupParams {
+                prefix,
+                group,
+                ..Default::default()
+            };
+
+            to_value(node.list_users(&lookup_params).await)?
+        }
+        UserCommand::Add {
+            username,
+            exp_date,
+            group,
+            password,
+            shell,
+            traffic_quota,
+            max_logins,
+        } => {
+            let password = match password {
+                Some(password) => password,
+                None => rpassword::prompt_password("Password: ")
+                    .map_err(|err| format!("couldn't read the password: {err}"))?,
+            };
+
+            let user = InputSSHUser {
+                username,
+                password,
+                exp_date,
+                group,
+                shell,
+                traffic_quota,
+                max_logins,
+            };
+
+            to_value(node.useradd(&user).await)?
+        }
+        UserCommand::Del { username } => to_value(node.userdel(&username).await)?,
+        UserCommand::Lock { username } => to_value(node.userlock(&username).await)?,
+        UserCommand::Unlock { username } => to_value(node.userunlock(&username).await)?,
+        UserCommand::Chexp { username, exp_date } => {
+            to_value(node.chexp(&username, &exp_date).await)?
+        }
+        UserCommand::Quota { username } => to_value(node.user_quota(&username).await)?,
+        UserCommand::Sessions { username } => {
+            let sessions = to_value(node.sessions(username.as_deref()).await)?;
+
+            print_rows(&sessions, SESSION_COLUMNS, format);
+            return Ok(());
+        }
+        UserCommand::Audit { username, from, to } => {
+            let entries = to_value(node.audit(from, to, username.as_deref()).await)?;
+
+            print_rows(&entries, AUDIT_COLUMNS, format);
+            return Ok(());
+        }
+    };
+
+    match reply {
+        Value::Array(usernames) if format == Format::Table => {
+            usernames
+                .iter()
+                .for_each(|username| println!("{}", username.as_str().unwrap_or_default()));
+        }
+        reply => print_record(&reply, format),
+    }
+
+    Ok(())
+}
+
+fn to_value<T: serde::Serialize>(
+    reply: Result<T, node_client::NodeClientError>,
+) -> Result<Value, String> {
+    reply
+        .map(|reply| serde_json::to_value(reply).unwrap())
+        .map_err(|err| err.to_string())
+}
@@ -0,0 +1,97 @@
+use chrono::DateTime;
+use serde_json::Value;
+
+/// Enum representing how responses are printed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+/// Prints a list of records, one row per record with the given columns.
+pub fn print_rows(value: &Value, columns: &[&str], format: Format) {
+    if format == Format::Json {
+        return print_json(value);
+    }
+
+    let records = match value {
+        Value::Array(records) => records.iter().collect(),
+        record => vec![record],
+    };
+
+    let rows: Vec<Vec<String>> = records
+        .iter()
+        .map(|record| columns.iter().map(|column| cell(&record[column])).collect())
+        .collect();
+
+    print_table(columns, &rows);
+}
+
+/// Prints a single record, one row per field.
+pub fn print_record(value: &Value, format: Format) {
+    if format == Format::Json {
+        return print_json(value);
+    }
+
+    match value {
+        Value::Object(fields) => {
+            let rows: Vec<Vec<String>> = fields
+                .iter()
+                .map(|(key, value)| vec![key.clone(), cell(value)])
+                .collect();
+
+            print_table(&["field", "value"], &rows);
+        }
+        value => println!("{}", cell(value)),
+    }
+}
+
+fn print_json(value: &Value) {
+    println!("{}", serde_json::to_string_pretty(value).unwrap());
+}
+
+fn print_table(columns: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = columns.iter().map(|column| column.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: Vec<String>| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+
+        println!("{}", line.join("  ").trim_end());
+    };
+
+    line(columns.iter().map(|column| column.to_uppercase()).collect());
+    for row in rows {
+        line(row.clone());
+    }
+}
+
+/// Renders a field as a table cell: dates as `YYYY-MM-DD HH:MM` (UTC), sizes with their
+/// `pretty` string and missing values as `-`.
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(value) => value.clone(),
+        Value::Array(values) => values.iter().map(cell).collect::<Vec<_>>().join(", "),
+        Value::Object(fields) => {
+            if let Some(secs) = fields.get("secs_since_epoch").and_then(Value::as_i64) {
+                DateTime::from_timestamp(secs, 0)
+                    .map(|date| date.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|| secs.to_string())
+            } else if let Some(pretty) = fields.get("pretty").and_then(Value::as_str) {
+                pretty.to_string()
+            } else {
+                value.to_string()
+            }
+        }
+        value => value.to_string(),
+    }
+}